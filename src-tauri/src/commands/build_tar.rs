@@ -1,38 +1,398 @@
+use crate::commands::error::CommandError;
+use crate::commands::fs_extra::FilePermissions;
+use crate::commands::ignore_rules::load_rules;
+use crate::commands::sync::in_git_dir;
+use crate::commands::upload_policy::{PolicyReport, UploadPolicy};
+use crate::operations::{CancelToken, OperationRegistry};
+use crate::utils::compression::{Codec, Compressor, Level, Preset};
+use crate::utils::ignore::IgnoreRules;
+use crate::utils::parallel::run_blocking;
+use crate::utils::path_guard::PathGuard;
+use crate::utils::progress::ProgressReporter;
 use crate::utils::tee_writer::TeeWriter;
-use brotli::CompressorWriter;
 use data_encoding::HEXLOWER;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::fs;
 use std::fs::File;
-use std::path::Path;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tauri::State;
 
+/// Modification time of the entries of deterministic archives, 1980-01-01 like in reproducible
+/// zip files, as some tools take a zero timestamp for a missing one.
+const DETERMINISTIC_MTIME: u64 = 315_532_800;
+
+/// How `build_tar` writes archives.
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TarOptions {
+    /// Sort the entries and normalize their metadata, so equal content yields an equal hash on
+    /// every machine: fixed mtime, owner 0 without names, mode `644` or `755` for executables and
+    /// plain ustar headers without access or change times.
+    pub deterministic: bool,
+    /// Chosen by the size of the files if left out, see [`Codec::adaptive`].
+    pub codec: Option<Codec>,
+    /// `balanced` if left out.
+    pub level: Option<Level>,
+}
+
+impl TarOptions {
+    /// Codec and level for archiving `total_size` bytes.
+    pub fn compression(&self, total_size: u64) -> (Codec, u32) {
+        let codec = self.codec.unwrap_or_else(|| Codec::adaptive(total_size));
+        let level = self.level.unwrap_or(Level::Preset(Preset::Balanced));
+        (codec, level.resolve(codec))
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveInfo {
+    /// The hash of the uncompressed archive.
+    pub tar_hash: String,
+    pub codec: Codec,
+    pub level: u32,
+    /// The `Content-Encoding` to upload the archive with, `None` if it is not compressed.
+    pub encoding: Option<&'static str>,
+    /// Requested files that were left out, see [`TarEntries`].
+    pub ignored: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "_tag", content = "value", rename_all = "camelCase")]
+pub enum TarOutcome {
+    Archived(ArchiveInfo),
+    /// Nothing was written, as the files violate the upload policy.
+    Rejected(PolicyReport),
+}
+
+/// Compression runs on a worker thread. Progress counts the bytes of the added files and is
+/// emitted on `progress_channel`, if given. A cancelled or failed archive is deleted.
+/// Files ignored by the rules of the project are left out and reported. The remaining ones are
+/// checked against `policy` first, so an upload that would be refused costs no compression.
+#[allow(clippy::too_many_arguments)]
 #[tauri::command]
-pub fn build_tar(
+pub async fn build_tar(
+    app_handle: tauri::AppHandle,
+    operations: State<'_, OperationRegistry>,
     file_name: String,
+    project_id: String,
     root_dir: String,
     files: Vec<String>,
-) -> Result<String, String> {
-    let file =
-        File::create(file_name).map_err(|e| format!("Could not create archive file: {e}"))?;
+    policy: Option<UploadPolicy>,
+    options: Option<TarOptions>,
+    progress_channel: Option<String>,
+    operation_id: Option<String>,
+) -> Result<TarOutcome, CommandError> {
+    let guard = PathGuard::from_app(&app_handle).with_temp_dir();
+    let file_name = guard.check(Path::new(&file_name))?;
+    let root_dir = guard.check(Path::new(&root_dir))?;
+    for x in &files {
+        guard.check_relative(&root_dir, x)?;
+    }
+    let rules = load_rules(&app_handle, &project_id, &root_dir)?;
+    let progress = ProgressReporter::for_channel(&app_handle, progress_channel)?;
+    let operation = operations.start(operation_id)?;
+    let cancel = operation.token();
+    let policy = policy.unwrap_or_default();
+    let options = options.unwrap_or_default();
+    run_blocking(move || {
+        let TarEntries { entries, ignored } = tar_entries(&root_dir, &files, &rules);
+        let report = policy.check(&entries)?;
+        if !report.violations.is_empty() {
+            return Ok(TarOutcome::Rejected(report));
+        }
+        write_tar_entries(&file_name, &entries, &options, &progress, &cancel)
+            .map(|info| TarOutcome::Archived(ArchiveInfo { ignored, ..info }))
+    })
+    .await
+}
+
+pub fn write_tar(
+    file_name: &Path,
+    root_dir: &Path,
+    files: &[String],
+    rules: &IgnoreRules,
+    options: &TarOptions,
+    progress: &ProgressReporter,
+    cancel: &CancelToken,
+) -> Result<ArchiveInfo, CommandError> {
+    let TarEntries { entries, ignored } = tar_entries(root_dir, files, rules);
+    write_tar_entries(file_name, &entries, options, progress, cancel)
+        .map(|info| ArchiveInfo { ignored, ..info })
+}
+
+/// The requested files of an archive, split into those to archive and those left out.
+pub struct TarEntries {
+    /// Project path and file to read.
+    pub entries: Vec<(String, PathBuf)>,
+    /// Project paths of the files that are ignored or lie in the repository of the project
+    /// history, which is never uploaded.
+    pub ignored: Vec<String>,
+}
+
+pub fn tar_entries(root_dir: &Path, files: &[String], rules: &IgnoreRules) -> TarEntries {
+    let (ignored, kept): (Vec<_>, Vec<_>) = files
+        .iter()
+        .partition(|x| in_git_dir(x) || rules.is_ignored(x, false));
+    TarEntries {
+        entries: kept
+            .into_iter()
+            .map(|x| (x.clone(), root_dir.join(x)))
+            .collect(),
+        ignored: ignored.into_iter().cloned().collect(),
+    }
+}
+
+/// Like [`write_tar`], with each entry given as its name in the archive and the file to read.
+pub fn write_tar_entries(
+    file_name: &Path,
+    entries: &[(String, PathBuf)],
+    options: &TarOptions,
+    progress: &ProgressReporter,
+    cancel: &CancelToken,
+) -> Result<ArchiveInfo, CommandError> {
+    let result = cancel.map_result(append_all(file_name, entries, options, progress, cancel));
+    if result.is_err() {
+        if let Err(e) = fs::remove_file(file_name) {
+            eprintln!("Could not remove partial archive: {e}");
+        }
+    }
+    result
+}
+
+fn append_all(
+    file_name: &Path,
+    entries: &[(String, PathBuf)],
+    options: &TarOptions,
+    progress: &ProgressReporter,
+    cancel: &CancelToken,
+) -> Result<ArchiveInfo, CommandError> {
+    let file = File::create(file_name)
+        .map_err(|e| CommandError::io("Could not create archive file", file_name, e))?;
+    stream_tar(file, file_name, entries, options, progress, cancel).map(|(_, info)| info)
+}
+
+/// Write the compressed archive of `entries` to `writer`, which is returned once the compressed
+/// stream is complete. Errors of the writer are reported for `target`.
+pub fn stream_tar<W: Write>(
+    writer: W,
+    target: &Path,
+    entries: &[(String, PathBuf)],
+    options: &TarOptions,
+    progress: &ProgressReporter,
+    cancel: &CancelToken,
+) -> Result<(W, ArchiveInfo), CommandError> {
+    let total = entries_size(entries);
+    progress.set_total(total);
 
-    let brotli = CompressorWriter::new(file, 4096, 11, 20);
+    let (codec, level) = options.compression(total);
+    let compressor = Compressor::new(writer, codec, level)
+        .map_err(|e| CommandError::io("Could not create archive file", target, e))?;
     let hasher = Sha256::new();
-    let tee = TeeWriter::new(brotli, hasher);
+    let tee = TeeWriter::new(compressor, hasher);
 
     let mut archive = tar::Builder::new(tee);
-    archive.follow_symlinks(true);
 
-    let root_dir = Path::new(&root_dir);
-    for x in files {
-        let abs_path = root_dir.join(&x);
-        eprintln!("adding file '{}' with name '{}'", abs_path.display(), &x);
-        archive
-            .append_path_with_name(&abs_path, &x)
-            .map_err(|e| format!("Could not add file to archive: {e}"))?;
+    let mut entries: Vec<_> = entries.iter().collect();
+    if options.deterministic {
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+    }
+    for (x, abs_path) in entries {
+        cancel.check()?;
+        progress.start_file(x);
+        append_path(&mut archive, abs_path, x, options, progress, cancel)
+            .map_err(|e| CommandError::io("Could not add file to archive", abs_path, e))?;
     }
 
     let tee = archive
         .into_inner()
-        .map_err(|e| format!("Could not close archive: {e}"))?;
-    let (_, hasher) = tee.into_inner();
-    Ok(HEXLOWER.encode(hasher.finalize().as_ref()))
+        .map_err(|e| CommandError::io("Could not close archive", target, e))?;
+    let (compressor, hasher) = tee.into_inner();
+    let writer = compressor
+        .finish()
+        .map_err(|e| CommandError::io("Could not close archive", target, e))?;
+    progress.finish();
+    Ok((
+        writer,
+        ArchiveInfo {
+            tar_hash: HEXLOWER.encode(hasher.finalize().as_ref()),
+            codec,
+            level,
+            encoding: codec.encoding(),
+            ignored: Vec::new(),
+        },
+    ))
+}
+
+/// Size of the files of `entries`, by which the codec is chosen.
+pub fn entries_size(entries: &[(String, PathBuf)]) -> u64 {
+    entries
+        .iter()
+        .filter_map(|(_, abs_path)| fs::metadata(abs_path).ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Like `Builder::append_path_with_name` with symlinks followed, but counting the bytes read.
+fn append_path<W: Write>(
+    archive: &mut tar::Builder<W>,
+    abs_path: &Path,
+    name: &str,
+    options: &TarOptions,
+    progress: &ProgressReporter,
+    cancel: &CancelToken,
+) -> io::Result<()> {
+    let metadata = fs::metadata(abs_path)?;
+    if options.deterministic {
+        let mut header = deterministic_header(&metadata);
+        if metadata.is_dir() {
+            return archive.append_data(&mut header, name, io::empty());
+        }
+        let file = cancel.reader(File::open(abs_path)?);
+        return archive.append_data(&mut header, name, progress.reader(file));
+    }
+    if metadata.is_dir() {
+        return archive.append_dir(name, abs_path);
+    }
+    let mut header = tar::Header::new_gnu();
+    header.set_metadata(&metadata);
+    let file = cancel.reader(File::open(abs_path)?);
+    archive.append_data(&mut header, name, progress.reader(file))
+}
+
+/// Only type, size and whether a file is executable are taken from `metadata`.
+fn deterministic_header(metadata: &fs::Metadata) -> tar::Header {
+    let mut header = tar::Header::new_ustar();
+    if metadata.is_dir() {
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_size(0);
+        header.set_mode(0o755);
+    } else {
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_size(metadata.len());
+        let executable = FilePermissions::from_metadata(metadata).is_executable();
+        header.set_mode(if executable { 0o755 } else { 0o644 });
+    }
+    header.set_mtime(DETERMINISTIC_MTIME);
+    header.set_uid(0);
+    header.set_gid(0);
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::error::ErrorKind;
+    use crate::utils::ignore::RuleSource;
+    use flate2::read::GzDecoder;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn deterministic_archives_depend_only_on_content() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("src/sub")).unwrap();
+        fs::write(dir.join("src/main.py"), "print('hi')").unwrap();
+        fs::write(dir.join("src/sub/lib.py"), "x = 1").unwrap();
+        let options = TarOptions {
+            deterministic: true,
+            codec: Some(Codec::Gzip),
+            level: None,
+        };
+        let build = |files: &[&str], archive: &str| {
+            let files: Vec<_> = files.iter().map(|f| f.to_string()).collect();
+            write_tar(
+                &dir.join(archive),
+                &dir.join("src"),
+                &files,
+                &IgnoreRules::default(),
+                &options,
+                &ProgressReporter::silent(),
+                &CancelToken::default(),
+            )
+            .unwrap()
+            .tar_hash
+        };
+
+        let hash = build(&["./sub/lib.py", "./main.py"], "a.tar.gz");
+        assert_eq!(
+            hash,
+            "43f5e6ea73e5b21caef09ba402a93dbbb853aa63fed106d1d89c98cb5575c1e5"
+        );
+        // rewritten content gets a new mtime, the order of the files changes
+        fs::write(dir.join("src/main.py"), "print('hi')").unwrap();
+        assert_eq!(build(&["./main.py", "./sub/lib.py"], "b.tar.gz"), hash);
+
+        let archive = File::open(dir.join("a.tar.gz")).unwrap();
+        let mut archive = tar::Archive::new(GzDecoder::new(archive));
+        let headers: Vec<_> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let header = entry.unwrap().header().clone();
+                let path = header.path().unwrap().to_string_lossy().to_string();
+                (path, header.mode().unwrap(), header.mtime().unwrap())
+            })
+            .collect();
+        assert_eq!(
+            headers,
+            [
+                ("main.py".to_string(), 0o644, DETERMINISTIC_MTIME),
+                ("sub/lib.py".to_string(), 0o644, DETERMINISTIC_MTIME),
+            ]
+        );
+    }
+
+    #[test]
+    fn cancelling_stops_copying_a_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+        fs::write(dir.join("big.bin"), vec![7; 1 << 20]).unwrap();
+        let cancel = CancelToken::default();
+        // cancelled once the file is started, so while it is copied into the archive
+        let progress = {
+            let cancel = cancel.clone();
+            ProgressReporter::new(move |progress| {
+                if progress.current_file.is_some() {
+                    cancel.cancel();
+                }
+            })
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let result = write_tar(
+                &dir.join("a.tar.gz"),
+                &dir,
+                &["./big.bin".to_string()],
+                &IgnoreRules::default(),
+                &TarOptions::default(),
+                &progress,
+                &cancel,
+            );
+            sender.send(result.map(|_| ())).unwrap();
+        });
+        let result = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(result.unwrap_err().kind, ErrorKind::Cancelled);
+        assert!(!tmp.path().join("a.tar.gz").exists());
+    }
+
+    #[test]
+    fn ignored_files_are_reported() {
+        let rules = IgnoreRules::default().with(RuleSource::Project, "*.log\n");
+        let files: Vec<_> = ["./main.py", "./run.log", "./.git/config"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        let TarEntries { entries, ignored } = tar_entries(Path::new("/p"), &files, &rules);
+        assert_eq!(
+            entries,
+            [("./main.py".to_string(), Path::new("/p").join("./main.py"))]
+        );
+        assert_eq!(ignored, ["./run.log", "./.git/config"]);
+    }
 }
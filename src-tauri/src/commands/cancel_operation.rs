@@ -0,0 +1,10 @@
+use tauri::State;
+
+use crate::operations::OperationRegistry;
+
+/// Cancel a running `build_tar`, `upload_archive`, `extract_tar` or `get_file_hashes` by the
+/// operation id it was started with. Returns whether the operation was still running.
+#[tauri::command]
+pub fn cancel_operation(operations: State<'_, OperationRegistry>, id: String) -> bool {
+    operations.cancel(&id)
+}
@@ -1,61 +1,47 @@
+use crate::commands::error::{CommandError, ErrorKind};
 use jsonwebtoken::{encode, Algorithm, EncodingKey};
-use serde::Serialize;
 use serde_json::Value;
 use std::fs;
 
-trait Traversable<T, E> {
-    fn traverse<U, G, F: FnOnce(T) -> Result<U, G>>(self, op: F) -> Result<Result<U, E>, G>;
-}
-
-impl<T, E> Traversable<T, E> for Result<T, E> {
-    fn traverse<U, G, F: FnOnce(T) -> Result<U, G>>(self, op: F) -> Result<Result<U, E>, G> {
-        match self {
-            Err(e) => Ok(Err(e)),
-            Ok(t) => op(t).map(|u| Ok(u)),
-        }
-    }
-}
-
-#[derive(Serialize, Debug)]
-#[serde(tag = "_tag", content = "value")]
-pub enum Either<E, A> {
-    Left(E),
-    Right(A),
-}
-
+/// A missing private key fails with `notFound`, an unusable one with `invalidKey`.
 #[tauri::command]
 pub fn create_jwt_token(
     app_handle: tauri::AppHandle,
     claims: Value,
-) -> Result<Either<String, String>, String> {
-    app_handle
+) -> Result<String, CommandError> {
+    sign_claims(&app_handle, &claims)
+}
+
+/// Sign `claims` with the private key of the app, like `create_jwt_token`.
+pub fn sign_claims(app_handle: &tauri::AppHandle, claims: &Value) -> Result<String, CommandError> {
+    let key_path = app_handle
         .path_resolver()
         .app_local_data_dir()
-        .ok_or("Unable to determine app-local data dir".to_string())
+        .ok_or_else(|| {
+            CommandError::new(
+                ErrorKind::NotFound,
+                "Unable to determine app-local data dir",
+            )
+        })
         .map(|mut key_path| {
             key_path.push("privateKey.pem");
             key_path
-        })
-        .map(|key_path| {
-            fs::read(key_path).map_err(|_| "Could not read private key file".to_string())
-        })
-        .and_then(|private_key| {
-            private_key.traverse(|pk| {
-                EncodingKey::from_ed_pem(&pk)
-                    .map_err(|_| "Invalid key file".to_string())
-                    .and_then(|encoding_key| {
-                        encode(
-                            &jsonwebtoken::Header::new(Algorithm::EdDSA),
-                            &claims,
-                            &encoding_key,
-                        )
-                        .map_err(|_| "Unable to encode JWT token".to_string())
-                    })
-                    .map_err(|e| format!("Could not create JWT token: {e}"))
-            })
-        })
-        .map(|e| match e {
-            Ok(t) => Either::Right(t),
-            Err(e) => Either::Left(e),
-        })
+        })?;
+    let private_key = fs::read(&key_path)
+        .map_err(|e| CommandError::io("Could not read private key file", &key_path, e))?;
+    let encoding_key = EncodingKey::from_ed_pem(&private_key).map_err(|e| {
+        CommandError::new(ErrorKind::InvalidKey, format!("Invalid key file: {e}"))
+            .with_path(&key_path)
+    })?;
+    encode(
+        &jsonwebtoken::Header::new(Algorithm::EdDSA),
+        claims,
+        &encoding_key,
+    )
+    .map_err(|e| {
+        CommandError::new(
+            ErrorKind::InvalidKey,
+            format!("Unable to encode JWT token: {e}"),
+        )
+    })
 }
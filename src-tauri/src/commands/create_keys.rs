@@ -1,21 +1,22 @@
+use crate::commands::error::{CommandError, ErrorKind};
 use ed25519_compact::*;
 use std::fs;
 
 #[tauri::command]
-pub fn create_keys(app_handle: tauri::AppHandle) -> Result<String, String> {
+pub fn create_keys(app_handle: tauri::AppHandle) -> Result<String, CommandError> {
     let kp = KeyPair::generate();
 
     app_handle
         .path_resolver()
         .app_local_data_dir()
-        .ok_or("Did not find local dir".to_string())
+        .ok_or_else(|| CommandError::new(ErrorKind::NotFound, "Did not find local dir"))
         .map(|mut key_path| {
             key_path.push("privateKey.pem");
             key_path
         })
         .and_then(|key_path| {
-            fs::write(key_path, kp.sk.to_pem())
-                .map_err(|_| "Could not write private key".to_string())
+            fs::write(&key_path, kp.sk.to_pem())
+                .map_err(|e| CommandError::io("Could not write private key", &key_path, e))
         })
         .map(|_| kp.pk.to_pem())
 }
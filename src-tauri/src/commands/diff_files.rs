@@ -0,0 +1,413 @@
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::commands::error::{CommandError, ErrorKind};
+use crate::utils::diff::{edit_script, hunks, lines, matching_lines, Edit};
+use crate::utils::object_store::ObjectStore;
+use crate::utils::parallel::run_blocking;
+use crate::utils::path_guard::PathGuard;
+
+/// Larger files are not diffed, reading and rendering them would stall the UI.
+const MAX_FILE_SIZE: u64 = 2 * 1024 * 1024;
+/// Hunks are cut off once they add up to this many lines.
+const MAX_DIFF_LINES: usize = 5000;
+/// Longer lines are shown as a whole instead of word by word.
+const MAX_WORD_DIFF_LINE: usize = 1000;
+/// Like git, a NUL byte in the first few kilobytes marks a file as binary.
+const BINARY_CHECK_SIZE: usize = 8000;
+const DEFAULT_CONTEXT: usize = 3;
+
+/// The version the local file is compared with.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "_tag", content = "value", rename_all = "camelCase")]
+pub enum DiffSource {
+    /// Current content on the server.
+    Remote(String),
+    /// Pristine copy of the last sync, by hash.
+    Pristine(String),
+}
+
+#[derive(Serialize, PartialEq, Eq, Debug)]
+#[serde(tag = "_tag", content = "value", rename_all = "camelCase")]
+pub enum FileDiff {
+    Identical,
+    Text(TextDiff),
+    /// At least one side is not UTF-8 text.
+    Binary,
+    /// The size limit in bytes, which one of the sides exceeds.
+    TooLarge(u64),
+}
+
+#[derive(Serialize, PartialEq, Eq, Debug)]
+pub struct TextDiff {
+    pub hunks: Vec<Hunk>,
+    /// Hunks were left out because the diff exceeds the line limit.
+    pub truncated: bool,
+}
+
+/// A region of a unified diff. Line numbers start at 1; a side without lines in the hunk reports
+/// the line after which the other side's lines go, as `diff -u` does.
+#[derive(Serialize, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Serialize, PartialEq, Eq, Debug)]
+pub struct DiffLine {
+    pub kind: LineKind,
+    /// Without the line terminator.
+    pub text: String,
+    /// For a changed line paired with its counterpart on the other side, the text split into
+    /// changed and unchanged parts.
+    pub words: Option<Vec<WordSegment>>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum LineKind {
+    Context,
+    Removed,
+    Added,
+}
+
+#[derive(Serialize, PartialEq, Eq, Debug)]
+pub struct WordSegment {
+    pub text: String,
+    pub changed: bool,
+}
+
+/// Diff the file at `path` in the project against `other`: removed lines are those only `other`
+/// has, added lines those only the local file has. A missing local file is diffed as empty.
+#[tauri::command]
+pub async fn diff_files(
+    app_handle: tauri::AppHandle,
+    root_dir: String,
+    path: String,
+    other: DiffSource,
+    context: Option<usize>,
+) -> Result<FileDiff, CommandError> {
+    let guard = PathGuard::from_app(&app_handle);
+    let root_dir = guard.check(Path::new(&root_dir))?;
+    let target = guard.check_relative(&root_dir, &path)?;
+    let store = ObjectStore::from_app(&app_handle)?;
+    run_blocking(move || {
+        let too_large = Ok(FileDiff::TooLarge(MAX_FILE_SIZE));
+        let local = match fs::File::open(&target) {
+            Ok(file) => read_limited(file, &target)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Some(Vec::new()),
+            Err(e) => return Err(CommandError::io("Could not open file", &target, e)),
+        };
+        let Some(local) = local else {
+            return too_large;
+        };
+        let other = match other {
+            DiffSource::Remote(content) => {
+                Some(content.into_bytes()).filter(|content| content.len() as u64 <= MAX_FILE_SIZE)
+            }
+            DiffSource::Pristine(hash) => read_pristine(&store, &hash)?,
+        };
+        let Some(other) = other else {
+            return too_large;
+        };
+        Ok(diff(&other, &local, context.unwrap_or(DEFAULT_CONTEXT)))
+    })
+    .await
+}
+
+pub fn diff(old: &[u8], new: &[u8], context: usize) -> FileDiff {
+    if old.len().max(new.len()) as u64 > MAX_FILE_SIZE {
+        return FileDiff::TooLarge(MAX_FILE_SIZE);
+    }
+    if old == new {
+        return FileDiff::Identical;
+    }
+    let (Some(old), Some(new)) = (as_text(old), as_text(new)) else {
+        return FileDiff::Binary;
+    };
+    let (old, new) = (lines(old), lines(new));
+    let edits = edit_script(&old, &new);
+
+    let mut diff = TextDiff {
+        hunks: Vec::new(),
+        truncated: false,
+    };
+    let mut total = 0;
+    for range in hunks(&edits, context) {
+        total += range.len();
+        if total > MAX_DIFF_LINES {
+            diff.truncated = true;
+            break;
+        }
+        let (old_start, new_start) = match edits[range.start] {
+            Edit::Keep(i, j) => (i, j),
+            Edit::Remove(i) => (i, new_position(&edits[..range.start])),
+            Edit::Insert(j) => (old_position(&edits[..range.start]), j),
+        };
+        let edits = &edits[range];
+        let count = |side: fn(&Edit) -> bool| edits.iter().filter(|e| side(e)).count();
+        let old_lines = count(|e| !matches!(e, Edit::Insert(_)));
+        let new_lines = count(|e| !matches!(e, Edit::Remove(_)));
+        diff.hunks.push(Hunk {
+            old_start: start_line(old_start, old_lines),
+            old_lines,
+            new_start: start_line(new_start, new_lines),
+            new_lines,
+            lines: diff_lines(edits, &old, &new),
+        });
+    }
+    FileDiff::Text(diff)
+}
+
+/// Line number of the first line of a hunk side starting at `index`, see [`Hunk`].
+fn start_line(index: usize, lines: usize) -> usize {
+    if lines == 0 {
+        index
+    } else {
+        index + 1
+    }
+}
+
+/// Number of old lines consumed by `edits`.
+fn old_position(edits: &[Edit]) -> usize {
+    edits
+        .iter()
+        .filter(|e| !matches!(e, Edit::Insert(_)))
+        .count()
+}
+
+fn new_position(edits: &[Edit]) -> usize {
+    edits
+        .iter()
+        .filter(|e| !matches!(e, Edit::Remove(_)))
+        .count()
+}
+
+fn diff_lines(edits: &[Edit], old: &[&str], new: &[&str]) -> Vec<DiffLine> {
+    let mut lines = Vec::with_capacity(edits.len());
+    let mut rest = edits;
+    while let Some(edit) = rest.first() {
+        if let Edit::Keep(i, _) = edit {
+            lines.push(line(LineKind::Context, old[*i], None));
+            rest = &rest[1..];
+            continue;
+        }
+        // a block of removals followed by insertions, pairing up lines in order for word diffs
+        let removed: Vec<&str> = take_while(&mut rest, |e| match e {
+            Edit::Remove(i) => Some(old[*i]),
+            _ => None,
+        });
+        let added: Vec<&str> = take_while(&mut rest, |e| match e {
+            Edit::Insert(j) => Some(new[*j]),
+            _ => None,
+        });
+        let (removed_words, added_words): (Vec<_>, Vec<_>) = removed
+            .iter()
+            .zip(&added)
+            .map(|(r, a)| word_diff(r, a).map_or((None, None), |(r, a)| (Some(r), Some(a))))
+            .unzip();
+        let mut removed_words = removed_words.into_iter();
+        for text in removed {
+            lines.push(line(
+                LineKind::Removed,
+                text,
+                removed_words.next().flatten(),
+            ));
+        }
+        let mut added_words = added_words.into_iter();
+        for text in added {
+            lines.push(line(LineKind::Added, text, added_words.next().flatten()));
+        }
+    }
+    lines
+}
+
+fn take_while<T>(edits: &mut &[Edit], f: impl Fn(&Edit) -> Option<T>) -> Vec<T> {
+    let mut taken = Vec::new();
+    while let Some(value) = edits.first().and_then(&f) {
+        taken.push(value);
+        *edits = &edits[1..];
+    }
+    taken
+}
+
+fn line(kind: LineKind, text: &str, words: Option<Vec<WordSegment>>) -> DiffLine {
+    DiffLine {
+        kind,
+        text: text.trim_end_matches(['\n', '\r']).to_string(),
+        words,
+    }
+}
+
+/// Segments of both lines, or `None` if they are too long or have nothing in common, in which case
+/// highlighting words adds nothing.
+fn word_diff(old: &str, new: &str) -> Option<(Vec<WordSegment>, Vec<WordSegment>)> {
+    let (old, new) = (
+        old.trim_end_matches(['\n', '\r']),
+        new.trim_end_matches(['\n', '\r']),
+    );
+    if old.len() > MAX_WORD_DIFF_LINE || new.len() > MAX_WORD_DIFF_LINE {
+        return None;
+    }
+    let (old, new) = (words(old), words(new));
+    let matches = matching_lines(&old, &new);
+    if matches.iter().all(|&(i, _)| old[i].trim().is_empty()) {
+        return None;
+    }
+    let mut old_changed = vec![true; old.len()];
+    let mut new_changed = vec![true; new.len()];
+    for (i, j) in matches {
+        old_changed[i] = false;
+        new_changed[j] = false;
+    }
+    Some((segments(&old, &old_changed), segments(&new, &new_changed)))
+}
+
+/// Runs of word characters, runs of whitespace and single other characters.
+fn words(text: &str) -> Vec<&str> {
+    let class = |c: char| {
+        if c.is_alphanumeric() || c == '_' {
+            0
+        } else if c.is_whitespace() {
+            1
+        } else {
+            2
+        }
+    };
+    let mut words = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        let end = index + c.len_utf8();
+        let continues = chars
+            .peek()
+            .map_or(false, |&(_, next)| class(c) != 2 && class(next) == class(c));
+        if !continues {
+            words.push(&text[start..end]);
+            start = end;
+        }
+    }
+    words
+}
+
+fn segments(words: &[&str], changed: &[bool]) -> Vec<WordSegment> {
+    let mut segments: Vec<WordSegment> = Vec::new();
+    for (word, &changed) in words.iter().zip(changed) {
+        match segments.last_mut() {
+            Some(last) if last.changed == changed => last.text.push_str(word),
+            _ => segments.push(WordSegment {
+                text: word.to_string(),
+                changed,
+            }),
+        }
+    }
+    segments
+}
+
+fn as_text(bytes: &[u8]) -> Option<&str> {
+    if bytes[..bytes.len().min(BINARY_CHECK_SIZE)].contains(&0) {
+        return None;
+    }
+    std::str::from_utf8(bytes).ok()
+}
+
+/// Reads at most one byte more than the size limit, enough for `diff` to tell it was exceeded.
+/// The content of `file`, or `None` if it exceeds the size limit. The size is checked before the
+/// file is read, and again after reading as it may have grown in the meantime.
+fn read_limited(file: fs::File, path: &Path) -> Result<Option<Vec<u8>>, CommandError> {
+    let metadata = file
+        .metadata()
+        .map_err(|e| CommandError::io("Could not read file metadata", path, e))?;
+    if metadata.len() > MAX_FILE_SIZE {
+        return Ok(None);
+    }
+    let mut content = Vec::new();
+    file.take(MAX_FILE_SIZE + 1)
+        .read_to_end(&mut content)
+        .map_err(|e| CommandError::io("Could not read file", path, e))?;
+    Ok(Some(content).filter(|content| content.len() as u64 <= MAX_FILE_SIZE))
+}
+
+fn read_pristine(store: &ObjectStore, hash: &str) -> Result<Option<Vec<u8>>, CommandError> {
+    let Some(file) = store.open(hash)? else {
+        return Err(CommandError::new(
+            ErrorKind::NotFound,
+            "The last synced version is not available",
+        ));
+    };
+    read_limited(file, &store.object_path(hash)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(text: &str, changed: bool) -> WordSegment {
+        WordSegment {
+            text: text.to_string(),
+            changed,
+        }
+    }
+
+    #[test]
+    fn diffs_lines_and_words() {
+        let FileDiff::Text(text) = diff(b"a\nint x = 1;\nb\n", b"a\nint y = 1;\nb\nc\n", 0) else {
+            panic!("expected a text diff");
+        };
+        assert!(!text.truncated);
+        let [changed, appended] = &text.hunks[..] else {
+            panic!("expected two hunks");
+        };
+        assert_eq!(
+            (
+                changed.old_start,
+                changed.old_lines,
+                changed.new_start,
+                changed.new_lines
+            ),
+            (2, 1, 2, 1)
+        );
+        assert_eq!(changed.lines[0].kind, LineKind::Removed);
+        assert_eq!(changed.lines[1].text, "int y = 1;");
+        assert_eq!(
+            changed.lines[1].words,
+            Some(vec![
+                segment("int ", false),
+                segment("y", true),
+                segment(" = 1;", false)
+            ])
+        );
+        assert_eq!(
+            (appended.old_start, appended.old_lines, appended.new_start),
+            (3, 0, 4)
+        );
+
+        assert_eq!(diff(b"a", b"a", 3), FileDiff::Identical);
+        assert_eq!(diff(b"a\0", b"b", 3), FileDiff::Binary);
+        let huge = vec![b'a'; MAX_FILE_SIZE as usize + 1];
+        assert_eq!(diff(b"", &huge, 3), FileDiff::TooLarge(MAX_FILE_SIZE));
+        assert_eq!(diff(&huge, &huge, 3), FileDiff::TooLarge(MAX_FILE_SIZE));
+    }
+
+    #[test]
+    fn large_files_are_not_read() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("data.csv");
+        fs::write(&path, "a").unwrap();
+        let file = fs::File::open(&path).unwrap();
+        assert_eq!(read_limited(file, &path).unwrap(), Some(b"a".to_vec()));
+
+        let file = fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(MAX_FILE_SIZE + 1).unwrap();
+        let file = fs::File::open(&path).unwrap();
+        assert_eq!(read_limited(file, &path).unwrap(), None);
+    }
+}
@@ -0,0 +1,101 @@
+use serde::Serialize;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use crate::operations::is_cancelled_read;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorKind {
+    Io,
+    Permission,
+    NotFound,
+    InvalidKey,
+    InvalidInput,
+    Archive,
+    /// The target is outside of every allowed root, or would be after resolving `..` or symlinks.
+    PathEscape,
+    /// The operation was aborted through `cancel_operation`.
+    Cancelled,
+    /// The server could not be reached, or the connection to it broke off.
+    Network,
+}
+
+/// Error returned by every command. Serializes to
+/// `{ kind, message, path: string | null, code: number | null }`, where `code` is the OS error
+/// code of the underlying I/O error, if any.
+#[derive(Serialize, Debug)]
+pub struct CommandError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub path: Option<String>,
+    pub code: Option<i32>,
+}
+
+impl CommandError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            path: None,
+            code: None,
+        }
+    }
+
+    /// Wrap an I/O error, distinguishing missing files, denied access and cancelled reads from
+    /// other failures.
+    pub fn io(message: &str, path: impl AsRef<Path>, e: io::Error) -> Self {
+        if is_cancelled_read(&e) {
+            return Self::new(ErrorKind::Cancelled, "Operation was cancelled");
+        }
+        let kind = match e.kind() {
+            io::ErrorKind::NotFound => ErrorKind::NotFound,
+            io::ErrorKind::PermissionDenied => ErrorKind::Permission,
+            _ => ErrorKind::Io,
+        };
+        Self {
+            kind,
+            message: format!("{message}: {e}"),
+            path: Some(path.as_ref().display().to_string()),
+            code: e.raw_os_error(),
+        }
+    }
+
+    pub fn with_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.path = Some(path.as_ref().display().to_string());
+        self
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{} ('{path}')", self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn io_errors_keep_kind_path_and_code() {
+        let e = io::Error::from_raw_os_error(2);
+        let error = CommandError::io("Could not open file", "/tmp/x", e);
+        assert_eq!(error.kind, ErrorKind::NotFound);
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "kind": "notFound",
+                "message": error.message,
+                "path": "/tmp/x",
+                "code": 2,
+            })
+        );
+    }
+}
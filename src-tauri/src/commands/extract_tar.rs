@@ -0,0 +1,271 @@
+use crate::commands::error::{CommandError, ErrorKind};
+use crate::commands::fs_extra::{self, FilePermissions};
+use crate::commands::sync::to_project_path;
+use crate::operations::{CancelToken, OperationRegistry};
+use crate::utils::parallel::run_blocking;
+use crate::utils::path_guard::PathGuard;
+use crate::utils::progress::ProgressReporter;
+use crate::utils::tee_reader::TeeReader;
+use brotli::Decompressor;
+use data_encoding::HEXLOWER;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use tar::EntryType;
+use tauri::State;
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum ArchiveSource {
+    Path(String),
+    Bytes(Vec<u8>),
+}
+
+impl ArchiveSource {
+    fn open(&self) -> Result<Box<dyn Read + '_>, CommandError> {
+        let compressed: Box<dyn Read + '_> = match self {
+            ArchiveSource::Path(path) => Box::new(
+                File::open(path)
+                    .map_err(|e| CommandError::io("Could not open archive file", path, e))?,
+            ),
+            ArchiveSource::Bytes(bytes) => Box::new(bytes.as_slice()),
+        };
+        Ok(Box::new(Decompressor::new(compressed, 4096)))
+    }
+}
+
+/// Unpack a brotli-compressed tar (as produced by `build_tar`) into `root_dir`.
+///
+/// The archive is read twice: first to check `tar_hash` (the SHA-256 of the uncompressed tar, like
+/// the hash returned by `build_tar`), entry paths and entry types, and only then to write files.
+/// Files are made read-only according to `permissions`, which must contain every file entry;
+/// directories without an entry stay writable. Progress of the second pass is emitted on
+/// `progress_channel`, if given. Files extracted before a cancellation are kept.
+#[allow(clippy::too_many_arguments)]
+#[tauri::command]
+pub async fn extract_tar(
+    app_handle: tauri::AppHandle,
+    operations: State<'_, OperationRegistry>,
+    root_dir: String,
+    archive: ArchiveSource,
+    tar_hash: String,
+    permissions: HashMap<String, FilePermissions>,
+    progress_channel: Option<String>,
+    operation_id: Option<String>,
+) -> Result<(), CommandError> {
+    let guard = PathGuard::from_app(&app_handle);
+    let root_dir = guard.check(Path::new(&root_dir))?;
+    let progress = ProgressReporter::for_channel(&app_handle, progress_channel)?;
+    let operation = operations.start(operation_id)?;
+    let cancel = operation.token();
+    run_blocking(move || {
+        let unpacked = unpack_tar(
+            &guard,
+            &root_dir,
+            &archive,
+            &tar_hash,
+            &permissions,
+            &progress,
+            &cancel,
+        );
+        cancel.map_result(unpacked)
+    })
+    .await
+}
+
+pub fn unpack_tar(
+    guard: &PathGuard,
+    root_dir: &Path,
+    archive: &ArchiveSource,
+    tar_hash: &str,
+    permissions: &HashMap<String, FilePermissions>,
+    progress: &ProgressReporter,
+    cancel: &CancelToken,
+) -> Result<(), CommandError> {
+    progress.set_total(verify_archive(archive, tar_hash, permissions, cancel)?);
+
+    let mut tar = tar::Archive::new(cancel.reader(archive.open()?));
+    for entry in tar.entries().map_err(archive_error)? {
+        cancel.check()?;
+        let mut entry = entry.map_err(archive_error)?;
+        let relative = entry_path(&entry)?;
+        let project_path = to_project_path(&relative);
+        let entry_permissions = permissions
+            .get(&project_path)
+            .copied()
+            .unwrap_or(FilePermissions::Rw);
+        let read_only = entry_permissions.is_read_only();
+        // an entry may still escape through a symlink that already exists in the project
+        let target = guard.check(&root_dir.join(&relative))?;
+
+        if entry.header().entry_type() == EntryType::Directory {
+            if !target.is_dir() {
+                fs_extra::create_missing_parents(root_dir, &target)?;
+                fs_extra::create_dir(&target, read_only)?;
+            } else if read_only {
+                fs_extra::set_read_only(&target)?;
+            }
+        } else {
+            fs_extra::create_missing_parents(root_dir, &target)?;
+            progress.start_file(&project_path);
+            fs_extra::write_file_from(
+                &target,
+                &mut progress.reader(&mut entry),
+                entry_permissions,
+            )?;
+        }
+    }
+    progress.finish();
+    Ok(())
+}
+
+fn verify_archive(
+    archive: &ArchiveSource,
+    tar_hash: &str,
+    permissions: &HashMap<String, FilePermissions>,
+    cancel: &CancelToken,
+) -> Result<u64, CommandError> {
+    let compressed = cancel.reader(archive.open()?);
+    let mut tar = tar::Archive::new(TeeReader::new(compressed, Sha256::new()));
+    let mut total = 0;
+    for entry in tar.entries().map_err(archive_error)? {
+        let entry = entry.map_err(archive_error)?;
+        let relative = entry_path(&entry)?;
+        match entry.header().entry_type() {
+            EntryType::Directory => {}
+            EntryType::Regular | EntryType::Continuous => {
+                total += entry.size();
+                if !permissions.contains_key(&to_project_path(&relative)) {
+                    return Err(CommandError::new(
+                        ErrorKind::Archive,
+                        "Missing permissions for archive entry",
+                    )
+                    .with_path(&relative));
+                }
+            }
+            other => {
+                return Err(CommandError::new(
+                    ErrorKind::Archive,
+                    format!("Unsupported archive entry of type {other:?}"),
+                )
+                .with_path(&relative))
+            }
+        }
+    }
+
+    // the tar reader stops at the end-of-archive marker, the hash covers the padding as well
+    let (mut rest, mut hasher) = tar.into_inner().into_inner();
+    io::copy(&mut rest, &mut hasher).map_err(archive_error)?;
+    let actual = HEXLOWER.encode(hasher.finalize().as_ref());
+    if actual != tar_hash {
+        return Err(CommandError::new(
+            ErrorKind::Archive,
+            format!("Archive hash mismatch: expected {tar_hash}, got {actual}"),
+        ));
+    }
+    Ok(total)
+}
+
+/// Entry paths must stay inside the project: no absolute paths, no `..`.
+fn entry_path<R: Read>(entry: &tar::Entry<R>) -> Result<PathBuf, CommandError> {
+    let path = entry.path().map_err(archive_error)?;
+    let mut relative = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => relative.push(name),
+            Component::CurDir => {}
+            _ => {
+                return Err(CommandError::new(
+                    ErrorKind::PathEscape,
+                    "Archive entry points outside the project",
+                )
+                .with_path(&path))
+            }
+        }
+    }
+    if relative.as_os_str().is_empty() {
+        return Err(CommandError::new(
+            ErrorKind::Archive,
+            "Archive entry has an empty path",
+        ));
+    }
+    Ok(relative)
+}
+
+/// Errors of the tar reader itself mean the archive is malformed, not that the disk failed.
+fn archive_error(e: io::Error) -> CommandError {
+    CommandError::new(ErrorKind::Archive, format!("Could not read archive: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::build_tar::{write_tar, TarOptions};
+    use crate::utils::compression::Codec;
+    use crate::utils::ignore::IgnoreRules;
+    use std::fs;
+
+    #[test]
+    fn extracts_what_build_tar_archived() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let (src, dst) = (dir.join("src"), dir.join("dst"));
+        fs::create_dir_all(src.join("sub")).unwrap();
+        fs::create_dir_all(&dst).unwrap();
+        fs::write(src.join("main.py"), "print('hi')").unwrap();
+        fs::write(src.join("sub/lib.py"), "x = 1").unwrap();
+
+        let archive = dir.join("archive.tar.br");
+        let tar_hash = write_tar(
+            &archive,
+            &src,
+            &["./main.py".to_string(), "./sub/lib.py".to_string()],
+            &IgnoreRules::default(),
+            &TarOptions {
+                codec: Some(Codec::Brotli),
+                ..Default::default()
+            },
+            &ProgressReporter::silent(),
+            &CancelToken::default(),
+        )
+        .unwrap()
+        .tar_hash;
+        let permissions = HashMap::from([
+            ("./main.py".to_string(), FilePermissions::Rw),
+            ("./sub/lib.py".to_string(), FilePermissions::R),
+        ]);
+        let source = ArchiveSource::Path(archive.to_string_lossy().to_string());
+        let dst = dst.canonicalize().unwrap();
+        let guard = PathGuard::new([dst.clone()]);
+
+        let (progress, cancel) = (ProgressReporter::silent(), CancelToken::default());
+        let unpack = |tar_hash: &str| {
+            unpack_tar(
+                &guard,
+                &dst,
+                &source,
+                tar_hash,
+                &permissions,
+                &progress,
+                &cancel,
+            )
+        };
+        assert!(unpack(&"0".repeat(64)).is_err());
+        assert!(!dst.join("main.py").exists());
+
+        unpack(&tar_hash).unwrap();
+        assert_eq!(
+            fs::read_to_string(dst.join("main.py")).unwrap(),
+            "print('hi')"
+        );
+        assert_eq!(fs::read_to_string(dst.join("sub/lib.py")).unwrap(), "x = 1");
+        assert!(fs::metadata(dst.join("sub/lib.py"))
+            .unwrap()
+            .permissions()
+            .readonly());
+    }
+}
@@ -1,15 +1,89 @@
+use crate::commands::error::{CommandError, ErrorKind};
+use crate::commands::preserved_files::{Preserve, PreservedFile, PreservedFiles};
+use crate::utils::path_guard::PathGuard;
+use serde::{Deserialize, Serialize};
 use std::fs;
-use std::fs::{File, Permissions};
-use std::io::Write;
-use std::path::Path;
+use std::fs::{File, OpenOptions, Permissions};
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Permissions of a project entry as set by the lecturer. On Unix they map to mode bits, on other
+/// platforms only the read-only flag is applied.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum FilePermissions {
+    R,
+    Rw,
+    Rx,
+    Rwx,
+}
+
+impl FilePermissions {
+    pub fn is_read_only(self) -> bool {
+        matches!(self, FilePermissions::R | FilePermissions::Rx)
+    }
+
+    pub fn is_executable(self) -> bool {
+        matches!(self, FilePermissions::Rx | FilePermissions::Rwx)
+    }
+
+    /// The permissions an existing entry carries, as compared by `matches`.
+    pub fn from_metadata(metadata: &fs::Metadata) -> Self {
+        #[cfg(unix)]
+        let executable = {
+            use std::os::unix::fs::PermissionsExt;
+            metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+        };
+        #[cfg(not(unix))]
+        let executable = false;
+        match (metadata.permissions().readonly(), executable) {
+            (true, false) => FilePermissions::R,
+            (false, false) => FilePermissions::Rw,
+            (true, true) => FilePermissions::Rx,
+            (false, true) => FilePermissions::Rwx,
+        }
+    }
+
+    /// Whether an entry carries these permissions. The read-only flag is compared everywhere, the
+    /// execute bits of files on Unix only.
+    pub fn matches(self, metadata: &fs::Metadata) -> bool {
+        if metadata.permissions().readonly() != self.is_read_only() {
+            return false;
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if metadata.is_file() {
+                return (metadata.permissions().mode() & 0o111 != 0) == self.is_executable();
+            }
+        }
+        true
+    }
+
+    /// Everyone may read, only the owner may write. Directories are always searchable.
+    #[cfg(unix)]
+    pub fn mode(self, is_dir: bool) -> u32 {
+        let mut mode = 0o444;
+        if !self.is_read_only() {
+            mode |= 0o200;
+        }
+        if is_dir || self.is_executable() {
+            mode |= 0o111;
+        }
+        mode
+    }
+}
 
 #[tauri::command]
-pub fn make_readonly(path: String) -> Result<(), String> {
-    let binding = Path::new(&path);
-    set_read_only(binding)
+pub fn make_readonly(app_handle: tauri::AppHandle, path: String) -> Result<(), CommandError> {
+    let path = PathGuard::from_app(&app_handle).check(Path::new(&path))?;
+    set_read_only(&path)?;
+    Ok(())
 }
 
-fn set_read_only(path: &Path) -> Result<(), String> {
+pub fn set_read_only(path: &Path) -> Result<(), CommandError> {
     File::open(path)
         .and_then(|f| {
             f.metadata().map(|m| m.permissions()).map(|mut p| {
@@ -18,110 +92,433 @@ fn set_read_only(path: &Path) -> Result<(), String> {
             })
         })
         .map(|_| ())
-        .map_err(|e| format!("Could not set permissions of file: {e}"))
+        .map_err(|e| CommandError::io("Could not set permissions of file", path, e))
+}
+
+pub fn apply_permissions(path: &Path, permissions: FilePermissions) -> Result<(), CommandError> {
+    File::open(path)
+        .and_then(|f| {
+            let metadata = f.metadata()?;
+            let mut p = metadata.permissions();
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                p.set_mode(permissions.mode(metadata.is_dir()));
+            }
+            #[cfg(not(unix))]
+            p.set_readonly(permissions.is_read_only());
+            f.set_permissions(p)
+        })
+        .map_err(|e| CommandError::io("Could not set permissions of file", path, e))
 }
 
-fn set_permissions(path: &Path, perms: Permissions) -> Result<(), String> {
+pub fn set_permissions(path: &Path, perms: Permissions) -> Result<(), CommandError> {
     File::open(path)
         .and_then(|f| f.set_permissions(perms))
         .map(|_| ())
-        .map_err(|e| format!("Could not set permissions of file: {e}"))
+        .map_err(|e| CommandError::io("Could not set permissions of file", path, e))
 }
 
-fn remove_read_only(path: &Path) -> Result<Permissions, String> {
+pub fn remove_read_only(path: &Path) -> Result<Permissions, CommandError> {
     File::open(path)
         .and_then(|f| {
             f.metadata().map(|m| m.permissions()).map(|mut p| {
                 let prev_perms = p.clone();
-                p.set_readonly(false);
+                make_owner_writable(&mut p);
                 let _ = f.set_permissions(p);
                 prev_perms
             })
         })
-        .map_err(|e| format!("Could not set permissions of file: {e}"))
+        .map_err(|e| CommandError::io("Could not set permissions of file", path, e))
 }
 
-fn get_existing_path(path: &Path) -> Result<&Path, String> {
+/// Unlike `set_readonly(false)`, which makes the entry writable for everyone on Unix.
+fn make_owner_writable(p: &mut Permissions) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        p.set_mode(p.mode() | 0o200);
+    }
+    #[cfg(not(unix))]
+    p.set_readonly(false);
+}
+
+/// Lifts the read-only flag of directories and restores their exact previous permissions when
+/// dropped, so an operation that fails in between can't leave them writable. This includes
+/// unwinding panics; release builds abort on panic and never leave the process running anyway.
+#[must_use = "permissions are restored as soon as the guard is dropped"]
+pub struct PermissionGuard {
+    unlocked: Vec<(PathBuf, Permissions)>,
+}
+
+impl PermissionGuard {
+    pub fn unlock(path: &Path) -> Result<Self, CommandError> {
+        let mut guard = Self {
+            unlocked: Vec::new(),
+        };
+        guard.add(path)?;
+        Ok(guard)
+    }
+
+    pub fn add(&mut self, path: &Path) -> Result<(), CommandError> {
+        let permissions = remove_read_only(path)?;
+        self.unlocked.push((path.to_path_buf(), permissions));
+        Ok(())
+    }
+
+    /// Restore now and report the first failure, which is only logged when dropped.
+    pub fn restore(mut self) -> Result<(), CommandError> {
+        self.restore_all()
+    }
+
+    fn restore_all(&mut self) -> Result<(), CommandError> {
+        let mut result = Ok(());
+        while let Some((path, permissions)) = self.unlocked.pop() {
+            let restored = set_permissions(&path, permissions);
+            result = result.and(restored);
+        }
+        result
+    }
+}
+
+impl Drop for PermissionGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.restore_all() {
+            eprintln!("Could not restore permissions: {e}");
+        }
+    }
+}
+
+pub fn get_existing_path(path: &Path) -> Result<&Path, CommandError> {
     let mut ancestors = path.ancestors();
     for p in &mut ancestors {
         if p.exists() && p.is_dir() {
             return Ok(p);
         }
     }
-    Err(format!("Could not find existing path"))
+    Err(CommandError::new(ErrorKind::NotFound, "Could not find existing path").with_path(path))
 }
 
-fn set_path_read_only(root_path: &Path, path: &Path, existing_path: &Path) -> Result<(), String> {
-    let mut ancestors = path.ancestors();
-    for p2 in &mut ancestors {
-        // stop if root is reached
-        if p2 == root_path {
+/// Make the directories created between `existing_path` and `path` read-only, excluding both.
+fn set_path_read_only(
+    root_path: &Path,
+    path: &Path,
+    existing_path: &Path,
+) -> Result<(), CommandError> {
+    for p2 in path.ancestors().skip(1) {
+        // stop if root or the previously existing path is reached
+        if p2 == root_path || p2 == existing_path {
             return Ok(());
         }
         set_read_only(p2)?;
-        // stop if path is reached
-        if p2 == existing_path {
-            return Ok(());
-        }
     }
     Ok(())
 }
+
+/// Create `path` and any missing ancestors below `root`. New ancestors are read-only, `path`
+/// itself stays writable for students.
+#[tauri::command]
+pub fn create_project_path(
+    app_handle: tauri::AppHandle,
+    path: String,
+    root: String,
+) -> Result<(), CommandError> {
+    let guard = PathGuard::from_app(&app_handle);
+    let create_path = &guard.check(Path::new(&path))?;
+    let root_path = &guard.check(Path::new(&root))?;
+    let existing_path = get_existing_path(create_path)?;
+    if existing_path == create_path {
+        // only make sure students can write to it
+        remove_read_only(create_path)?;
+        return Ok(());
+    }
+    let unlocked = PermissionGuard::unlock(existing_path)?;
+    fs::create_dir_all(create_path)
+        .map_err(|e| CommandError::io("Could not create dir", create_path, e))?;
+    set_path_read_only(root_path, create_path, existing_path)?;
+    unlocked.restore()
+}
+
 #[tauri::command]
-pub fn create_project_path(path: String, root: String) -> Result<(), String> {
-    let create_path = Path::new(&path);
-    let root_path = Path::new(&root);
-    get_existing_path(create_path).and_then(|existing_path| {
-        //remove readonly from path
-        remove_read_only(existing_path)?;
-        // create dir
-        fs::create_dir_all(create_path).map_err(|e| format!("Could not create dir: {e}"))?;
-        // set permissions back to readonly for whole path
-        set_path_read_only(root_path, create_path, existing_path)
-    })?;
-    // remove readonly from full path to allow students to write to it
-    remove_read_only(create_path)?;
+pub fn create_project_dir(
+    app_handle: tauri::AppHandle,
+    path: String,
+    read_only: bool,
+) -> Result<(), CommandError> {
+    let path = PathGuard::from_app(&app_handle).check(Path::new(&path))?;
+    create_dir(&path, read_only)?;
     Ok(())
 }
 
+pub fn create_dir(create_path: &Path, read_only: bool) -> Result<(), CommandError> {
+    let parent = create_path.parent().ok_or_else(|| no_parent(create_path))?;
+    let unlocked = PermissionGuard::unlock(parent)?;
+    fs::create_dir_all(create_path)
+        .map_err(|e| CommandError::io("Could not create dir", create_path, e))?;
+    if read_only {
+        set_read_only(create_path)?;
+    }
+    unlocked.restore()
+}
+
+/// Create the directories between the closest existing ancestor of `target` and `target` itself,
+/// temporarily lifting the read-only flag of that ancestor.
+pub fn create_missing_parents(root_dir: &Path, target: &Path) -> Result<(), CommandError> {
+    let Some(parent) = target.parent() else {
+        return Ok(());
+    };
+    if parent.is_dir() {
+        return Ok(());
+    }
+    let existing = get_existing_path(parent)?;
+    if !existing.starts_with(root_dir) {
+        return Err(
+            CommandError::new(ErrorKind::NotFound, "Project directory does not exist")
+                .with_path(root_dir),
+        );
+    }
+    let unlocked = PermissionGuard::unlock(existing)?;
+    fs::create_dir_all(parent).map_err(|e| CommandError::io("Could not create dir", parent, e))?;
+    unlocked.restore()
+}
+
+/// Write a project file. With `preserve`, local edits the new contents replace are kept as
+/// described by [`Preserve`] and the kept version is returned.
 #[tauri::command]
-pub fn create_project_dir(path: String, read_only: bool) -> Result<(), String> {
-    let create_path = Path::new(&path);
-    create_path
-        .parent()
-        .ok_or("Could not get parent path".to_string())
-        .and_then(|parent| {
-            let parent_permissions = remove_read_only(parent)?;
-
-            fs::create_dir_all(create_path).map_err(|e| format!("Could not create dir: {e}"))?;
-            if read_only {
-                set_read_only(create_path)?;
-            }
-            set_permissions(parent, parent_permissions)
-        })
+pub fn write_file(
+    app_handle: tauri::AppHandle,
+    path: String,
+    contents: Vec<u8>,
+    permissions: FilePermissions,
+    preserve: Option<Preserve>,
+) -> Result<Option<PreservedFile>, CommandError> {
+    let path = PathGuard::from_app(&app_handle).check(Path::new(&path))?;
+    let preserved = match preserve {
+        None => None,
+        Some(preserve) => preserve_file(&app_handle, &path, &preserve, Some(&contents))?,
+    };
+    write_file_from(&path, &mut contents.as_slice(), permissions)?;
+    Ok(preserved)
 }
 
+/// Remove a project file, keeping local edits like [`write_file`] does.
 #[tauri::command]
-pub fn write_file(path: String, contents: Vec<u8>, read_only: bool) -> Result<(), String> {
-    let create_path = Path::new(&path);
-    create_path
-        .parent()
-        .ok_or("Could not get parent path".to_string())
-        .and_then(|parent| {
-            let parent_permissions = remove_read_only(parent)?;
-
-            File::create(create_path)
-                .map_err(|e| format!("Could not create file: {e}"))
-                .and_then(|mut f| {
-                    f.write_all(&contents)
-                        .map_err(|e| format!("Could not write to file: {e}"))
-                })
-                .and_then(|_| {
-                    if read_only {
-                        set_read_only(create_path)
-                    } else {
-                        Ok(())
-                    }
-                })
-                .and_then(|_| set_permissions(parent, parent_permissions))
-        })
+pub fn remove_file(
+    app_handle: tauri::AppHandle,
+    path: String,
+    preserve: Option<Preserve>,
+) -> Result<Option<PreservedFile>, CommandError> {
+    let path = PathGuard::from_app(&app_handle).check(Path::new(&path))?;
+    let preserved = match preserve {
+        None => None,
+        Some(preserve) => preserve_file(&app_handle, &path, &preserve, None)?,
+    };
+    let parent = path.parent().ok_or_else(|| no_parent(&path))?;
+    let unlocked = PermissionGuard::unlock(parent)?;
+    remove_read_only(&path)?;
+    fs::remove_file(&path).map_err(|e| CommandError::io("Could not remove file", &path, e))?;
+    unlocked.restore()?;
+    Ok(preserved)
+}
+
+fn preserve_file(
+    app_handle: &tauri::AppHandle,
+    path: &Path,
+    preserve: &Preserve,
+    replacement: Option<&[u8]>,
+) -> Result<Option<PreservedFile>, CommandError> {
+    let root_dir = PathGuard::from_app(app_handle).check(Path::new(&preserve.root_dir))?;
+    PreservedFiles::from_app(app_handle, &preserve.project_id)?.preserve(
+        &root_dir,
+        path,
+        preserve.synced_hash.as_deref(),
+        preserve.mode,
+        replacement,
+    )
+}
+
+/// Replace the file at `create_path` atomically: the contents are written to a temp file next to
+/// it, flushed to disk and renamed into place, so a crash or full disk never leaves a truncated
+/// file behind. The parent's permissions are restored whether or not the write succeeds.
+pub fn write_file_from(
+    create_path: &Path,
+    contents: &mut impl Read,
+    permissions: FilePermissions,
+) -> Result<(), CommandError> {
+    let parent = create_path.parent().ok_or_else(|| no_parent(create_path))?;
+    let unlocked = PermissionGuard::unlock(parent)?;
+
+    let temp_path = temp_path_for(create_path);
+    let written = write_temp_file(&temp_path, contents, permissions)
+        .and_then(|_| replace_file(&temp_path, create_path))
+        .map(|_| sync_dir(parent));
+    if written.is_err() {
+        let _ = remove_read_only(&temp_path);
+        let _ = fs::remove_file(&temp_path);
+    }
+    written?;
+    unlocked.restore()
+}
+
+/// Hidden, so that change detection and the watcher skip it should it ever be left behind.
+fn temp_path_for(path: &Path) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(
+        ".{name}.{}.{}.tmp",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+fn write_temp_file(
+    temp_path: &Path,
+    contents: &mut impl Read,
+    permissions: FilePermissions,
+) -> Result<(), CommandError> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(temp_path)
+        .map_err(|e| CommandError::io("Could not create file", temp_path, e))?;
+    io::copy(contents, &mut file)
+        .map_err(|e| CommandError::io("Could not write to file", temp_path, e))?;
+    file.sync_all()
+        .map_err(|e| CommandError::io("Could not flush file", temp_path, e))?;
+    drop(file);
+    apply_permissions(temp_path, permissions)
+}
+
+/// Windows refuses to replace a read-only file, so the target is unlocked first and locked again
+/// if the rename fails.
+fn replace_file(temp_path: &Path, target: &Path) -> Result<(), CommandError> {
+    let previous = if target.is_file() {
+        Some(remove_read_only(target)?)
+    } else {
+        None
+    };
+    fs::rename(temp_path, target).map_err(|e| {
+        if let Some(permissions) = previous {
+            let _ = set_permissions(target, permissions);
+        }
+        CommandError::io("Could not replace file", target, e)
+    })
+}
+
+/// Persist the rename itself. Directories can't be opened for syncing on Windows.
+fn sync_dir(dir: &Path) {
+    #[cfg(unix)]
+    if let Err(e) = File::open(dir).and_then(|d| d.sync_all()) {
+        eprintln!("Could not sync dir '{}': {e}", dir.display());
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+}
+
+fn no_parent(path: &Path) -> CommandError {
+    CommandError::new(ErrorKind::InvalidInput, "Could not get parent path").with_path(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn replaces_read_only_files_in_read_only_dirs() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let file = dir.join("main.py");
+        fs::write(&file, "old").unwrap();
+        set_read_only(&file).unwrap();
+        set_read_only(dir).unwrap();
+
+        write_file_from(&file, &mut "new".as_bytes(), FilePermissions::R).unwrap();
+
+        assert_eq!(fs::read_to_string(&file).unwrap(), "new");
+        assert!(fs::metadata(&file).unwrap().permissions().readonly());
+        assert!(fs::metadata(dir).unwrap().permissions().readonly());
+        assert_eq!(entries(dir), vec!["main.py"]);
+        remove_read_only(dir).unwrap();
+    }
+
+    #[test]
+    fn guard_restores_permissions_on_panic() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        set_read_only(dir).unwrap();
+
+        let result = std::panic::catch_unwind(|| {
+            let _unlocked = PermissionGuard::unlock(dir).unwrap();
+            assert!(!fs::metadata(dir).unwrap().permissions().readonly());
+            panic!("sync failed");
+        });
+
+        assert!(result.is_err());
+        assert!(fs::metadata(dir).unwrap().permissions().readonly());
+        remove_read_only(dir).unwrap();
+    }
+
+    #[test]
+    fn failed_writes_keep_the_previous_contents() {
+        struct Failing;
+        impl Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::Other, "disk full"))
+            }
+        }
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let file = dir.join("main.py");
+        fs::write(&file, "old").unwrap();
+        set_read_only(dir).unwrap();
+
+        assert!(write_file_from(&file, &mut Failing, FilePermissions::Rw).is_err());
+
+        assert_eq!(fs::read_to_string(&file).unwrap(), "old");
+        assert!(fs::metadata(dir).unwrap().permissions().readonly());
+        assert_eq!(entries(dir), vec!["main.py"]);
+        remove_read_only(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn executables_keep_their_mode_bits() {
+        use std::os::unix::fs::PermissionsExt;
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        write_file_from(
+            &dir.join("run.sh"),
+            &mut "#!/bin/sh".as_bytes(),
+            FilePermissions::Rx,
+        )
+        .unwrap();
+        write_file_from(
+            &dir.join("main.py"),
+            &mut "".as_bytes(),
+            FilePermissions::Rw,
+        )
+        .unwrap();
+        set_read_only(dir).unwrap();
+        let _unlocked = PermissionGuard::unlock(dir).unwrap();
+
+        assert_eq!(mode(&dir.join("run.sh")), 0o555);
+        assert_eq!(mode(&dir.join("main.py")), 0o644);
+        assert_eq!(
+            mode(dir) & 0o022,
+            0,
+            "unlocking must not grant write access to others"
+        );
+    }
 }
@@ -1,16 +1,104 @@
+use std::collections::HashMap;
+use std::fs;
 use std::fs::File;
 use std::io;
+use std::path::Path;
 
 use data_encoding::HEXLOWER;
+use serde::Serialize;
 use sha2::{Digest, Sha256};
+use tauri::State;
 
+use crate::commands::error::CommandError;
+use crate::operations::OperationRegistry;
+use crate::utils::parallel::{map_bounded, run_blocking};
+use crate::utils::path_guard::PathGuard;
+use crate::utils::progress::ProgressReporter;
+
+#[tauri::command]
+pub async fn get_file_hash(
+    app_handle: tauri::AppHandle,
+    path: String,
+    progress_channel: Option<String>,
+) -> Result<String, CommandError> {
+    let path = PathGuard::from_app(&app_handle).check(Path::new(&path))?;
+    let progress = ProgressReporter::for_channel(&app_handle, progress_channel)?;
+    run_blocking(move || {
+        progress.set_total(fs::metadata(&path).map_or(0, |m| m.len()));
+        let hash = hash_file(&path, &progress);
+        progress.finish();
+        hash
+    })
+    .await
+}
+
+/// Result of `get_file_hashes` for one file.
+#[derive(Serialize, Debug)]
+#[serde(tag = "_tag", content = "value", rename_all = "camelCase")]
+pub enum FileHash {
+    Hashed(String),
+    /// The file could not be read or lies outside of the allowed directories.
+    Failed(CommandError),
+}
+
+impl From<Result<String, CommandError>> for FileHash {
+    fn from(result: Result<String, CommandError>) -> Self {
+        match result {
+            Ok(hash) => Self::Hashed(hash),
+            Err(e) => Self::Failed(e),
+        }
+    }
+}
+
+/// Hash many files relative to `root` at once. A file that cannot be read or lies outside of `root`
+/// yields `Failed` for its path instead of failing the whole batch. Cancelling the operation fails
+/// the batch as a whole.
 #[tauri::command]
-pub fn get_file_hash(path: String) -> Result<String, String> {
+pub async fn get_file_hashes(
+    app_handle: tauri::AppHandle,
+    operations: State<'_, OperationRegistry>,
+    root: String,
+    paths: Vec<String>,
+    progress_channel: Option<String>,
+    operation_id: Option<String>,
+) -> Result<HashMap<String, FileHash>, CommandError> {
+    let guard = PathGuard::from_app(&app_handle);
+    let root = guard.check(Path::new(&root))?;
+    let progress = ProgressReporter::for_channel(&app_handle, progress_channel)?;
+    let operation = operations.start(operation_id)?;
+    let cancel = operation.token();
+    run_blocking(move || {
+        progress.set_total(
+            paths
+                .iter()
+                .filter_map(|path| guard.check_relative(&root, path).ok())
+                .filter_map(|target| fs::metadata(target).ok())
+                .map(|m| m.len())
+                .sum(),
+        );
+        let hashes = map_bounded(&paths, |path| {
+            cancel.check()?;
+            let target = guard.check_relative(&root, path)?;
+            progress.start_file(path);
+            hash_file(&target, &progress)
+        });
+        cancel.check()?;
+        progress.finish();
+        Ok(paths
+            .into_iter()
+            .zip(hashes.into_iter().map(FileHash::from))
+            .collect())
+    })
+    .await
+}
+
+pub fn hash_file(path: &Path, progress: &ProgressReporter) -> Result<String, CommandError> {
     let mut hasher = Sha256::new();
 
-    let mut input = File::open(path).map_err(|e| format!("Could not open file: {e}"))?;
+    let input = File::open(path).map_err(|e| CommandError::io("Could not open file", path, e))?;
 
-    io::copy(&mut input, &mut hasher).map_err(|e| format!("Could not consume file: {e}"))?;
+    io::copy(&mut progress.reader(input), &mut hasher)
+        .map_err(|e| CommandError::io("Could not consume file", path, e))?;
     let digest = hasher.finalize();
 
     Ok(HEXLOWER.encode(digest.as_ref()))
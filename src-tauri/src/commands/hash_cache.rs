@@ -0,0 +1,357 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::commands::error::{CommandError, ErrorKind};
+use crate::commands::get_file_hash::hash_file;
+use crate::operations::CancelToken;
+use crate::utils::parallel::map_bounded;
+use crate::utils::progress::ProgressReporter;
+use crate::utils::project_data::project_data_path;
+
+/// Bump whenever the on-disk format changes so stale caches are discarded instead of misread.
+const CACHE_VERSION: u32 = 1;
+
+/// Files modified this recently are hashed but not cached: another write within the timestamp
+/// granularity of the filesystem would leave size and mtime unchanged and go unnoticed.
+const RACY_WINDOW: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+struct FileStamp {
+    size: u64,
+    mtime_secs: u64,
+    mtime_nanos: u32,
+    inode: Option<u64>,
+}
+
+impl FileStamp {
+    fn is_racy(&self) -> bool {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        now.saturating_sub(Duration::new(self.mtime_secs, self.mtime_nanos)) < RACY_WINDOW
+    }
+
+    fn of(path: &Path) -> Result<Self, CommandError> {
+        let metadata =
+            fs::metadata(path).map_err(|e| CommandError::io("Could not read metadata", path, e))?;
+        let mtime = metadata
+            .modified()
+            .map_err(|e| CommandError::io("Could not read modification time", path, e))?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Ok(Self {
+            size: metadata.len(),
+            mtime_secs: mtime.as_secs(),
+            mtime_nanos: mtime.subsec_nanos(),
+            inode: inode(&metadata),
+        })
+    }
+}
+
+#[cfg(unix)]
+fn inode(metadata: &fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn inode(_metadata: &fs::Metadata) -> Option<u64> {
+    None
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct CacheEntry {
+    stamp: FileStamp,
+    hash: String,
+}
+
+/// File hashes of a single project, keyed by the project-relative path. An entry is only reused
+/// while size, mtime and inode of the file are unchanged.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HashCache {
+    version: u32,
+    entries: HashMap<String, CacheEntry>,
+}
+
+impl Default for HashCache {
+    fn default() -> Self {
+        Self {
+            version: CACHE_VERSION,
+            entries: HashMap::new(),
+        }
+    }
+}
+
+impl HashCache {
+    /// A missing, unreadable or corrupt cache yields an empty one, i.e. a full rehash.
+    pub fn load(cache_file: &Path) -> Self {
+        let Ok(contents) = fs::read(cache_file) else {
+            return Self::default();
+        };
+        match serde_json::from_slice::<HashCache>(&contents) {
+            Ok(cache) if cache.version == CACHE_VERSION => cache,
+            Ok(_) => Self::default(),
+            Err(e) => {
+                eprintln!(
+                    "discarding corrupt hash cache '{}': {e}",
+                    cache_file.display()
+                );
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self, cache_file: &Path) -> Result<(), CommandError> {
+        if let Some(parent) = cache_file.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| CommandError::io("Could not create dir", parent, e))?;
+        }
+        let contents = serde_json::to_vec(self).map_err(|e| {
+            CommandError::new(
+                ErrorKind::Io,
+                format!("Could not serialize hash cache: {e}"),
+            )
+        })?;
+        fs::write(cache_file, contents)
+            .map_err(|e| CommandError::io("Could not write hash cache", cache_file, e))
+    }
+
+    /// Return the hashes of `files` (pairs of project path and absolute path), reading only the
+    /// files that changed since they were cached. Those are rehashed in parallel, reporting their
+    /// bytes to `progress`.
+    pub fn hash_all(
+        &mut self,
+        files: &[(String, PathBuf)],
+        progress: &ProgressReporter,
+        cancel: &CancelToken,
+    ) -> Result<Vec<String>, CommandError> {
+        let stamps = files
+            .iter()
+            .map(|(_, abs_path)| FileStamp::of(abs_path))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut hashes: Vec<Option<String>> = files
+            .iter()
+            .zip(&stamps)
+            .map(|((path, _), stamp)| {
+                self.entries
+                    .get(path)
+                    .filter(|entry| entry.stamp == *stamp)
+                    .map(|entry| entry.hash.clone())
+            })
+            .collect();
+
+        let misses: Vec<usize> = (0..files.len()).filter(|&i| hashes[i].is_none()).collect();
+        progress.set_total(misses.iter().map(|&i| stamps[i].size).sum());
+        let fresh = map_bounded(&misses, |&i| {
+            cancel.check()?;
+            progress.start_file(&files[i].0);
+            hash_file(&files[i].1, progress)
+        });
+        cancel.check()?;
+        for (i, hash) in misses.into_iter().zip(fresh) {
+            let hash = hash?;
+            let path = &files[i].0;
+            if stamps[i].is_racy() {
+                self.entries.remove(path);
+            } else {
+                self.entries.insert(
+                    path.clone(),
+                    CacheEntry {
+                        stamp: stamps[i].clone(),
+                        hash: hash.clone(),
+                    },
+                );
+            }
+            hashes[i] = Some(hash);
+        }
+        Ok(hashes.into_iter().flatten().collect())
+    }
+
+    /// Drop entries of files that no longer exist.
+    pub fn retain_paths(&mut self, paths: &HashSet<&str>) {
+        self.entries.retain(|path, _| paths.contains(path.as_str()));
+    }
+}
+
+pub fn cache_file(
+    app_handle: &tauri::AppHandle,
+    project_id: &str,
+) -> Result<PathBuf, CommandError> {
+    project_data_path(app_handle, "hash_cache", project_id).map(|path| path.with_extension("json"))
+}
+
+#[tauri::command]
+pub fn invalidate_hash_cache(
+    app_handle: tauri::AppHandle,
+    project_id: String,
+) -> Result<(), CommandError> {
+    let cache_file = cache_file(&app_handle, &project_id)?;
+    match fs::remove_file(&cache_file) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(CommandError::io(
+            "Could not remove hash cache",
+            &cache_file,
+            e,
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELLO_HASH: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    /// A cache holding `hash` for `main.py` with `stamp`, as if it had been cached earlier.
+    fn cache_with(stamp: FileStamp, hash: &str) -> HashCache {
+        let mut cache = HashCache::default();
+        cache.entries.insert(
+            "main.py".to_string(),
+            CacheEntry {
+                stamp,
+                hash: hash.to_string(),
+            },
+        );
+        cache
+    }
+
+    #[test]
+    fn entries_are_reused_only_while_the_stamp_matches() {
+        let tmp = tempfile::tempdir().unwrap();
+        let file = tmp.path().join("main.py");
+        fs::write(&file, "hello").unwrap();
+        let files = [("main.py".to_string(), file.clone())];
+        let stamp = FileStamp::of(&file).unwrap();
+
+        let mut cache = cache_with(stamp.clone(), "cached");
+        assert_eq!(
+            cache
+                .hash_all(&files, &ProgressReporter::silent(), &CancelToken::default())
+                .unwrap(),
+            ["cached"]
+        );
+
+        let changed = [
+            FileStamp {
+                size: stamp.size + 1,
+                ..stamp.clone()
+            },
+            FileStamp {
+                mtime_secs: stamp.mtime_secs - 1,
+                ..stamp.clone()
+            },
+            FileStamp {
+                mtime_nanos: (stamp.mtime_nanos + 1) % 1_000_000_000,
+                ..stamp.clone()
+            },
+            FileStamp {
+                inode: Some(stamp.inode.unwrap_or_default() + 1),
+                ..stamp.clone()
+            },
+        ];
+        for stale in changed {
+            let mut cache = cache_with(stale, "cached");
+            assert_eq!(
+                cache
+                    .hash_all(&files, &ProgressReporter::silent(), &CancelToken::default())
+                    .unwrap(),
+                [HELLO_HASH]
+            );
+        }
+    }
+
+    #[test]
+    fn recently_modified_files_are_not_cached() {
+        let tmp = tempfile::tempdir().unwrap();
+        let file = tmp.path().join("main.py");
+        fs::write(&file, "hello").unwrap();
+        let stamp = FileStamp::of(&file).unwrap();
+        assert!(stamp.is_racy());
+        assert!(!FileStamp {
+            mtime_secs: 0,
+            ..stamp
+        }
+        .is_racy());
+
+        let mut cache = HashCache::default();
+        let files = [("main.py".to_string(), file)];
+        assert_eq!(
+            cache
+                .hash_all(&files, &ProgressReporter::silent(), &CancelToken::default())
+                .unwrap(),
+            [HELLO_HASH]
+        );
+        assert!(cache.entries.is_empty());
+    }
+
+    #[test]
+    fn cancelling_stops_rehashing() {
+        let tmp = tempfile::tempdir().unwrap();
+        let file = tmp.path().join("main.py");
+        fs::write(&file, "hello").unwrap();
+        let stamp = FileStamp::of(&file).unwrap();
+        let files = [("main.py".to_string(), file)];
+
+        let cancel = CancelToken::default();
+        cancel.cancel();
+        let mut cache = cache_with(
+            FileStamp {
+                mtime_secs: 0,
+                ..stamp
+            },
+            "cached",
+        );
+        let result = cache.hash_all(&files, &ProgressReporter::silent(), &cancel);
+        assert_eq!(result.unwrap_err().kind, ErrorKind::Cancelled);
+        assert_eq!(cache.entries["main.py"].hash, "cached");
+    }
+
+    #[test]
+    fn corrupt_or_outdated_caches_are_discarded() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cache_file = tmp.path().join("cache/p1.json");
+        let stamp = FileStamp {
+            size: 5,
+            mtime_secs: 0,
+            mtime_nanos: 0,
+            inode: None,
+        };
+        cache_with(stamp, HELLO_HASH).save(&cache_file).unwrap();
+        assert_eq!(HashCache::load(&cache_file).entries.len(), 1);
+
+        fs::write(&cache_file, "{\"version\":1,\"entries\":{\"main.py\":").unwrap();
+        assert!(HashCache::load(&cache_file).entries.is_empty());
+
+        let outdated = serde_json::json!({ "version": CACHE_VERSION + 1, "entries": {} });
+        fs::write(&cache_file, outdated.to_string()).unwrap();
+        assert_eq!(HashCache::load(&cache_file).version, CACHE_VERSION);
+
+        assert!(HashCache::load(&tmp.path().join("missing.json"))
+            .entries
+            .is_empty());
+    }
+
+    #[test]
+    fn retain_paths_drops_removed_files() {
+        let stamp = FileStamp {
+            size: 5,
+            mtime_secs: 0,
+            mtime_nanos: 0,
+            inode: None,
+        };
+        let mut cache = cache_with(stamp.clone(), HELLO_HASH);
+        cache.entries.insert(
+            "removed.py".to_string(),
+            CacheEntry {
+                stamp,
+                hash: HELLO_HASH.to_string(),
+            },
+        );
+        cache.retain_paths(&["main.py", "other.py"].into_iter().collect());
+        assert_eq!(cache.entries.keys().collect::<Vec<_>>(), ["main.py"]);
+    }
+}
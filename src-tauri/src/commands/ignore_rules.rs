@@ -0,0 +1,190 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::commands::error::CommandError;
+use crate::commands::sync::is_visible;
+use crate::utils::ignore::{IgnoreRules, Rule, RuleSource};
+use crate::utils::path_guard::PathGuard;
+use crate::utils::project_data::project_data_path;
+
+/// Rules students can edit, in the root of the project directory.
+pub const PROJECT_RULES_FILE: &str = ".cxignore";
+
+/// Build outputs and caches of the usual languages and IDEs. Hidden entries such as `.idea` or
+/// `.venv` are skipped anyway.
+const DEFAULT_RULES: &str = "\
+__pycache__/
+*.py[cod]
+*.class
+*.o
+*.obj
+*.exe
+*.swp
+*~
+target/
+node_modules/
+venv/
+cmake-build-*/
+Thumbs.db
+";
+
+/// Why a path is excluded from syncing, or that it is not.
+#[derive(Serialize, PartialEq, Eq, Debug)]
+#[serde(tag = "_tag", content = "value", rename_all = "camelCase")]
+pub enum IgnoreExplanation {
+    Included,
+    /// The path, or the given parent directory, is hidden.
+    Hidden(String),
+    Ignored(MatchedRule),
+    /// The path was excluded by an earlier rule and is included again by a `!` rule.
+    Reincluded(MatchedRule),
+}
+
+#[derive(Serialize, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchedRule {
+    /// The path the rule matched, `path` itself or one of its parent directories.
+    pub path: String,
+    pub rule: Rule,
+}
+
+/// The rules of a project: the defaults, then the ones of its course and finally its
+/// `.cxignore`. Course rules are stored by [`write_course_ignore_rules`].
+pub fn load_rules(
+    app_handle: &tauri::AppHandle,
+    project_id: &str,
+    root_dir: &Path,
+) -> Result<IgnoreRules, CommandError> {
+    load_rules_from(&course_rules_file(app_handle, project_id)?, root_dir)
+}
+
+pub fn load_rules_from(course_file: &Path, root_dir: &Path) -> Result<IgnoreRules, CommandError> {
+    let mut rules = IgnoreRules::default().with(RuleSource::Default, DEFAULT_RULES);
+    for (source, path) in [
+        (RuleSource::Course, course_file.to_path_buf()),
+        (RuleSource::Project, root_dir.join(PROJECT_RULES_FILE)),
+    ] {
+        match fs::read_to_string(&path) {
+            Ok(text) => rules.add(source, &text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(CommandError::io("Could not read ignore rules", &path, e)),
+        }
+    }
+    Ok(rules)
+}
+
+pub fn course_rules_file(
+    app_handle: &tauri::AppHandle,
+    project_id: &str,
+) -> Result<PathBuf, CommandError> {
+    project_data_path(app_handle, "ignore", project_id).map(|dir| dir.join("course.cxignore"))
+}
+
+pub fn explain(rules: &IgnoreRules, path: &str, is_dir: bool) -> IgnoreExplanation {
+    let mut hidden = String::from(".");
+    for name in path.split('/').filter(|c| !c.is_empty() && *c != ".") {
+        hidden.push('/');
+        hidden.push_str(name);
+        if !is_visible(name) {
+            return IgnoreExplanation::Hidden(hidden);
+        }
+    }
+    match rules.explain(path, is_dir) {
+        None => IgnoreExplanation::Included,
+        Some((path, rule)) => {
+            let matched = MatchedRule {
+                path,
+                rule: rule.clone(),
+            };
+            if rule.is_negated() {
+                IgnoreExplanation::Reincluded(matched)
+            } else {
+                IgnoreExplanation::Ignored(matched)
+            }
+        }
+    }
+}
+
+/// Store the ignore rules delivered with a project by its course. An empty text removes them.
+#[tauri::command]
+pub fn write_course_ignore_rules(
+    app_handle: tauri::AppHandle,
+    project_id: String,
+    rules: String,
+) -> Result<(), CommandError> {
+    let path = course_rules_file(&app_handle, &project_id)?;
+    if rules.trim().is_empty() {
+        return match fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                Err(CommandError::io("Could not remove ignore rules", &path, e))
+            }
+            _ => Ok(()),
+        };
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| CommandError::io("Could not create directory", dir, e))?;
+    }
+    fs::write(&path, rules).map_err(|e| CommandError::io("Could not write ignore rules", &path, e))
+}
+
+/// Tell whether the file or directory at the project path `path` is synced, and which rule
+/// excludes it otherwise.
+#[tauri::command]
+pub fn explain_ignored(
+    app_handle: tauri::AppHandle,
+    project_id: String,
+    root_dir: String,
+    path: String,
+) -> Result<IgnoreExplanation, CommandError> {
+    let guard = PathGuard::from_app(&app_handle);
+    let root_dir = guard.check(Path::new(&root_dir))?;
+    let target = guard.check_relative(&root_dir, &path)?;
+    let rules = load_rules(&app_handle, &project_id, &root_dir)?;
+    Ok(explain(&rules, &path, target.is_dir()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn project_rules_override_course_and_defaults() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let course_file = dir.join("course.cxignore");
+        fs::write(&course_file, "*.csv\n").unwrap();
+        fs::write(dir.join(PROJECT_RULES_FILE), "!input.csv\n").unwrap();
+        let rules = load_rules_from(&course_file, dir).unwrap();
+
+        assert_eq!(
+            explain(&rules, "./src/__pycache__/a.pyc", false),
+            IgnoreExplanation::Ignored(MatchedRule {
+                path: "./src/__pycache__".to_string(),
+                rule: rules.matching_rule("__pycache__", true).unwrap().clone(),
+            })
+        );
+        assert!(rules.is_ignored("./data.csv", false));
+        assert!(matches!(
+            explain(&rules, "./input.csv", false),
+            IgnoreExplanation::Reincluded(MatchedRule {
+                rule: Rule {
+                    source: RuleSource::Project,
+                    ..
+                },
+                ..
+            })
+        ));
+        assert_eq!(
+            explain(&rules, "./.idea/workspace.xml", false),
+            IgnoreExplanation::Hidden("./.idea".to_string())
+        );
+        assert_eq!(
+            explain(&rules, "./main.py", false),
+            IgnoreExplanation::Included
+        );
+    }
+}
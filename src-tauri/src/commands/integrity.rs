@@ -0,0 +1,503 @@
+use std::collections::HashSet;
+use std::fs;
+use std::fs::Metadata;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::commands::error::CommandError;
+use crate::commands::fs_extra::{self, FilePermissions};
+use crate::commands::get_file_hash::hash_file;
+use crate::commands::sync::{is_visible, to_project_path, FileEntryType};
+use crate::utils::object_store::ObjectStore;
+use crate::utils::parallel::{map_bounded, run_blocking};
+use crate::utils::path_guard::{relative_path, PathGuard};
+use crate::utils::progress::ProgressReporter;
+use crate::utils::project_data::check_project_id;
+
+/// A file as it was recorded after the last successful sync, including its permissions.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestFile {
+    pub path: String,
+    pub hash: String,
+    #[serde(rename = "type")]
+    pub entry_type: FileEntryType,
+    pub permissions: FilePermissions,
+}
+
+/// A directory as it was recorded after the last successful sync.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestDir {
+    pub path: String,
+    pub permissions: FilePermissions,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum Tampering {
+    /// The content no longer matches the recorded hash, or the file was replaced by a directory.
+    Modified,
+    Removed,
+    /// The content is unchanged, but the read-only flag was lifted.
+    Writable,
+}
+
+#[derive(Serialize, PartialEq, Eq, Debug)]
+pub struct TamperedFile {
+    pub path: String,
+    pub tampering: Tampering,
+    pub restored: bool,
+}
+
+/// Compare the read-only files of `files` (the manifest of the last sync) against the project
+/// directory and report those that were changed.
+///
+/// With `restore`, modified and removed files are rewritten from their pristine copy in the object
+/// store (see `store_pristine_files`), if there is one, and get their permissions back. Files
+/// without an intact copy are left as they are and reported as not restored, for the sync to
+/// download them again. Files that were merely made writable are always made read-only again, as
+/// no content is lost.
+#[tauri::command]
+pub async fn check_read_only_files(
+    app_handle: tauri::AppHandle,
+    project_id: String,
+    root_dir: String,
+    files: Vec<ManifestFile>,
+    restore: bool,
+) -> Result<Vec<TamperedFile>, CommandError> {
+    let guard = PathGuard::from_app(&app_handle);
+    let root_dir = guard.check(Path::new(&root_dir))?;
+    check_project_id(&project_id)?;
+    let store = ObjectStore::from_app(&app_handle)?;
+    run_blocking(move || check_read_only(&guard, &root_dir, &store, &files, restore)).await
+}
+
+pub fn check_read_only(
+    guard: &PathGuard,
+    root_dir: &Path,
+    store: &ObjectStore,
+    files: &[ManifestFile],
+    restore: bool,
+) -> Result<Vec<TamperedFile>, CommandError> {
+    let read_only: Vec<&ManifestFile> = files
+        .iter()
+        .filter(|f| f.entry_type == FileEntryType::File && f.permissions.is_read_only())
+        .collect();
+    map_bounded(&read_only, |file| {
+        check_file(guard, root_dir, store, file, restore)
+    })
+    .into_iter()
+    .filter_map(Result::transpose)
+    .collect()
+}
+
+fn check_file(
+    guard: &PathGuard,
+    root_dir: &Path,
+    store: &ObjectStore,
+    file: &ManifestFile,
+    restore: bool,
+) -> Result<Option<TamperedFile>, CommandError> {
+    let target = guard.check_relative(root_dir, &file.path)?;
+    let tampering = match fs::metadata(&target) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Tampering::Removed,
+        Err(e) => return Err(CommandError::io("Could not read file metadata", &target, e)),
+        Ok(metadata) if !metadata.is_file() => Tampering::Modified,
+        Ok(metadata) => {
+            if hash_file(&target, &ProgressReporter::silent())? != file.hash {
+                Tampering::Modified
+            } else if metadata.permissions().readonly() {
+                return Ok(None);
+            } else {
+                Tampering::Writable
+            }
+        }
+    };
+
+    let restored = match tampering {
+        Tampering::Writable => {
+            fs_extra::apply_permissions(&target, file.permissions)?;
+            true
+        }
+        _ if restore => restore_file(root_dir, store, &target, file)?,
+        _ => false,
+    };
+    Ok(Some(TamperedFile {
+        path: file.path.clone(),
+        tampering,
+        restored,
+    }))
+}
+
+/// Rewrite `target` from its pristine copy. Returns `false` if there is no intact copy.
+fn restore_file(
+    root_dir: &Path,
+    store: &ObjectStore,
+    target: &Path,
+    file: &ManifestFile,
+) -> Result<bool, CommandError> {
+    let Some(mut reader) = store.open(&file.hash)? else {
+        return Ok(false);
+    };
+    // a damaged copy must not be passed off as the original
+    let pristine = store.object_path(&file.hash)?;
+    if target.is_dir() || hash_file(&pristine, &ProgressReporter::silent())? != file.hash {
+        return Ok(false);
+    }
+    fs_extra::create_missing_parents(root_dir, target)?;
+    fs_extra::write_file_from(target, &mut reader, file.permissions)?;
+    Ok(true)
+}
+
+/// Differences between the project directory and its manifest. Every list holds project paths in
+/// the `./dir/file` form, sorted.
+#[derive(Serialize, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectReport {
+    /// Entries of the manifest that do not exist, or exist as the other kind of entry.
+    pub missing: Vec<String>,
+    /// Visible entries that are not in the manifest. Nothing below an extra directory is listed.
+    pub extra: Vec<String>,
+    pub hash_mismatches: Vec<String>,
+    pub wrong_permissions: Vec<String>,
+    /// Directories that should be read-only, but are writable.
+    pub writable_dirs: Vec<String>,
+    /// Entries that are symlinks instead of regular files or directories. They are not followed.
+    pub symlinks: Vec<String>,
+}
+
+#[derive(Clone, Copy)]
+enum Finding {
+    Missing,
+    HashMismatch,
+    WrongPermissions,
+    WritableDir,
+    Symlink,
+}
+
+impl ProjectReport {
+    fn add(&mut self, path: &str, finding: Finding) {
+        let list = match finding {
+            Finding::Missing => &mut self.missing,
+            Finding::HashMismatch => &mut self.hash_mismatches,
+            Finding::WrongPermissions => &mut self.wrong_permissions,
+            Finding::WritableDir => &mut self.writable_dirs,
+            Finding::Symlink => &mut self.symlinks,
+        };
+        list.push(path.to_string());
+    }
+
+    fn sort(&mut self) {
+        for list in [
+            &mut self.missing,
+            &mut self.extra,
+            &mut self.hash_mismatches,
+            &mut self.wrong_permissions,
+            &mut self.writable_dirs,
+            &mut self.symlinks,
+        ] {
+            list.sort_unstable();
+        }
+    }
+}
+
+/// Compare the project directory against the manifest of the last sync: `files` as stored by the
+/// frontend and `dirs` with their permissions. Directories that only appear as ancestors of files
+/// are expected to exist, but their permissions are not checked.
+#[tauri::command]
+pub async fn verify_project(
+    app_handle: tauri::AppHandle,
+    root_dir: String,
+    files: Vec<ManifestFile>,
+    dirs: Vec<ManifestDir>,
+) -> Result<ProjectReport, CommandError> {
+    let guard = PathGuard::from_app(&app_handle);
+    let root_dir = guard.check(Path::new(&root_dir))?;
+    run_blocking(move || verify(&guard, &root_dir, &files, &dirs)).await
+}
+
+/// Recreate missing directories and reapply the permissions of the manifest to directories and
+/// files, then verify the project again. File contents are left alone, see
+/// `check_read_only_files` for restoring them. Symlinks are reported, but never touched.
+#[tauri::command]
+pub async fn repair_project(
+    app_handle: tauri::AppHandle,
+    root_dir: String,
+    files: Vec<ManifestFile>,
+    dirs: Vec<ManifestDir>,
+) -> Result<ProjectReport, CommandError> {
+    let guard = PathGuard::from_app(&app_handle);
+    let root_dir = guard.check(Path::new(&root_dir))?;
+    run_blocking(move || repair(&guard, &root_dir, &files, &dirs)).await
+}
+
+pub fn verify(
+    guard: &PathGuard,
+    root_dir: &Path,
+    files: &[ManifestFile],
+    dirs: &[ManifestDir],
+) -> Result<ProjectReport, CommandError> {
+    let files: Vec<&ManifestFile> = files
+        .iter()
+        .filter(|f| f.entry_type == FileEntryType::File)
+        .collect();
+    let mut report = ProjectReport::default();
+
+    for dir in dirs {
+        let (_, metadata) = entry_metadata(guard, root_dir, &dir.path)?;
+        if let Some(finding) = check_dir(metadata, dir.permissions) {
+            report.add(&dir.path, finding);
+        }
+    }
+    let findings = map_bounded(&files, |file| check_file_entry(guard, root_dir, file));
+    for (file, findings) in files.iter().zip(findings) {
+        for finding in findings? {
+            report.add(&file.path, finding);
+        }
+    }
+
+    // the manifest lists project paths, so every ancestor of an entry is expected as well
+    let mut expected = HashSet::new();
+    for path in files
+        .iter()
+        .map(|f| f.path.as_str())
+        .chain(dirs.iter().map(|d| d.path.as_str()))
+    {
+        let mut path = path;
+        while expected.insert(path) {
+            let Some(i) = path.rfind('/') else {
+                break;
+            };
+            path = &path[..i];
+        }
+    }
+    report.extra = find_extra(root_dir, &expected)?;
+    report.sort();
+    Ok(report)
+}
+
+pub fn repair(
+    guard: &PathGuard,
+    root_dir: &Path,
+    files: &[ManifestFile],
+    dirs: &[ManifestDir],
+) -> Result<ProjectReport, CommandError> {
+    // parents first, so every directory is created below its final parent
+    let mut sorted: Vec<&ManifestDir> = dirs.iter().collect();
+    sorted.sort_by_key(|d| d.path.matches('/').count());
+    for dir in sorted {
+        let (target, metadata) = entry_metadata(guard, root_dir, &dir.path)?;
+        match metadata {
+            None => {
+                fs_extra::create_missing_parents(root_dir, &target)?;
+                fs_extra::create_dir(&target, false)?;
+                fs_extra::apply_permissions(&target, dir.permissions)?;
+            }
+            Some(m) if m.is_dir() && !dir.permissions.matches(&m) => {
+                fs_extra::apply_permissions(&target, dir.permissions)?;
+            }
+            Some(_) => {}
+        }
+    }
+    for file in files.iter().filter(|f| f.entry_type == FileEntryType::File) {
+        if let (target, Some(m)) = entry_metadata(guard, root_dir, &file.path)? {
+            if m.is_file() && !file.permissions.matches(&m) {
+                fs_extra::apply_permissions(&target, file.permissions)?;
+            }
+        }
+    }
+    verify(guard, root_dir, files, dirs)
+}
+
+/// Resolve a manifest path without following a symlink at its end. Symlinks further up must not
+/// lead out of the allowed roots.
+fn entry_metadata(
+    guard: &PathGuard,
+    root_dir: &Path,
+    path: &str,
+) -> Result<(PathBuf, Option<Metadata>), CommandError> {
+    let target = root_dir.join(relative_path(path)?);
+    match fs::symlink_metadata(&target) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok((target, None)),
+        Err(e) => Err(CommandError::io("Could not read metadata", &target, e)),
+        Ok(metadata) if metadata.file_type().is_symlink() => Ok((target, Some(metadata))),
+        Ok(metadata) => {
+            guard.check(&target)?;
+            Ok((target, Some(metadata)))
+        }
+    }
+}
+
+fn check_dir(metadata: Option<Metadata>, permissions: FilePermissions) -> Option<Finding> {
+    match metadata {
+        None => Some(Finding::Missing),
+        Some(m) if m.file_type().is_symlink() => Some(Finding::Symlink),
+        Some(m) if !m.is_dir() => Some(Finding::Missing),
+        Some(m) if permissions.is_read_only() && !m.permissions().readonly() => {
+            Some(Finding::WritableDir)
+        }
+        Some(m) if !permissions.matches(&m) => Some(Finding::WrongPermissions),
+        Some(_) => None,
+    }
+}
+
+fn check_file_entry(
+    guard: &PathGuard,
+    root_dir: &Path,
+    file: &ManifestFile,
+) -> Result<Vec<Finding>, CommandError> {
+    let (target, metadata) = entry_metadata(guard, root_dir, &file.path)?;
+    let metadata = match metadata {
+        None => return Ok(vec![Finding::Missing]),
+        Some(m) if m.file_type().is_symlink() => return Ok(vec![Finding::Symlink]),
+        Some(m) if !m.is_file() => return Ok(vec![Finding::Missing]),
+        Some(m) => m,
+    };
+    let mut findings = Vec::new();
+    if hash_file(&target, &ProgressReporter::silent())? != file.hash {
+        findings.push(Finding::HashMismatch);
+    }
+    if !file.permissions.matches(&metadata) {
+        findings.push(Finding::WrongPermissions);
+    }
+    Ok(findings)
+}
+
+/// Visible entries below `root_dir` that are not `expected`. Symlinked directories are not entered.
+fn find_extra(root_dir: &Path, expected: &HashSet<&str>) -> Result<Vec<String>, CommandError> {
+    let mut extra = Vec::new();
+    let mut dirs = vec![root_dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries =
+            fs::read_dir(&dir).map_err(|e| CommandError::io("Could not read dir", &dir, e))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| CommandError::io("Could not read dir entry", &dir, e))?;
+            if !is_visible(&entry.file_name().to_string_lossy()) {
+                continue;
+            }
+            let path = entry.path();
+            let Ok(relative) = path.strip_prefix(root_dir) else {
+                continue;
+            };
+            let project_path = to_project_path(relative);
+            let file_type = entry
+                .file_type()
+                .map_err(|e| CommandError::io("Could not read file type", &path, e))?;
+            if !expected.contains(project_path.as_str()) {
+                extra.push(project_path);
+            } else if file_type.is_dir() {
+                dirs.push(path);
+            }
+        }
+    }
+    Ok(extra)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_and_restores_tampered_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let (root, store) = (dir.join("project"), ObjectStore::new(dir.join("pristine")));
+        fs::create_dir_all(&root).unwrap();
+        let root = root.canonicalize().unwrap();
+        let guard = PathGuard::new([root.clone()]);
+        let task = root.join("task.txt");
+        fs::write(&task, "original").unwrap();
+        fs_extra::set_read_only(&task).unwrap();
+
+        let files = [ManifestFile {
+            path: "./task.txt".to_string(),
+            hash: hash_file(&task, &ProgressReporter::silent()).unwrap(),
+            entry_type: FileEntryType::File,
+            permissions: FilePermissions::R,
+        }];
+        let check = |restore| check_read_only(&guard, &root, &store, &files, restore);
+        assert_eq!(check(false).unwrap(), []);
+        store
+            .update_project("p1", &[(files[0].hash.clone(), task.clone())])
+            .unwrap();
+
+        fs_extra::remove_read_only(&task).unwrap();
+        fs::write(&task, "changed").unwrap();
+        let tampered = |restored| {
+            vec![TamperedFile {
+                path: "./task.txt".to_string(),
+                tampering: Tampering::Modified,
+                restored,
+            }]
+        };
+        assert_eq!(check(false).unwrap(), tampered(false));
+        assert_eq!(check(true).unwrap(), tampered(true));
+        assert_eq!(fs::read_to_string(&task).unwrap(), "original");
+        assert!(fs::metadata(&task).unwrap().permissions().readonly());
+
+        fs_extra::remove_read_only(&task).unwrap();
+        fs::remove_file(&task).unwrap();
+        assert_eq!(check(true).unwrap()[0].tampering, Tampering::Removed);
+        assert_eq!(fs::read_to_string(&task).unwrap(), "original");
+    }
+
+    #[test]
+    fn reports_and_repairs_project_state() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("src")).unwrap();
+        let root = dir.canonicalize().unwrap();
+        let guard = PathGuard::new([root.clone()]);
+        fs::write(root.join("src/main.py"), "print('hi')").unwrap();
+        fs::write(root.join("notes.txt"), "extra").unwrap();
+        fs::write(root.join(".hidden"), "ignored").unwrap();
+
+        let files = [
+            ManifestFile {
+                path: "./src/main.py".to_string(),
+                hash: hash_file(&root.join("src/main.py"), &ProgressReporter::silent()).unwrap(),
+                entry_type: FileEntryType::File,
+                permissions: FilePermissions::R,
+            },
+            ManifestFile {
+                path: "./lib/util.py".to_string(),
+                hash: "0".repeat(64),
+                entry_type: FileEntryType::File,
+                permissions: FilePermissions::Rw,
+            },
+        ];
+        let dirs = [
+            ManifestDir {
+                path: "./src".to_string(),
+                permissions: FilePermissions::R,
+            },
+            ManifestDir {
+                path: "./lib".to_string(),
+                permissions: FilePermissions::Rw,
+            },
+        ];
+        let report = verify(&guard, &root, &files, &dirs).unwrap();
+        assert_eq!(
+            report,
+            ProjectReport {
+                missing: vec!["./lib".to_string(), "./lib/util.py".to_string()],
+                extra: vec!["./notes.txt".to_string()],
+                wrong_permissions: vec!["./src/main.py".to_string()],
+                writable_dirs: vec!["./src".to_string()],
+                ..ProjectReport::default()
+            }
+        );
+
+        let report = repair(&guard, &root, &files, &dirs).unwrap();
+        assert_eq!(report.missing, ["./lib/util.py"]);
+        assert!(report.wrong_permissions.is_empty() && report.writable_dirs.is_empty());
+        assert!(fs::metadata(root.join("src"))
+            .unwrap()
+            .permissions()
+            .readonly());
+        fs_extra::remove_read_only(&root.join("src")).unwrap();
+    }
+}
@@ -0,0 +1,171 @@
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::commands::error::CommandError;
+use crate::commands::fs_extra::{self, FilePermissions};
+use crate::utils::merge::merge;
+use crate::utils::object_store::ObjectStore;
+use crate::utils::parallel::run_blocking;
+use crate::utils::path_guard::PathGuard;
+
+/// A file changed both locally and remotely since the last sync.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ConflictingFile {
+    pub path: String,
+    /// Hash of the version of the last sync, whose pristine copy is the common base.
+    pub base_hash: String,
+    /// Current content on the server, as bytes since it may not be text.
+    pub remote: Vec<u8>,
+}
+
+#[derive(Serialize, PartialEq, Eq, Debug)]
+#[serde(tag = "_tag", content = "value", rename_all = "camelCase")]
+pub enum MergeOutcome {
+    /// The edits did not overlap, the merged text replaced the local file.
+    Merged,
+    /// Regions were edited on both sides. The local file is left unchanged, `text` is the merge
+    /// with those regions marked up like git does.
+    Conflicting { regions: usize, text: String },
+    /// The file cannot be merged as text, e.g. because it is binary or the base is unknown.
+    Unmergeable(String),
+}
+
+#[derive(Serialize, Debug)]
+pub struct MergeReport {
+    pub path: String,
+    pub outcome: MergeOutcome,
+}
+
+/// Merge the local and remote versions of conflicting files with the version of the last sync as
+/// base. Files without overlapping edits are rewritten with the merged text, keeping their
+/// permissions; they can then be uploaded like any local change.
+#[tauri::command]
+pub async fn merge_conflicts(
+    app_handle: tauri::AppHandle,
+    root_dir: String,
+    files: Vec<ConflictingFile>,
+) -> Result<Vec<MergeReport>, CommandError> {
+    let guard = PathGuard::from_app(&app_handle);
+    let root_dir = guard.check(Path::new(&root_dir))?;
+    let store = ObjectStore::from_app(&app_handle)?;
+    run_blocking(move || {
+        files
+            .into_iter()
+            .map(|file| {
+                let target = guard.check_relative(&root_dir, &file.path)?;
+                let outcome = merge_file(&store, &target, &file)?;
+                Ok(MergeReport {
+                    path: file.path,
+                    outcome,
+                })
+            })
+            .collect()
+    })
+    .await
+}
+
+pub fn merge_file(
+    store: &ObjectStore,
+    target: &Path,
+    file: &ConflictingFile,
+) -> Result<MergeOutcome, CommandError> {
+    let unmergeable = |reason: &str| Ok(MergeOutcome::Unmergeable(reason.to_string()));
+    let local = match fs::read(target) {
+        Ok(local) => local,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return unmergeable("The local file was removed")
+        }
+        Err(e) => return Err(CommandError::io("Could not read file", target, e)),
+    };
+    let Some(mut reader) = store.open(&file.base_hash)? else {
+        return unmergeable("The last synced version is not available");
+    };
+    let mut base = Vec::new();
+    reader
+        .read_to_end(&mut base)
+        .map_err(|e| CommandError::io("Could not read stored file", target, e))?;
+    let (Ok(base), Ok(local), Ok(remote)) = (
+        String::from_utf8(base),
+        String::from_utf8(local),
+        std::str::from_utf8(&file.remote),
+    ) else {
+        return unmergeable("Binary files cannot be merged");
+    };
+
+    let merged = merge(&base, &local, remote);
+    if merged.conflicts > 0 {
+        return Ok(MergeOutcome::Conflicting {
+            regions: merged.conflicts,
+            text: merged.text,
+        });
+    }
+    if merged.text != local {
+        let metadata = fs::metadata(target)
+            .map_err(|e| CommandError::io("Could not read file metadata", target, e))?;
+        fs_extra::write_file_from(
+            target,
+            &mut merged.text.as_bytes(),
+            FilePermissions::from_metadata(&metadata),
+        )?;
+    }
+    Ok(MergeOutcome::Merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data_encoding::HEXLOWER;
+    use sha2::{Digest, Sha256};
+
+    #[test]
+    fn conflicts_return_the_marked_up_text() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = ObjectStore::new(tmp.path().join("objects"));
+        let base = b"x = 1\n";
+        let base_hash = HEXLOWER.encode(&Sha256::digest(base));
+        store.insert(&base_hash, &mut &base[..]).unwrap();
+        let target = tmp.path().join("main.py");
+        fs::write(&target, "x = 2\n").unwrap();
+
+        let file = ConflictingFile {
+            path: "./main.py".to_string(),
+            base_hash,
+            remote: b"x = 3\n".to_vec(),
+        };
+        let MergeOutcome::Conflicting { regions, text } =
+            merge_file(&store, &target, &file).unwrap()
+        else {
+            panic!("expected a conflict");
+        };
+        assert_eq!(regions, 1);
+        assert!(text.contains("x = 2\n") && text.contains("x = 3\n"));
+        assert_eq!(fs::read_to_string(&target).unwrap(), "x = 2\n");
+    }
+
+    #[test]
+    fn binary_remote_versions_are_not_merged() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = ObjectStore::new(tmp.path().join("objects"));
+        let base = b"print('hi')\n";
+        let base_hash = HEXLOWER.encode(&Sha256::digest(base));
+        store.insert(&base_hash, &mut &base[..]).unwrap();
+        let target = tmp.path().join("main.py");
+        fs::write(&target, "print('hello')\n").unwrap();
+
+        let file = ConflictingFile {
+            path: "./main.py".to_string(),
+            base_hash,
+            remote: vec![0x7f, b'E', b'L', b'F', 0xff, 0xfe],
+        };
+        assert_eq!(
+            merge_file(&store, &target, &file).unwrap(),
+            MergeOutcome::Unmergeable("Binary files cannot be merged".to_string())
+        );
+        assert_eq!(fs::read_to_string(&target).unwrap(), "print('hello')\n");
+    }
+}
@@ -1,7 +1,23 @@
 pub mod build_tar;
+pub mod cancel_operation;
 pub mod create_jwt_token;
 pub mod create_keys;
+pub mod diff_files;
+pub mod error;
+pub mod extract_tar;
 pub mod fs_extra;
 pub mod get_file_hash;
+pub mod hash_cache;
+pub mod ignore_rules;
+pub mod integrity;
+pub mod merge_conflicts;
 pub mod path;
+pub mod preserved_files;
+pub mod pristine;
+pub mod project_history;
+pub mod snapshots;
+pub mod sync;
 pub mod system_info;
+pub mod upload_archive;
+pub mod upload_policy;
+pub mod watch_projects;
@@ -1,16 +1,27 @@
 use std::path::Path;
 
+use crate::commands::error::{CommandError, ErrorKind};
+
 #[tauri::command]
-pub fn path_remove_ancestor(ancestor: String, to: String) -> Result<String, String> {
+pub fn path_remove_ancestor(ancestor: String, to: String) -> Result<String, CommandError> {
     let ancestor = Path::new(&ancestor);
     let to = Path::new(&to);
     let relative = to.strip_prefix(ancestor).map_err(|e| {
-        eprintln!("{}", e);
-        "Could not strip ancestor directory".to_string()
+        CommandError::new(
+            ErrorKind::InvalidInput,
+            format!("Could not strip ancestor directory: {e}"),
+        )
+        .with_path(to)
     })?;
     Path::new(".")
         .join(relative)
         .into_os_string()
         .into_string()
-        .map_err(|_| "Could not convert result to String".to_string())
+        .map_err(|_| {
+            CommandError::new(
+                ErrorKind::InvalidInput,
+                "Could not convert result to String",
+            )
+            .with_path(relative)
+        })
 }
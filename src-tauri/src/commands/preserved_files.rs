@@ -0,0 +1,336 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use chrono::{DateTime, Local};
+use data_encoding::HEXLOWER;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::commands::error::{CommandError, ErrorKind};
+use crate::commands::fs_extra::{self, FilePermissions};
+use crate::commands::sync::to_project_path;
+use crate::utils::path_guard::PathGuard;
+use crate::utils::project_data::project_data_path;
+
+/// Serializes updates of the manifests, which are read, modified and written as a whole.
+static LOCK: Mutex<()> = Mutex::new(());
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum PreserveMode {
+    /// Next to the original, e.g. `main.conflict-2026-10-16T12-00.cpp`.
+    ConflictCopy,
+    /// In the app-local data dir, out of the project directory.
+    Trash,
+}
+
+/// Asks a write or removal to keep the local content it replaces, if that content was edited
+/// since the last sync.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Preserve {
+    pub project_id: String,
+    pub root_dir: String,
+    /// Hash of the file at the last sync, `None` if it was never synced.
+    pub synced_hash: Option<String>,
+    pub mode: PreserveMode,
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PreservedFile {
+    pub id: String,
+    /// Project path of the replaced file.
+    pub path: String,
+    pub mode: PreserveMode,
+    /// Project path of the conflict copy, or the name of the file in the trash.
+    pub copy: String,
+    /// Local time of the replacement in RFC 3339 format.
+    pub preserved_at: String,
+}
+
+/// The replaced local versions of a project and their manifest, kept in
+/// `{app-local data dir}/preserved/{project_id}`.
+pub struct PreservedFiles {
+    dir: PathBuf,
+}
+
+impl PreservedFiles {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn from_app(app_handle: &tauri::AppHandle, project_id: &str) -> Result<Self, CommandError> {
+        project_data_path(app_handle, "preserved", project_id).map(Self::new)
+    }
+
+    /// The manifest, without entries whose copy was deleted in the meantime.
+    pub fn list(&self, root_dir: &Path) -> Result<Vec<PreservedFile>, CommandError> {
+        let _lock = lock();
+        let mut files = self.load()?;
+        files.retain(|file| {
+            self.copy_path(root_dir, file)
+                .map_or(false, |copy| copy.is_file())
+        });
+        Ok(files)
+    }
+
+    /// Project paths of the conflict copies, which must not be uploaded.
+    pub fn conflict_copies(&self) -> Result<Vec<String>, CommandError> {
+        let _lock = lock();
+        Ok(self
+            .load()?
+            .into_iter()
+            .filter(|file| file.mode == PreserveMode::ConflictCopy)
+            .map(|file| file.copy)
+            .collect())
+    }
+
+    /// Keep the current content of `target` before it is replaced by `replacement`, or removed if
+    /// that is `None`. Nothing is kept if the file doesn't exist, wasn't edited since the last
+    /// sync or already has the new content.
+    pub fn preserve(
+        &self,
+        root_dir: &Path,
+        target: &Path,
+        synced_hash: Option<&str>,
+        mode: PreserveMode,
+        replacement: Option<&[u8]>,
+    ) -> Result<Option<PreservedFile>, CommandError> {
+        let current = match fs::read(target) {
+            Ok(current) => current,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(CommandError::io("Could not read file", target, e)),
+        };
+        let unedited = synced_hash.map_or(false, |hash| {
+            HEXLOWER.encode(Sha256::digest(&current).as_ref()) == hash
+        });
+        if unedited || replacement == Some(current.as_slice()) {
+            return Ok(None);
+        }
+
+        let now = Local::now();
+        let id = format!(
+            "{}-{}",
+            now.format("%Y%m%d%H%M%S%3f"),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        );
+        let (copy, copy_path) = match mode {
+            PreserveMode::ConflictCopy => {
+                let copy_path = conflict_copy_path(target, &now);
+                let copy = to_project_path(copy_path.strip_prefix(root_dir).map_err(|_| {
+                    CommandError::new(ErrorKind::InvalidInput, "File is outside of the project")
+                        .with_path(target)
+                })?);
+                (copy, copy_path)
+            }
+            PreserveMode::Trash => (id.clone(), self.dir.join("trash").join(&id)),
+        };
+        let path = to_project_path(target.strip_prefix(root_dir).unwrap_or(target));
+        let preserved = PreservedFile {
+            id,
+            path,
+            mode,
+            copy,
+            preserved_at: now.to_rfc3339(),
+        };
+
+        // recorded before the copy exists: a conflict copy missing from the manifest would be
+        // synced as a new file of the project, while `list` skips entries without their copy
+        {
+            let _lock = lock();
+            let mut files = self.load()?;
+            files.push(preserved.clone());
+            self.save(&files)?;
+        }
+        if let Err(e) = write_copy(&copy_path, &current) {
+            let _lock = lock();
+            if let Ok(mut files) = self.load() {
+                files.retain(|file| file.id != preserved.id);
+                if let Err(e) = self.save(&files) {
+                    eprintln!("{e}");
+                }
+            }
+            return Err(e);
+        }
+        Ok(Some(preserved))
+    }
+
+    /// Put a preserved version back in place of the file it replaced and drop it.
+    pub fn restore(
+        &self,
+        guard: &PathGuard,
+        root_dir: &Path,
+        id: &str,
+    ) -> Result<(), CommandError> {
+        let _lock = lock();
+        let mut files = self.load()?;
+        let Some(index) = files.iter().position(|file| file.id == id) else {
+            return Err(CommandError::new(
+                ErrorKind::NotFound,
+                format!("No preserved file with id '{id}'"),
+            ));
+        };
+        let copy = self.copy_path(root_dir, &files[index])?;
+        let target = guard.check_relative(root_dir, &files[index].path)?;
+        let content =
+            fs::read(&copy).map_err(|e| CommandError::io("Could not read file", &copy, e))?;
+        let permissions = fs::metadata(&target)
+            .map_or(FilePermissions::Rw, |m| FilePermissions::from_metadata(&m));
+        fs_extra::create_missing_parents(root_dir, &target)?;
+        fs_extra::write_file_from(&target, &mut content.as_slice(), permissions)?;
+        fs::remove_file(&copy).map_err(|e| CommandError::io("Could not remove file", &copy, e))?;
+        files.remove(index);
+        self.save(&files)
+    }
+
+    fn copy_path(&self, root_dir: &Path, file: &PreservedFile) -> Result<PathBuf, CommandError> {
+        match file.mode {
+            PreserveMode::ConflictCopy => {
+                PathGuard::new([root_dir.to_path_buf()]).check_relative(root_dir, &file.copy)
+            }
+            PreserveMode::Trash => Ok(self.dir.join("trash").join(&file.id)),
+        }
+    }
+
+    fn manifest(&self) -> PathBuf {
+        self.dir.join("manifest.json")
+    }
+
+    fn load(&self) -> Result<Vec<PreservedFile>, CommandError> {
+        let manifest = self.manifest();
+        match fs::read(&manifest) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| {
+                CommandError::new(ErrorKind::Io, format!("Invalid manifest: {e}"))
+                    .with_path(&manifest)
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(CommandError::io("Could not read manifest", &manifest, e)),
+        }
+    }
+
+    fn save(&self, files: &[PreservedFile]) -> Result<(), CommandError> {
+        let json = serde_json::to_vec(files).map_err(|e| {
+            CommandError::new(ErrorKind::Io, format!("Could not serialize manifest: {e}"))
+        })?;
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| CommandError::io("Could not create dir", &self.dir, e))?;
+        fs_extra::write_file_from(&self.manifest(), &mut json.as_slice(), FilePermissions::Rw)
+    }
+}
+
+fn lock() -> MutexGuard<'static, ()> {
+    LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn write_copy(copy: &Path, content: &[u8]) -> Result<(), CommandError> {
+    if let Some(dir) = copy.parent() {
+        fs::create_dir_all(dir).map_err(|e| CommandError::io("Could not create dir", dir, e))?;
+    }
+    fs_extra::write_file_from(copy, &mut &content[..], FilePermissions::Rw)
+}
+
+/// `dir/main.cpp` becomes `dir/main.conflict-2026-10-16T12-00.cpp`, with a counter appended to
+/// the timestamp if that name is taken.
+fn conflict_copy_path(target: &Path, now: &DateTime<Local>) -> PathBuf {
+    let stem = target.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = target
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    let timestamp = now.format("%Y-%m-%dT%H-%M");
+    (1..)
+        .map(|n| match n {
+            1 => format!("{stem}.conflict-{timestamp}{extension}"),
+            n => format!("{stem}.conflict-{timestamp}-{n}{extension}"),
+        })
+        .map(|name| target.with_file_name(name))
+        .find(|path| !path.exists())
+        .expect("some conflict copy name is free")
+}
+
+#[tauri::command]
+pub fn list_preserved_files(
+    app_handle: tauri::AppHandle,
+    project_id: String,
+    root_dir: String,
+) -> Result<Vec<PreservedFile>, CommandError> {
+    let root_dir = PathGuard::from_app(&app_handle).check(Path::new(&root_dir))?;
+    PreservedFiles::from_app(&app_handle, &project_id)?.list(&root_dir)
+}
+
+/// Restore a version kept by a write or removal with `preserve`. The current file is overwritten.
+#[tauri::command]
+pub fn restore_preserved_file(
+    app_handle: tauri::AppHandle,
+    project_id: String,
+    root_dir: String,
+    id: String,
+) -> Result<(), CommandError> {
+    let guard = PathGuard::from_app(&app_handle);
+    let root_dir = guard.check(Path::new(&root_dir))?;
+    PreservedFiles::from_app(&app_handle, &project_id)?.restore(&guard, &root_dir, &id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_edited_files_and_restores_them() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("project")).unwrap();
+        let root = dir.join("project").canonicalize().unwrap();
+        let guard = PathGuard::new([root.clone()]);
+        let preserved = PreservedFiles::new(dir.join("preserved"));
+        let main = root.join("main.cpp");
+        fs::write(&main, "synced").unwrap();
+        let synced_hash = HEXLOWER.encode(Sha256::digest(b"synced").as_ref());
+
+        let preserve = |mode| {
+            preserved
+                .preserve(&root, &main, Some(&synced_hash), mode, Some(b"remote"))
+                .unwrap()
+        };
+        assert_eq!(preserve(PreserveMode::ConflictCopy), None);
+
+        fs::write(&main, "edited").unwrap();
+        let copy = preserve(PreserveMode::ConflictCopy).unwrap();
+        assert!(copy.copy.starts_with("./main.conflict-") && copy.copy.ends_with(".cpp"));
+        let trashed = preserve(PreserveMode::Trash).unwrap();
+        assert_eq!(
+            preserved.conflict_copies().unwrap(),
+            vec![copy.copy.clone()]
+        );
+        assert_eq!(preserved.list(&root).unwrap().len(), 2);
+
+        fs::write(&main, "remote").unwrap();
+        preserved.restore(&guard, &root, &copy.id).unwrap();
+        assert_eq!(fs::read_to_string(&main).unwrap(), "edited");
+        assert_eq!(preserved.list(&root).unwrap(), [trashed]);
+    }
+
+    #[test]
+    fn failed_copies_leave_no_manifest_entry() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("project");
+        fs::create_dir_all(&root).unwrap();
+        let main = root.join("main.cpp");
+        fs::write(&main, "edited").unwrap();
+        let preserved = PreservedFiles::new(tmp.path().join("preserved"));
+        // the trash can't be created where a file is in the way
+        fs::create_dir_all(tmp.path().join("preserved")).unwrap();
+        fs::write(tmp.path().join("preserved/trash"), "").unwrap();
+
+        assert!(preserved
+            .preserve(&root, &main, None, PreserveMode::Trash, None)
+            .is_err());
+        assert_eq!(preserved.load().unwrap(), []);
+    }
+}
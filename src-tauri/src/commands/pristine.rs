@@ -0,0 +1,51 @@
+use std::path::Path;
+
+use crate::commands::error::CommandError;
+use crate::commands::integrity::ManifestFile;
+use crate::commands::sync::FileEntryType;
+use crate::utils::object_store::ObjectStore;
+use crate::utils::parallel::run_blocking;
+use crate::utils::path_guard::PathGuard;
+
+/// Keep the files of a project as they are right after a sync, i.e. the last-synced remote version,
+/// in the object store. They replace the copies of the previous sync. Returns the paths that were
+/// changed or removed since they were hashed and could not be stored.
+#[tauri::command]
+pub async fn store_pristine_files(
+    app_handle: tauri::AppHandle,
+    project_id: String,
+    root_dir: String,
+    files: Vec<ManifestFile>,
+) -> Result<Vec<String>, CommandError> {
+    let guard = PathGuard::from_app(&app_handle);
+    let root_dir = guard.check(Path::new(&root_dir))?;
+    let store = ObjectStore::from_app(&app_handle)?;
+    run_blocking(move || {
+        let files: Vec<&ManifestFile> = files
+            .iter()
+            .filter(|f| f.entry_type == FileEntryType::File)
+            .collect();
+        let sources = files
+            .iter()
+            .map(|f| Ok((f.hash.clone(), guard.check_relative(&root_dir, &f.path)?)))
+            .collect::<Result<Vec<_>, CommandError>>()?;
+        let changed = store.update_project(&project_id, &sources)?;
+        Ok(files
+            .iter()
+            .zip(&sources)
+            .filter(|(_, (_, source))| changed.contains(source))
+            .map(|(f, _)| f.path.clone())
+            .collect())
+    })
+    .await
+}
+
+/// Drop the pristine copies of a removed project, unless another project shares them.
+#[tauri::command]
+pub async fn remove_pristine_files(
+    app_handle: tauri::AppHandle,
+    project_id: String,
+) -> Result<(), CommandError> {
+    let store = ObjectStore::from_app(&app_handle)?;
+    run_blocking(move || store.remove_project(&project_id)).await
+}
@@ -0,0 +1,262 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use gix::objs::tree::{Entry, EntryKind};
+use gix::objs::Tree;
+use gix::ObjectId;
+use serde::Deserialize;
+
+use crate::commands::error::{CommandError, ErrorKind};
+use crate::commands::fs_extra::FilePermissions;
+use crate::commands::ignore_rules::load_rules;
+use crate::commands::preserved_files::PreservedFiles;
+use crate::commands::sync::list_local_files;
+use crate::utils::parallel::run_blocking;
+use crate::utils::path_guard::PathGuard;
+
+/// Author and committer of the sync commits.
+const IDENTITY: [&str; 2] = [
+    "user.name=Code Expert Sync",
+    "user.email=sync@code-expert.local",
+];
+
+/// The project paths a sync transferred, named in the commit message.
+#[derive(Deserialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncedChanges {
+    pub uploaded: Vec<String>,
+    pub downloaded: Vec<String>,
+    /// Removed locally as they were removed from the server.
+    pub deleted: Vec<String>,
+}
+
+/// Record the project directory in the git repository at its root, creating the repository if
+/// needed. Returns the id of the new commit, or `None` if nothing changed since the last one.
+/// Conflict copies and ignored files are left out, as they are from uploads.
+#[tauri::command]
+pub async fn commit_project_history(
+    app_handle: tauri::AppHandle,
+    project_id: String,
+    root_dir: String,
+    changes: SyncedChanges,
+) -> Result<Option<String>, CommandError> {
+    let root_dir = PathGuard::from_app(&app_handle).check(Path::new(&root_dir))?;
+    let conflict_copies = PreservedFiles::from_app(&app_handle, &project_id)?.conflict_copies()?;
+    let rules = load_rules(&app_handle, &project_id, &root_dir)?;
+    run_blocking(move || {
+        let files = list_local_files(&root_dir, &rules)?
+            .into_iter()
+            .filter(|(path, _)| !conflict_copies.contains(path))
+            .collect::<Vec<_>>();
+        commit(&root_dir, &files, &commit_message(&changes))
+    })
+    .await
+}
+
+pub fn commit(
+    root_dir: &Path,
+    files: &[(String, PathBuf)],
+    message: &str,
+) -> Result<Option<String>, CommandError> {
+    let mut repo = if root_dir.join(".git").is_dir() {
+        gix::open(root_dir).map_err(git_error(root_dir, "Could not open repository"))?
+    } else {
+        gix::init(root_dir).map_err(git_error(root_dir, "Could not create repository"))?
+    };
+
+    let mut root = TreeNode::default();
+    for (path, abs_path) in files {
+        let content =
+            fs::read(abs_path).map_err(|e| CommandError::io("Could not read file", abs_path, e))?;
+        let metadata = fs::metadata(abs_path)
+            .map_err(|e| CommandError::io("Could not read file metadata", abs_path, e))?;
+        let kind = if FilePermissions::from_metadata(&metadata).is_executable() {
+            EntryKind::BlobExecutable
+        } else {
+            EntryKind::Blob
+        };
+        let id = repo
+            .write_blob(content)
+            .map_err(git_error(abs_path, "Could not store file"))?;
+        root.insert(path.trim_start_matches("./"), kind, id.detach());
+    }
+    let tree = root.write(&repo, root_dir)?;
+
+    let parent = repo.head_commit().ok();
+    let parent_tree = parent.as_ref().and_then(|commit| commit.tree_id().ok());
+    let unchanged = match parent_tree {
+        Some(parent_tree) => parent_tree == tree,
+        None => files.is_empty(),
+    };
+    if unchanged {
+        return Ok(None);
+    }
+
+    let parent = parent.map(|commit| commit.id);
+    // only for this instance, the student's own commits keep their identity
+    let mut config = repo.config_snapshot_mut();
+    config
+        .append_config(IDENTITY, gix::config::Source::Api)
+        .map_err(git_error(root_dir, "Could not configure repository"))?;
+    let repo = config
+        .commit()
+        .map_err(git_error(root_dir, "Could not configure repository"))?;
+    let commit = repo
+        .commit("HEAD", message, tree, parent)
+        .map_err(git_error(root_dir, "Could not commit"))?;
+    // without an index matching the commit, git would report every file as deleted and untracked
+    let mut index = repo
+        .index_from_tree(&tree)
+        .map_err(git_error(root_dir, "Could not create index"))?;
+    index
+        .write(Default::default())
+        .map_err(git_error(root_dir, "Could not write index"))?;
+    Ok(Some(commit.to_string()))
+}
+
+/// `Upload main.cpp` or `Sync: upload 2 files, delete 1 file`, followed by the list of paths.
+pub fn commit_message(changes: &SyncedChanges) -> String {
+    let kinds: Vec<_> = [
+        ("Upload", "Uploaded", &changes.uploaded[..]),
+        ("Download", "Downloaded", &changes.downloaded[..]),
+        ("Delete", "Deleted", &changes.deleted[..]),
+    ]
+    .into_iter()
+    .filter(|(_, _, paths)| !paths.is_empty())
+    .collect();
+    let subject = match &kinds[..] {
+        [] => "Sync".to_string(),
+        [(verb, _, [path])] => format!("{verb} {}", path.trim_start_matches("./")),
+        [(verb, _, paths)] => format!("{verb} {}", count_files(paths.len())),
+        kinds => {
+            let counts: Vec<_> = kinds
+                .iter()
+                .map(|(verb, _, paths)| {
+                    format!("{} {}", verb.to_lowercase(), count_files(paths.len()))
+                })
+                .collect();
+            format!("Sync: {}", counts.join(", "))
+        }
+    };
+    let mut message = format!("{subject}\n");
+    for (_, heading, paths) in kinds {
+        message.push_str(&format!("\n{heading}:\n"));
+        for path in paths {
+            message.push_str(&format!("  {}\n", path.trim_start_matches("./")));
+        }
+    }
+    message
+}
+
+fn count_files(count: usize) -> String {
+    match count {
+        1 => "1 file".to_string(),
+        count => format!("{count} files"),
+    }
+}
+
+fn git_error<E: Display>(path: &Path, message: &'static str) -> impl FnOnce(E) -> CommandError {
+    let path = path.to_path_buf();
+    move |e| CommandError::new(ErrorKind::Io, format!("{message}: {e}")).with_path(path)
+}
+
+/// Directory tree of the blobs to commit, written bottom-up.
+#[derive(Default)]
+struct TreeNode {
+    files: Vec<(String, EntryKind, ObjectId)>,
+    dirs: BTreeMap<String, TreeNode>,
+}
+
+impl TreeNode {
+    fn insert(&mut self, path: &str, kind: EntryKind, id: ObjectId) {
+        match path.split_once('/') {
+            Some((dir, rest)) => self
+                .dirs
+                .entry(dir.to_string())
+                .or_default()
+                .insert(rest, kind, id),
+            None => self.files.push((path.to_string(), kind, id)),
+        }
+    }
+
+    fn write(&self, repo: &gix::Repository, root_dir: &Path) -> Result<ObjectId, CommandError> {
+        let mut entries = Vec::new();
+        for (name, dir) in &self.dirs {
+            entries.push(Entry {
+                mode: EntryKind::Tree.into(),
+                filename: name.as_str().into(),
+                oid: dir.write(repo, root_dir)?,
+            });
+        }
+        for (name, kind, id) in &self.files {
+            entries.push(Entry {
+                mode: (*kind).into(),
+                filename: name.as_str().into(),
+                oid: *id,
+            });
+        }
+        // git orders entries by name, with directory names compared as if followed by '/'
+        entries.sort();
+        repo.write_object(&Tree { entries })
+            .map(|id| id.detach())
+            .map_err(git_error(root_dir, "Could not store directory"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::ignore::IgnoreRules;
+
+    #[test]
+    fn commits_only_changed_trees() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("main.cpp"), "int main() {}").unwrap();
+        fs::write(dir.join("src/lib.cpp"), "").unwrap();
+        let files = list_local_files(dir, &IgnoreRules::default()).unwrap();
+        let changes = SyncedChanges {
+            uploaded: vec!["./main.cpp".to_string()],
+            downloaded: vec![],
+            deleted: vec![],
+        };
+
+        let message = commit_message(&changes);
+        assert_eq!(message, "Upload main.cpp\n\nUploaded:\n  main.cpp\n");
+        let first = commit(dir, &files, &message).unwrap().unwrap();
+        assert_eq!(commit(dir, &files, "again").unwrap(), None);
+
+        fs::write(dir.join("main.cpp"), "int main() { return 1; }").unwrap();
+        let second = commit(dir, &files, "edit").unwrap().unwrap();
+        let repo = gix::open(dir).unwrap();
+        let head = repo.head_commit().unwrap();
+        assert_eq!(head.id.to_string(), second);
+        assert_eq!(head.parent_ids().next().unwrap().to_string(), first);
+        assert!(repo.index_path().is_file());
+        // the repository itself is not part of the project files
+        assert_eq!(
+            list_local_files(dir, &IgnoreRules::default())
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn deleted_files_are_named_apart_from_downloads() {
+        let changes = SyncedChanges {
+            uploaded: vec![],
+            downloaded: vec!["./a.cpp".to_string(), "./b.cpp".to_string()],
+            deleted: vec!["./old.cpp".to_string()],
+        };
+        assert_eq!(
+            commit_message(&changes),
+            "Sync: download 2 files, delete 1 file\n\n\
+             Downloaded:\n  a.cpp\n  b.cpp\n\n\
+             Deleted:\n  old.cpp\n"
+        );
+    }
+}
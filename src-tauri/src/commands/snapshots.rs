@@ -0,0 +1,732 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use brotli::Decompressor;
+use chrono::{DateTime, Duration, Local};
+use data_encoding::HEXLOWER;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::commands::build_tar::{write_tar_entries, TarOptions};
+use crate::commands::error::{CommandError, ErrorKind};
+use crate::commands::fs_extra::{self, FilePermissions};
+use crate::commands::hash_cache::{self, HashCache};
+use crate::commands::ignore_rules::load_rules;
+use crate::commands::sync::{get_local_changes, scan_local_files, FileEntryType, LocalFileChange};
+use crate::commands::sync::{LocalFileState, PreviousFileInfo};
+use crate::operations::CancelToken;
+use crate::utils::compression::{Codec, Level, Preset};
+use crate::utils::parallel::run_blocking;
+use crate::utils::path_guard::PathGuard;
+use crate::utils::progress::ProgressReporter;
+use crate::utils::project_data::project_data_path;
+use crate::utils::settings::read_setting;
+
+/// Serializes changes to the snapshots, so pruning never removes a pack a snapshot being created
+/// refers to.
+static LOCK: Mutex<()> = Mutex::new(());
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// How many snapshots are kept, read from the `snapshotRetention` setting. The snapshot just taken
+/// and one about to be restored are always kept.
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Retention {
+    pub max_count: usize,
+    pub max_age_days: i64,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self {
+            max_count: 20,
+            max_age_days: 30,
+        }
+    }
+}
+
+impl Retention {
+    pub fn from_app(app_handle: &tauri::AppHandle) -> Self {
+        read_setting(app_handle, "snapshotRetention")
+            .and_then(|value| serde_json::from_value(value).ok())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotFile {
+    pub path: String,
+    pub hash: String,
+    pub permissions: FilePermissions,
+    /// Id of the snapshot whose pack holds the content.
+    pub pack: String,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Snapshot {
+    id: String,
+    created_at: String,
+    files: Vec<SnapshotFile>,
+}
+
+#[derive(Serialize, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotInfo {
+    pub id: String,
+    /// Local time in RFC 3339 format.
+    pub created_at: String,
+    pub file_count: usize,
+}
+
+impl From<&Snapshot> for SnapshotInfo {
+    fn from(snapshot: &Snapshot) -> Self {
+        Self {
+            id: snapshot.id.clone(),
+            created_at: snapshot.created_at.clone(),
+            file_count: snapshot.files.len(),
+        }
+    }
+}
+
+/// Snapshots of a project directory in `{app-local data dir}/snapshots/{project_id}`. Each
+/// snapshot is a manifest `{id}.json` listing the files with their hashes. Contents are stored
+/// once, in the brotli-compressed tar `packs/{id}.tar.br` of the first snapshot that had them,
+/// with entries named by hash; later snapshots refer to that pack.
+pub struct Snapshots {
+    dir: PathBuf,
+}
+
+impl Snapshots {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn from_app(app_handle: &tauri::AppHandle, project_id: &str) -> Result<Self, CommandError> {
+        project_data_path(app_handle, "snapshots", project_id).map(Self::new)
+    }
+
+    /// Snapshot the files of `root_dir`, hashed as in `compute_sync_plan`, unless they are the same
+    /// as in the latest snapshot. Then prune snapshots beyond `retention`, except for `keep`.
+    pub fn create(
+        &self,
+        root_dir: &Path,
+        files: &[LocalFileState],
+        retention: Retention,
+        keep: Option<&str>,
+    ) -> Result<Option<SnapshotInfo>, CommandError> {
+        let _lock = lock();
+        let snapshots = self.load_all()?;
+        let unchanged = snapshots.last().map_or(files.is_empty(), |latest| {
+            let latest: HashSet<_> = latest.files.iter().map(|f| (&f.path, &f.hash)).collect();
+            latest == files.iter().map(|f| (&f.path, &f.hash)).collect()
+        });
+        if unchanged {
+            return Ok(None);
+        }
+
+        let now = Local::now();
+        let id = format!(
+            "{}-{}",
+            now.format("%Y%m%dT%H%M%S%3f"),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        );
+        let mut packed: HashMap<&str, &str> = snapshots
+            .iter()
+            .flat_map(|s| &s.files)
+            .map(|f| (f.hash.as_str(), f.pack.as_str()))
+            .collect();
+        let mut entries = Vec::new();
+        let mut snapshot_files = Vec::new();
+        for file in files {
+            let abs_path = root_dir.join(&file.path);
+            let metadata = fs::metadata(&abs_path)
+                .map_err(|e| CommandError::io("Could not read file metadata", &abs_path, e))?;
+            let pack = *packed.entry(&file.hash).or_insert_with(|| {
+                entries.push((file.hash.clone(), abs_path));
+                id.as_str()
+            });
+            snapshot_files.push(SnapshotFile {
+                path: file.path.clone(),
+                hash: file.hash.clone(),
+                permissions: FilePermissions::from_metadata(&metadata),
+                pack: pack.to_string(),
+            });
+        }
+        if !entries.is_empty() {
+            let packs = self.dir.join("packs");
+            fs::create_dir_all(&packs)
+                .map_err(|e| CommandError::io("Could not create dir", &packs, e))?;
+            write_tar_entries(
+                &self.pack_path(&id),
+                &entries,
+                // packs are read back with brotli; they are written before every sync, where the
+                // slow top quality would hold it up
+                &TarOptions {
+                    codec: Some(Codec::Brotli),
+                    level: Some(Level::Preset(Preset::Balanced)),
+                    ..Default::default()
+                },
+                &ProgressReporter::silent(),
+                &CancelToken::default(),
+            )?;
+        }
+
+        let snapshot = Snapshot {
+            id,
+            created_at: now.to_rfc3339(),
+            files: snapshot_files,
+        };
+        self.save(&snapshot)?;
+        let info = SnapshotInfo::from(&snapshot);
+        let mut snapshots = snapshots;
+        snapshots.push(snapshot);
+        self.prune(snapshots, retention, now, keep)?;
+        Ok(Some(info))
+    }
+
+    /// Newest first.
+    pub fn list(&self) -> Result<Vec<SnapshotInfo>, CommandError> {
+        let _lock = lock();
+        Ok(self
+            .load_all()?
+            .iter()
+            .rev()
+            .map(SnapshotInfo::from)
+            .collect())
+    }
+
+    /// Changes of the working tree since the snapshot, as `compute_sync_plan` reports local ones.
+    pub fn diff(
+        &self,
+        id: &str,
+        files: &[LocalFileState],
+    ) -> Result<Vec<LocalFileChange>, CommandError> {
+        let snapshot = self.load(id)?;
+        let previous: Vec<_> = snapshot
+            .files
+            .into_iter()
+            .map(|f| PreviousFileInfo {
+                path: f.path,
+                version: 0,
+                hash: f.hash,
+                entry_type: FileEntryType::File,
+            })
+            .collect();
+        Ok(get_local_changes(&previous, files))
+    }
+
+    /// Write the files of a snapshot back into `root_dir`, all of them or only `paths`. Files
+    /// created since the snapshot are left alone. Returns the restored paths.
+    pub fn restore(
+        &self,
+        guard: &PathGuard,
+        root_dir: &Path,
+        id: &str,
+        paths: Option<&[String]>,
+    ) -> Result<Vec<String>, CommandError> {
+        let _lock = lock();
+        let snapshot = self.load(id)?;
+        let files: Vec<_> = match paths {
+            None => snapshot.files.iter().collect(),
+            Some(paths) => paths
+                .iter()
+                .map(|path| {
+                    snapshot
+                        .files
+                        .iter()
+                        .find(|f| &f.path == path)
+                        .ok_or_else(|| {
+                            CommandError::new(
+                                ErrorKind::NotFound,
+                                format!("'{path}' is not part of the snapshot"),
+                            )
+                        })
+                })
+                .collect::<Result<_, _>>()?,
+        };
+        let mut by_pack: HashMap<&str, Vec<&SnapshotFile>> = HashMap::new();
+        for file in &files {
+            by_pack.entry(&file.pack).or_default().push(file);
+        }
+        for (pack, files) in by_pack {
+            self.restore_from_pack(guard, root_dir, pack, &files)?;
+        }
+        Ok(files.iter().map(|f| f.path.clone()).collect())
+    }
+
+    pub fn remove_all(&self) -> Result<(), CommandError> {
+        let _lock = lock();
+        match fs::remove_dir_all(&self.dir) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                Err(CommandError::io("Could not remove snapshots", &self.dir, e))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Extract the content of `files` from a pack in a single pass. Contents are checked against
+    /// their hash before they replace the working file, a file that changed while it was packed
+    /// would otherwise be restored silently.
+    fn restore_from_pack(
+        &self,
+        guard: &PathGuard,
+        root_dir: &Path,
+        pack: &str,
+        files: &[&SnapshotFile],
+    ) -> Result<(), CommandError> {
+        let pack_path = self.pack_path(pack);
+        let archive_error =
+            |e: io::Error| CommandError::io("Could not read snapshot", &pack_path, e);
+        let compressed = File::open(&pack_path).map_err(archive_error)?;
+        let mut tar = tar::Archive::new(Decompressor::new(compressed, 4096));
+        let mut pending: HashMap<&str, Vec<&SnapshotFile>> = HashMap::new();
+        for file in files {
+            pending.entry(&file.hash).or_default().push(file);
+        }
+        for entry in tar.entries().map_err(archive_error)? {
+            let mut entry = entry.map_err(archive_error)?;
+            let name = entry.path().map_err(archive_error)?;
+            let Some(files) = pending.remove(name.to_string_lossy().as_ref()) else {
+                continue;
+            };
+            // the first file is extracted from the pack, the others are copies of it
+            let mut source: Option<PathBuf> = None;
+            for file in files {
+                let target = guard.check_relative(root_dir, &file.path)?;
+                fs_extra::create_missing_parents(root_dir, &target)?;
+                match &source {
+                    None => {
+                        let mut reader = VerifyingReader::new(&mut entry, &file.hash);
+                        let written =
+                            fs_extra::write_file_from(&target, &mut reader, file.permissions);
+                        if reader.damaged {
+                            return Err(CommandError::new(
+                                ErrorKind::Archive,
+                                "The snapshot copy of the file is damaged",
+                            )
+                            .with_path(&target));
+                        }
+                        written?;
+                        source = Some(target);
+                    }
+                    Some(source) => {
+                        let mut reader = File::open(source)
+                            .map_err(|e| CommandError::io("Could not open file", source, e))?;
+                        fs_extra::write_file_from(&target, &mut reader, file.permissions)?;
+                    }
+                }
+            }
+            if pending.is_empty() {
+                return Ok(());
+            }
+        }
+        match pending.into_values().flatten().next() {
+            None => Ok(()),
+            Some(missing) => Err(CommandError::new(
+                ErrorKind::Archive,
+                "The snapshot does not contain the file",
+            )
+            .with_path(&missing.path)),
+        }
+    }
+
+    /// Drop snapshots beyond the retention limits, keeping the newest one and `keep`, and the packs
+    /// no remaining snapshot refers to. Must be called with the lock held.
+    fn prune(
+        &self,
+        mut snapshots: Vec<Snapshot>,
+        retention: Retention,
+        now: DateTime<Local>,
+        keep: Option<&str>,
+    ) -> Result<(), CommandError> {
+        let oldest = now - Duration::days(retention.max_age_days.clamp(0, 36500));
+        let keep_from = snapshots.len().saturating_sub(retention.max_count.max(1));
+        let expired = |index: usize, snapshot: &Snapshot| {
+            index + 1 < snapshots.len()
+                && keep != Some(snapshot.id.as_str())
+                && (index < keep_from
+                    || DateTime::parse_from_rfc3339(&snapshot.created_at)
+                        .map_or(false, |created| created < oldest))
+        };
+        let expired: Vec<bool> = snapshots
+            .iter()
+            .enumerate()
+            .map(|(index, snapshot)| expired(index, snapshot))
+            .collect();
+        let mut expired = expired.into_iter();
+        snapshots.retain(|_| !expired.next().unwrap_or(false));
+
+        for manifest in read_dir_or_empty(&self.dir)? {
+            let name = manifest.file_stem().unwrap_or_default().to_string_lossy();
+            if manifest.extension().map_or(false, |e| e == "json")
+                && !snapshots.iter().any(|s| s.id == name)
+            {
+                remove_file(&manifest)?;
+            }
+        }
+        let packs: HashSet<&str> = snapshots
+            .iter()
+            .flat_map(|s| &s.files)
+            .map(|f| f.pack.as_str())
+            .collect();
+        for pack in read_dir_or_empty(&self.dir.join("packs"))? {
+            let name = pack.file_name().unwrap_or_default().to_string_lossy();
+            let id = name.strip_suffix(".tar.br").unwrap_or(&name);
+            if !packs.contains(id) {
+                remove_file(&pack)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn pack_path(&self, id: &str) -> PathBuf {
+        self.dir.join("packs").join(format!("{id}.tar.br"))
+    }
+
+    fn load(&self, id: &str) -> Result<Snapshot, CommandError> {
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(CommandError::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid snapshot id '{id}'"),
+            ));
+        }
+        let manifest = self.dir.join(format!("{id}.json"));
+        let bytes = fs::read(&manifest).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => {
+                CommandError::new(ErrorKind::NotFound, format!("No snapshot with id '{id}'"))
+            }
+            _ => CommandError::io("Could not read snapshot", &manifest, e),
+        })?;
+        serde_json::from_slice(&bytes).map_err(|e| {
+            CommandError::new(ErrorKind::Io, format!("Invalid snapshot: {e}")).with_path(&manifest)
+        })
+    }
+
+    /// Oldest first; ids start with the creation time.
+    fn load_all(&self) -> Result<Vec<Snapshot>, CommandError> {
+        let mut ids: Vec<String> = read_dir_or_empty(&self.dir)?
+            .into_iter()
+            .filter(|path| path.extension().map_or(false, |e| e == "json"))
+            .map(|path| {
+                path.file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        ids.sort();
+        ids.iter().map(|id| self.load(id)).collect()
+    }
+
+    fn save(&self, snapshot: &Snapshot) -> Result<(), CommandError> {
+        let json = serde_json::to_vec(snapshot).map_err(|e| {
+            CommandError::new(ErrorKind::Io, format!("Could not serialize snapshot: {e}"))
+        })?;
+        let manifest = self.dir.join(format!("{}.json", snapshot.id));
+        fs_extra::write_file_from(&manifest, &mut json.as_slice(), FilePermissions::Rw)
+    }
+}
+
+/// Passes the content of `reader` through, but fails at its end if the content doesn't match
+/// `hash`. `write_file_from` then discards its temp file instead of replacing the target.
+struct VerifyingReader<'a, R: io::Read> {
+    reader: R,
+    hasher: Sha256,
+    hash: &'a str,
+    damaged: bool,
+}
+
+impl<'a, R: io::Read> VerifyingReader<'a, R> {
+    fn new(reader: R, hash: &'a str) -> Self {
+        Self {
+            reader,
+            hasher: Sha256::new(),
+            hash,
+            damaged: false,
+        }
+    }
+}
+
+impl<R: io::Read> io::Read for VerifyingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        if n == 0
+            && !buf.is_empty()
+            && HEXLOWER.encode(self.hasher.clone().finalize().as_ref()) != self.hash
+        {
+            self.damaged = true;
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Content does not match its hash",
+            ));
+        }
+        Ok(n)
+    }
+}
+
+fn lock() -> MutexGuard<'static, ()> {
+    LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn read_dir_or_empty(dir: &Path) -> Result<Vec<PathBuf>, CommandError> {
+    match fs::read_dir(dir) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(CommandError::io("Could not read dir", dir, e)),
+        Ok(entries) => entries
+            .map(|entry| {
+                entry
+                    .map(|e| e.path())
+                    .map_err(|e| CommandError::io("Could not read dir entry", dir, e))
+            })
+            .filter(|path| path.as_ref().map_or(true, |path| path.is_file()))
+            .collect(),
+    }
+}
+
+fn remove_file(path: &Path) -> Result<(), CommandError> {
+    fs::remove_file(path).map_err(|e| CommandError::io("Could not remove file", path, e))
+}
+
+/// The files of `root_dir` that are not ignored with their hashes, taken from and written back to
+/// the hash cache.
+fn scan(
+    app_handle: &tauri::AppHandle,
+    project_id: &str,
+    root_dir: &Path,
+) -> Result<Vec<LocalFileState>, CommandError> {
+    let rules = load_rules(app_handle, project_id, root_dir)?;
+    let cache_file = hash_cache::cache_file(app_handle, project_id)?;
+    let mut cache = HashCache::load(&cache_file);
+    let files = scan_local_files(
+        root_dir,
+        &rules,
+        &mut cache,
+        &ProgressReporter::silent(),
+        &CancelToken::default(),
+    )?;
+    if let Err(e) = cache.save(&cache_file) {
+        eprintln!("{e}");
+    }
+    Ok(files)
+}
+
+/// Snapshot the project directory, e.g. before a sync. Returns `None` if nothing changed since
+/// the latest snapshot or the directory doesn't exist yet.
+#[tauri::command]
+pub async fn create_snapshot(
+    app_handle: tauri::AppHandle,
+    project_id: String,
+    root_dir: String,
+) -> Result<Option<SnapshotInfo>, CommandError> {
+    let root_dir = PathGuard::from_app(&app_handle).check(Path::new(&root_dir))?;
+    let snapshots = Snapshots::from_app(&app_handle, &project_id)?;
+    let retention = Retention::from_app(&app_handle);
+    run_blocking(move || {
+        if !root_dir.is_dir() {
+            return Ok(None);
+        }
+        let files = scan(&app_handle, &project_id, &root_dir)?;
+        snapshots.create(&root_dir, &files, retention, None)
+    })
+    .await
+}
+
+#[tauri::command]
+pub fn list_snapshots(
+    app_handle: tauri::AppHandle,
+    project_id: String,
+) -> Result<Vec<SnapshotInfo>, CommandError> {
+    Snapshots::from_app(&app_handle, &project_id)?.list()
+}
+
+/// Files added, removed or updated in the project directory since the snapshot.
+#[tauri::command]
+pub async fn diff_snapshot(
+    app_handle: tauri::AppHandle,
+    project_id: String,
+    root_dir: String,
+    id: String,
+) -> Result<Vec<LocalFileChange>, CommandError> {
+    let root_dir = PathGuard::from_app(&app_handle).check(Path::new(&root_dir))?;
+    let snapshots = Snapshots::from_app(&app_handle, &project_id)?;
+    run_blocking(move || {
+        let files = scan(&app_handle, &project_id, &root_dir)?;
+        snapshots.diff(&id, &files)
+    })
+    .await
+}
+
+/// Restore a whole snapshot, or only `paths` from it. The current state is snapshotted first, so
+/// a restore can be undone.
+#[tauri::command]
+pub async fn restore_snapshot(
+    app_handle: tauri::AppHandle,
+    project_id: String,
+    root_dir: String,
+    id: String,
+    paths: Option<Vec<String>>,
+) -> Result<Vec<String>, CommandError> {
+    let guard = PathGuard::from_app(&app_handle);
+    let root_dir = guard.check(Path::new(&root_dir))?;
+    let snapshots = Snapshots::from_app(&app_handle, &project_id)?;
+    let retention = Retention::from_app(&app_handle);
+    run_blocking(move || {
+        let files = scan(&app_handle, &project_id, &root_dir)?;
+        // pruning must not drop the snapshot to restore, which may be the oldest one
+        snapshots.create(&root_dir, &files, retention, Some(&id))?;
+        snapshots.restore(&guard, &root_dir, &id, paths.as_deref())
+    })
+    .await
+}
+
+#[tauri::command]
+pub fn remove_snapshots(
+    app_handle: tauri::AppHandle,
+    project_id: String,
+) -> Result<(), CommandError> {
+    Snapshots::from_app(&app_handle, &project_id)?.remove_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(root: &Path, path: &str, content: &str) -> LocalFileState {
+        fs::write(root.join(path), content).unwrap();
+        LocalFileState {
+            path: format!("./{path}"),
+            hash: HEXLOWER.encode(Sha256::digest(content.as_bytes()).as_ref()),
+        }
+    }
+
+    #[test]
+    fn snapshots_deduplicate_and_restore() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("project")).unwrap();
+        let root = dir.join("project").canonicalize().unwrap();
+        let guard = PathGuard::new([root.clone()]);
+        let snapshots = Snapshots::new(dir.join("snapshots"));
+        let retention = Retention {
+            max_count: 2,
+            max_age_days: 30,
+        };
+
+        let v1 = [state(&root, "a.txt", "a"), state(&root, "b.txt", "b")];
+        let first = snapshots
+            .create(&root, &v1, retention, None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(snapshots.create(&root, &v1, retention, None).unwrap(), None);
+        let v2 = [state(&root, "a.txt", "a"), state(&root, "b.txt", "B")];
+        let second = snapshots
+            .create(&root, &v2, retention, None)
+            .unwrap()
+            .unwrap();
+        // only the changed file is packed again, the first pack is still referred to
+        assert_eq!(
+            read_dir_or_empty(&dir.join("snapshots/packs"))
+                .unwrap()
+                .len(),
+            2
+        );
+
+        let changes = snapshots.diff(&first.id, &v2).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, "./b.txt");
+
+        fs::write(root.join("a.txt"), "broken").unwrap();
+        fs::remove_file(root.join("b.txt")).unwrap();
+        let restored = snapshots
+            .restore(&guard, &root, &first.id, Some(&["./b.txt".to_string()]))
+            .unwrap();
+        assert_eq!(restored, ["./b.txt"]);
+        assert_eq!(fs::read_to_string(root.join("b.txt")).unwrap(), "b");
+        snapshots.restore(&guard, &root, &second.id, None).unwrap();
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(root.join("b.txt")).unwrap(), "B");
+
+        let v3 = [state(&root, "a.txt", "x")];
+        snapshots
+            .create(&root, &v3, retention, None)
+            .unwrap()
+            .unwrap();
+        let ids: Vec<_> = snapshots
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|s| s.id)
+            .collect();
+        assert_eq!(ids.len(), 2);
+        assert_eq!(ids[1], second.id);
+        assert!(snapshots.restore(&guard, &root, &first.id, None).is_err());
+    }
+
+    #[test]
+    fn restoring_the_oldest_snapshot_keeps_it() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("project")).unwrap();
+        let root = dir.join("project").canonicalize().unwrap();
+        let guard = PathGuard::new([root.clone()]);
+        let snapshots = Snapshots::new(dir.join("snapshots"));
+        let retention = Retention {
+            max_count: 2,
+            max_age_days: 30,
+        };
+
+        let v1 = [state(&root, "a.txt", "a")];
+        let oldest = snapshots
+            .create(&root, &v1, retention, None)
+            .unwrap()
+            .unwrap();
+        let v2 = [state(&root, "a.txt", "b")];
+        snapshots
+            .create(&root, &v2, retention, None)
+            .unwrap()
+            .unwrap();
+
+        // as `restore_snapshot` does, the current state is snapshotted before restoring
+        let v3 = [state(&root, "a.txt", "c")];
+        snapshots
+            .create(&root, &v3, retention, Some(&oldest.id))
+            .unwrap()
+            .unwrap();
+        snapshots.restore(&guard, &root, &oldest.id, None).unwrap();
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "a");
+    }
+
+    #[test]
+    fn damaged_copies_leave_the_working_file_alone() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("project")).unwrap();
+        let root = dir.join("project").canonicalize().unwrap();
+        let guard = PathGuard::new([root.clone()]);
+        let snapshots = Snapshots::new(dir.join("snapshots"));
+
+        // the file changed after it was hashed, so the pack holds content of another hash
+        let file = state(&root, "a.txt", "a");
+        fs::write(root.join("a.txt"), "changed").unwrap();
+        let snapshot = snapshots
+            .create(&root, &[file], Retention::default(), None)
+            .unwrap()
+            .unwrap();
+
+        fs::write(root.join("a.txt"), "mine").unwrap();
+        let error = snapshots
+            .restore(&guard, &root, &snapshot.id, None)
+            .unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Archive);
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "mine");
+    }
+}
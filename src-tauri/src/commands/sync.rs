@@ -0,0 +1,429 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::commands::error::{CommandError, ErrorKind};
+use crate::commands::hash_cache::{self, HashCache};
+use crate::commands::ignore_rules::load_rules;
+use crate::commands::preserved_files::PreservedFiles;
+use crate::operations::{CancelToken, OperationRegistry};
+use crate::utils::ignore::IgnoreRules;
+use crate::utils::parallel::run_blocking;
+use crate::utils::progress::ProgressReporter;
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum FileEntryType {
+    File,
+    Dir,
+}
+
+/// A file as it was recorded after the last successful sync.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviousFileInfo {
+    pub path: String,
+    pub version: u64,
+    pub hash: String,
+    #[serde(rename = "type")]
+    pub entry_type: FileEntryType,
+}
+
+/// A file as it is currently listed by the server.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteFileInfo {
+    pub path: String,
+    pub version: u64,
+    #[serde(rename = "type")]
+    pub entry_type: FileEntryType,
+}
+
+/// A file as it is currently found in the project directory.
+#[derive(Debug)]
+pub struct LocalFileState {
+    pub path: String,
+    pub hash: String,
+}
+
+#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+#[serde(tag = "_tag", content = "value", rename_all = "camelCase")]
+pub enum LocalChange {
+    Updated,
+    Removed,
+    Added,
+}
+
+#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+#[serde(tag = "_tag", content = "value", rename_all = "camelCase")]
+pub enum RemoteChange {
+    Updated(u64),
+    Removed,
+    Added(u64),
+}
+
+#[derive(Serialize, PartialEq, Eq, Debug)]
+pub struct LocalFileChange {
+    pub path: String,
+    pub change: LocalChange,
+}
+
+#[derive(Serialize, PartialEq, Eq, Debug)]
+pub struct RemoteFileChange {
+    pub path: String,
+    pub change: RemoteChange,
+}
+
+#[derive(Serialize, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Conflict {
+    pub path: String,
+    pub change_local: LocalChange,
+    pub change_remote: RemoteChange,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SyncPlan {
+    pub local: Vec<LocalFileChange>,
+    pub remote: Vec<RemoteFileChange>,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Compare the project directory and the server's file list against the manifest stored after
+/// the last sync. Without a manifest the project has never been synced, so every remote file is
+/// new and there are no local changes to report. Ignored files are not synced from this side, so
+/// local changes to them are not reported either. Rehashing the changed files reports progress
+/// and can be cancelled like `get_file_hashes`.
+#[allow(clippy::too_many_arguments)]
+#[tauri::command]
+pub async fn compute_sync_plan(
+    app_handle: tauri::AppHandle,
+    operations: State<'_, OperationRegistry>,
+    project_id: String,
+    root_dir: String,
+    previous: Option<Vec<PreviousFileInfo>>,
+    remote: Vec<RemoteFileInfo>,
+    progress_channel: Option<String>,
+    operation_id: Option<String>,
+) -> Result<SyncPlan, CommandError> {
+    let progress = ProgressReporter::for_channel(&app_handle, progress_channel)?;
+    let operation = operations.start(operation_id)?;
+    let cancel = operation.token();
+    run_blocking(move || {
+        let root_dir = Path::new(&root_dir);
+        let (local, remote) = match previous {
+            None => (Vec::new(), get_remote_changes(&[], &remote)),
+            Some(previous) => {
+                let cache_file = hash_cache::cache_file(&app_handle, &project_id)?;
+                let mut cache = HashCache::load(&cache_file);
+                let conflict_copies =
+                    PreservedFiles::from_app(&app_handle, &project_id)?.conflict_copies()?;
+                let rules = load_rules(&app_handle, &project_id, root_dir)?;
+                let mut latest =
+                    scan_local_files(root_dir, &rules, &mut cache, &progress, &cancel)?;
+                // copies of replaced local edits are the student's to keep, not part of the project
+                latest.retain(|file| !conflict_copies.contains(&file.path));
+                cache.retain_paths(&latest.iter().map(|f| f.path.as_str()).collect());
+                if let Err(e) = cache.save(&cache_file) {
+                    eprintln!("{e}");
+                }
+                let previous: Vec<_> = previous
+                    .into_iter()
+                    .filter(|file| !rules.is_ignored(&file.path, false))
+                    .collect();
+                (
+                    get_local_changes(&previous, &latest),
+                    get_remote_changes(&previous, &remote),
+                )
+            }
+        };
+        progress.finish();
+        let conflicts = get_conflicts(&local, &remote);
+        Ok(SyncPlan {
+            local,
+            remote,
+            conflicts,
+        })
+    })
+    .await
+}
+
+/// Hidden entries (names starting with `.`) are skipped together with everything below them. This
+/// keeps the `.git` directory of the project history out of change detection.
+pub fn is_visible(name: &str) -> bool {
+    !name.starts_with('.')
+}
+
+/// Whether a project path lies in the git repository of the project history.
+pub fn in_git_dir(path: &str) -> bool {
+    Path::new(path)
+        .components()
+        .any(|c| c.as_os_str() == ".git")
+}
+
+/// Relative paths are reported in the same `./dir/file` form the frontend stores.
+pub fn to_project_path(relative: &Path) -> String {
+    let mut path = String::from(".");
+    for component in relative.components() {
+        path.push('/');
+        path.push_str(&component.as_os_str().to_string_lossy());
+    }
+    path
+}
+
+pub fn scan_local_files(
+    root_dir: &Path,
+    rules: &IgnoreRules,
+    cache: &mut HashCache,
+    progress: &ProgressReporter,
+    cancel: &CancelToken,
+) -> Result<Vec<LocalFileState>, CommandError> {
+    let files = list_local_files(root_dir, rules)?;
+    let hashes = cache.hash_all(&files, progress, cancel)?;
+    Ok(files
+        .into_iter()
+        .zip(hashes)
+        .map(|((path, _), hash)| LocalFileState { path, hash })
+        .collect())
+}
+
+/// The visible files below `root_dir` that are not ignored, as project path and absolute path.
+/// Ignored directories are not entered.
+pub fn list_local_files(
+    root_dir: &Path,
+    rules: &IgnoreRules,
+) -> Result<Vec<(String, PathBuf)>, CommandError> {
+    let mut files = Vec::new();
+    let mut dirs = vec![root_dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries =
+            fs::read_dir(&dir).map_err(|e| CommandError::io("Could not read dir", &dir, e))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| CommandError::io("Could not read dir entry", &dir, e))?;
+            if !is_visible(&entry.file_name().to_string_lossy()) {
+                continue;
+            }
+            let abs_path = entry.path();
+            let relative = abs_path.strip_prefix(root_dir).map_err(|_| {
+                CommandError::new(
+                    ErrorKind::InvalidInput,
+                    "Could not strip ancestor directory",
+                )
+                .with_path(&abs_path)
+            })?;
+            let path = to_project_path(relative);
+            let is_dir = abs_path.is_dir();
+            if rules.is_ignored_entry(&path, is_dir) {
+                continue;
+            }
+            if is_dir {
+                dirs.push(abs_path);
+            } else {
+                files.push((path, abs_path));
+            }
+        }
+    }
+    Ok(files)
+}
+
+pub fn get_local_changes(
+    previous: &[PreviousFileInfo],
+    latest: &[LocalFileState],
+) -> Vec<LocalFileChange> {
+    let previous: Vec<_> = previous
+        .iter()
+        .filter(|f| f.entry_type == FileEntryType::File)
+        .collect();
+    let previous_by_path: HashMap<_, _> = previous.iter().map(|f| (f.path.as_str(), f)).collect();
+    let latest_by_path: HashMap<_, _> = latest.iter().map(|f| (f.path.as_str(), f)).collect();
+
+    let removed = previous
+        .iter()
+        .filter(|f| !latest_by_path.contains_key(f.path.as_str()))
+        .map(|f| LocalFileChange {
+            path: f.path.clone(),
+            change: LocalChange::Removed,
+        });
+    let added = latest
+        .iter()
+        .filter(|f| !previous_by_path.contains_key(f.path.as_str()))
+        .map(|f| LocalFileChange {
+            path: f.path.clone(),
+            change: LocalChange::Added,
+        });
+    let updated = previous
+        .iter()
+        .filter(|f| {
+            latest_by_path
+                .get(f.path.as_str())
+                .map_or(false, |l| l.hash != f.hash)
+        })
+        .map(|f| LocalFileChange {
+            path: f.path.clone(),
+            change: LocalChange::Updated,
+        });
+    removed.chain(added).chain(updated).collect()
+}
+
+pub fn get_remote_changes(
+    previous: &[PreviousFileInfo],
+    latest: &[RemoteFileInfo],
+) -> Vec<RemoteFileChange> {
+    let previous: Vec<_> = previous
+        .iter()
+        .filter(|f| f.entry_type == FileEntryType::File)
+        .collect();
+    let latest: Vec<_> = latest
+        .iter()
+        .filter(|f| f.entry_type == FileEntryType::File)
+        .collect();
+    let previous_by_path: HashMap<_, _> = previous.iter().map(|f| (f.path.as_str(), f)).collect();
+    let latest_by_path: HashMap<_, _> = latest.iter().map(|f| (f.path.as_str(), f)).collect();
+
+    let removed = previous
+        .iter()
+        .filter(|f| !latest_by_path.contains_key(f.path.as_str()))
+        .map(|f| RemoteFileChange {
+            path: f.path.clone(),
+            change: RemoteChange::Removed,
+        });
+    let added = latest
+        .iter()
+        .filter(|f| !previous_by_path.contains_key(f.path.as_str()))
+        .map(|f| RemoteFileChange {
+            path: f.path.clone(),
+            change: RemoteChange::Added(f.version),
+        });
+    let updated = previous.iter().filter_map(|f| {
+        latest_by_path
+            .get(f.path.as_str())
+            .filter(|l| l.version != f.version)
+            .map(|l| RemoteFileChange {
+                path: f.path.clone(),
+                change: RemoteChange::Updated(l.version),
+            })
+    });
+    removed.chain(added).chain(updated).collect()
+}
+
+pub fn get_conflicts(local: &[LocalFileChange], remote: &[RemoteFileChange]) -> Vec<Conflict> {
+    let remote_by_path: HashMap<_, _> = remote.iter().map(|c| (c.path.as_str(), c)).collect();
+    local
+        .iter()
+        .filter_map(|l| {
+            remote_by_path.get(l.path.as_str()).map(|r| Conflict {
+                path: l.path.clone(),
+                change_local: l.change.clone(),
+                change_remote: r.change.clone(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn previous(path: &str, version: u64, hash: &str) -> PreviousFileInfo {
+        PreviousFileInfo {
+            path: path.to_string(),
+            version,
+            hash: hash.to_string(),
+            entry_type: FileEntryType::File,
+        }
+    }
+
+    fn remote(path: &str, version: u64) -> RemoteFileInfo {
+        RemoteFileInfo {
+            path: path.to_string(),
+            version,
+            entry_type: FileEntryType::File,
+        }
+    }
+
+    fn local(path: &str, hash: &str) -> LocalFileState {
+        LocalFileState {
+            path: path.to_string(),
+            hash: hash.to_string(),
+        }
+    }
+
+    #[test]
+    fn local_changes_are_detected_by_hash() {
+        let prev = [previous("./a", 1, "h1"), previous("./b", 1, "h2")];
+        let latest = [local("./a", "h1*"), local("./c", "h3")];
+        assert_eq!(
+            get_local_changes(&prev, &latest),
+            vec![
+                LocalFileChange {
+                    path: "./b".to_string(),
+                    change: LocalChange::Removed
+                },
+                LocalFileChange {
+                    path: "./c".to_string(),
+                    change: LocalChange::Added
+                },
+                LocalFileChange {
+                    path: "./a".to_string(),
+                    change: LocalChange::Updated
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn remote_changes_are_detected_by_version() {
+        let prev = [previous("./a", 1, "h1"), previous("./b", 1, "h2")];
+        let latest = [remote("./a", 2), remote("./b", 1), remote("./c", 1)];
+        assert_eq!(
+            get_remote_changes(&prev, &latest),
+            vec![
+                RemoteFileChange {
+                    path: "./c".to_string(),
+                    change: RemoteChange::Added(1)
+                },
+                RemoteFileChange {
+                    path: "./a".to_string(),
+                    change: RemoteChange::Updated(2)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn conflicts_are_changes_on_both_sides() {
+        let local = [LocalFileChange {
+            path: "./a".to_string(),
+            change: LocalChange::Updated,
+        }];
+        let remote = [
+            RemoteFileChange {
+                path: "./a".to_string(),
+                change: RemoteChange::Removed,
+            },
+            RemoteFileChange {
+                path: "./b".to_string(),
+                change: RemoteChange::Added(1),
+            },
+        ];
+        assert_eq!(
+            get_conflicts(&local, &remote),
+            vec![Conflict {
+                path: "./a".to_string(),
+                change_local: LocalChange::Updated,
+                change_remote: RemoteChange::Removed,
+            }]
+        );
+    }
+
+    #[test]
+    fn changes_serialize_as_tagged_unions() {
+        let json = serde_json::to_value(RemoteChange::Updated(3)).unwrap();
+        assert_eq!(json, serde_json::json!({ "_tag": "updated", "value": 3 }));
+        let json = serde_json::to_value(LocalChange::Removed).unwrap();
+        assert_eq!(json, serde_json::json!({ "_tag": "removed" }));
+    }
+}
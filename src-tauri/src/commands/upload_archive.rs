@@ -0,0 +1,779 @@
+use crate::commands::build_tar::{
+    entries_size, stream_tar, tar_entries, ArchiveInfo, TarEntries, TarOptions,
+};
+use crate::commands::create_jwt_token::sign_claims;
+use crate::commands::error::{CommandError, ErrorKind};
+use crate::commands::ignore_rules::load_rules;
+use crate::commands::upload_policy::{PolicyReport, UploadPolicy};
+use crate::operations::{CancelToken, OperationRegistry};
+use crate::utils::compression::Level;
+use crate::utils::parallel::run_blocking;
+use crate::utils::path_guard::PathGuard;
+use crate::utils::progress::ProgressReporter;
+use crate::utils::settings::read_setting;
+use crossbeam_channel::{Receiver, RecvTimeoutError, SendTimeoutError, Sender};
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::State;
+use url::Url;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest the server may take to accept more of the body or to answer.
+const IO_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How often an upload waiting for the server checks whether it has been cancelled.
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The server answers with a short JSON document, anything larger is not read.
+const MAX_RESPONSE_SIZE: u64 = 1024 * 1024;
+
+const MAX_REDIRECTS: usize = 5;
+
+/// Size of the chunks the body is sent in, as the compressors write in small pieces.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Chunks the archive may be ahead of the connection.
+const PIPE_CHUNKS: usize = 16;
+
+/// Seconds a token is accepted by the server, like the tokens of the frontend.
+const TOKEN_LIFETIME: u64 = 10;
+
+/// Statuses of answers that accept a request.
+const SUCCESS: Range<u16> = 200..300;
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadResponse {
+    /// `None` if there were no files to upload, or the server answered before the whole archive
+    /// was sent.
+    pub archive: Option<ArchiveInfo>,
+    pub status: u16,
+    /// The JSON the server answered with, or its text if it is not JSON.
+    pub body: Value,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "_tag", content = "value", rename_all = "camelCase")]
+pub enum UploadOutcome {
+    Uploaded(UploadResponse),
+    /// Nothing was sent, as the files violate the upload policy.
+    Rejected(PolicyReport),
+}
+
+/// Archive the files like `build_tar` and upload the archive to the API at `api_url`, without a
+/// temporary file. The archive is streamed to `project/{projectId}/archive` with chunked transfer
+/// encoding while it is built, so its hash is only known once it has been sent. The server keeps it
+/// under the `archiveId` it answers with. The POST to `project/{projectId}/files` that
+/// `apiPostSigned` would send then applies it: its token carries `removeFiles`, the `archiveId` and
+/// the `tarHash` the server checks the archive against. Cancelling aborts the requests, also while
+/// they wait for the server.
+#[allow(clippy::too_many_arguments)]
+#[tauri::command]
+pub async fn upload_archive(
+    app_handle: tauri::AppHandle,
+    operations: State<'_, OperationRegistry>,
+    api_url: String,
+    project_id: String,
+    root_dir: String,
+    files: Vec<String>,
+    remove_files: Vec<String>,
+    policy: Option<UploadPolicy>,
+    options: Option<TarOptions>,
+    progress_channel: Option<String>,
+    operation_id: Option<String>,
+) -> Result<UploadOutcome, CommandError> {
+    let guard = PathGuard::from_app(&app_handle);
+    let root_dir = guard.check(Path::new(&root_dir))?;
+    for x in &files {
+        guard.check_relative(&root_dir, x)?;
+    }
+    let endpoints = Endpoints::new(&api_url, &project_id)?;
+    let client_id = read_setting(&app_handle, "clientId")
+        .and_then(|id| id.as_str().map(str::to_string))
+        .ok_or_else(|| CommandError::new(ErrorKind::NotFound, "No client id was found"))?;
+    let rules = load_rules(&app_handle, &project_id, &root_dir)?;
+    let progress = ProgressReporter::for_channel(&app_handle, progress_channel)?;
+    let operation = operations.start(operation_id)?;
+    let cancel = operation.token();
+    let policy = policy.unwrap_or_default();
+    let options = options.unwrap_or_default();
+    run_blocking(move || {
+        let TarEntries { entries, ignored } = tar_entries(&root_dir, &files, &rules);
+        let report = policy.check(&entries)?;
+        if !report.violations.is_empty() {
+            return Ok(UploadOutcome::Rejected(report));
+        }
+        let sign = |mut claims: Value| {
+            let exp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |now| now.as_secs())
+                + TOKEN_LIFETIME;
+            claims["iss"] = client_id.clone().into();
+            claims["exp"] = exp.into();
+            sign_claims(&app_handle, &claims)
+        };
+        post_archive(
+            &agent(IO_TIMEOUT),
+            &endpoints,
+            &entries,
+            &remove_files,
+            &options,
+            sign,
+            &progress,
+            &cancel,
+        )
+        .map(|response| {
+            UploadOutcome::Uploaded(UploadResponse {
+                archive: response
+                    .archive
+                    .map(|archive| ArchiveInfo { ignored, ..archive }),
+                ..response
+            })
+        })
+    })
+    .await
+}
+
+/// Where the archive of a project is sent to and where it is applied.
+pub struct Endpoints {
+    pub archive: Url,
+    pub files: Url,
+}
+
+impl Endpoints {
+    pub fn new(api_url: &str, project_id: &str) -> Result<Self, CommandError> {
+        Ok(Self {
+            archive: project_url(api_url, project_id, "archive")?,
+            files: project_url(api_url, project_id, "files")?,
+        })
+    }
+}
+
+/// `project/{project_id}/{endpoint}` of the API, resolved like the paths of `apiPost`.
+fn project_url(api_url: &str, project_id: &str, endpoint: &str) -> Result<Url, CommandError> {
+    let invalid = |reason: &dyn fmt::Display| {
+        CommandError::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid API URL '{api_url}': {reason}"),
+        )
+    };
+    let mut url = Url::parse(api_url)
+        .and_then(|base| base.join("project/"))
+        .map_err(|e| invalid(&e))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(invalid(&"not an HTTP URL"));
+    }
+    // pushed segments are percent-encoded, so the id stays a single segment
+    url.path_segments_mut()
+        .map_err(|()| invalid(&"not a base URL"))?
+        .pop_if_empty()
+        .push(project_id)
+        .push(endpoint);
+    Ok(url)
+}
+
+/// The client for uploads. Proxies are taken from the environment like `HTTPS_PROXY`; redirects
+/// are followed by [`follow_redirects`], which sends the body again.
+fn agent(io_timeout: Duration) -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .timeout_connect(CONNECT_TIMEOUT)
+        .timeout_read(io_timeout)
+        .timeout_write(io_timeout)
+        .try_proxy_from_env(true)
+        .redirects(0)
+        .build()
+}
+
+/// Stream `entries` as archive to `endpoints.archive`, then apply it and remove `remove_files`
+/// with a request to `endpoints.files`, authorized with the tokens `sign` creates for the given
+/// claims. Without entries, only the second request is sent, without an archive. The answer of
+/// the first request is returned if it is not a success.
+#[allow(clippy::too_many_arguments)]
+pub fn post_archive(
+    agent: &ureq::Agent,
+    endpoints: &Endpoints,
+    entries: &[(String, PathBuf)],
+    remove_files: &[String],
+    options: &TarOptions,
+    sign: impl Fn(Value) -> Result<String, CommandError>,
+    progress: &ProgressReporter,
+    cancel: &CancelToken,
+) -> Result<UploadResponse, CommandError> {
+    let mut claims = serde_json::json!({ "removeFiles": remove_files });
+    let archive = if entries.is_empty() {
+        None
+    } else {
+        let (archive, answer) = follow_redirects(endpoints.archive.clone(), |url| {
+            let request = authorized(agent, url, &sign(serde_json::json!({}))?);
+            send_archive(request, url, entries, options, progress, cancel)
+        })?;
+        let archive_id = answer.json()["archiveId"].as_str().map(str::to_string);
+        match (archive, archive_id) {
+            (Some(archive), Some(archive_id)) if SUCCESS.contains(&answer.status) => {
+                claims["archiveId"] = archive_id.into();
+                claims["tarHash"] = archive.tar_hash.clone().into();
+                Some(archive)
+            }
+            (archive, _) if !SUCCESS.contains(&answer.status) => {
+                return Ok(UploadResponse {
+                    archive,
+                    status: answer.status,
+                    body: answer.json(),
+                })
+            }
+            _ => {
+                return Err(network_error(
+                    endpoints.archive.as_str(),
+                    "the server did not accept the archive",
+                ))
+            }
+        }
+    };
+
+    let ((), answer) = follow_redirects(endpoints.files.clone(), |url| {
+        let request = authorized(agent, url, &sign(claims.clone())?).set("Content-Length", "0");
+        let answer = wait_for_answer(&spawn_request(request, url, io::empty()), cancel)?;
+        Ok(((), answer))
+    })?;
+    Ok(UploadResponse {
+        archive,
+        status: answer.status,
+        body: answer.json(),
+    })
+}
+
+fn authorized(agent: &ureq::Agent, url: &Url, token: &str) -> ureq::Request {
+    agent
+        .request_url("POST", url)
+        .set("Authorization", &format!("Bearer {token}"))
+}
+
+/// Send the request `send` makes for `url`, and again for the target of each `307` or `308`
+/// redirect, see [`redirect_target`]. A redirected archive is built again.
+fn follow_redirects<T>(
+    mut url: Url,
+    mut send: impl FnMut(&Url) -> Result<(T, Answer), CommandError>,
+) -> Result<(T, Answer), CommandError> {
+    for _ in 0..=MAX_REDIRECTS {
+        let (value, answer) = send(&url)?;
+        match redirect_target(&url, &answer) {
+            Some(target) => url = target,
+            None => return Ok((value, answer)),
+        }
+    }
+    Err(CommandError::new(ErrorKind::Network, "Too many redirects").with_path(url.as_str()))
+}
+
+/// Send the archive as chunked body of `request` while it is built, and return it with the
+/// answer. The archive is `None` if the server answered before all of it was sent.
+fn send_archive(
+    request: ureq::Request,
+    url: &Url,
+    entries: &[(String, PathBuf)],
+    options: &TarOptions,
+    progress: &ProgressReporter,
+    cancel: &CancelToken,
+) -> Result<(Option<ArchiveInfo>, Answer), CommandError> {
+    // the encoding is sent ahead of the archive, so the codec is chosen here
+    let (codec, level) = options.compression(entries_size(entries));
+    let options = TarOptions {
+        codec: Some(codec),
+        level: Some(Level::Value(level)),
+        ..options.clone()
+    };
+    let request = request.set("Content-Type", "application/x-tar");
+    let request = match codec.encoding() {
+        Some(encoding) => request.set("Content-Encoding", encoding),
+        None => request,
+    };
+    let (sender, receiver) = crossbeam_channel::bounded(PIPE_CHUNKS);
+    let answer = spawn_request(request, url, PipeReader::new(receiver));
+
+    let mut body = PipeWriter::new(sender, cancel.clone());
+    let result = stream_tar(
+        &mut body,
+        Path::new(url.as_str()),
+        entries,
+        &options,
+        progress,
+        cancel,
+    )
+    .map(|(_, archive)| archive);
+    match cancel.map_result(result) {
+        Ok(archive) => {
+            // a closed pipe means the request has ended, its answer tells how
+            let _ = body.finish();
+            Ok((Some(archive), wait_for_answer(&answer, cancel)?))
+        }
+        // the server answered or the connection failed before the body was sent
+        Err(e) if e.kind != ErrorKind::Cancelled && body.closed => {
+            Ok((None, wait_for_answer(&answer, cancel)?))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// What the server answered, read on the request thread.
+#[derive(Debug)]
+struct Answer {
+    status: u16,
+    location: Option<String>,
+    body: Vec<u8>,
+}
+
+impl Answer {
+    fn json(&self) -> Value {
+        serde_json::from_slice(&self.body)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&self.body).into_owned()))
+    }
+}
+
+/// Send `request` with `body` on a thread of its own, so waiting for the server doesn't hold up
+/// cancelling. An abandoned request ends with its timeouts.
+fn spawn_request(
+    request: ureq::Request,
+    url: &Url,
+    body: impl Read + Send + 'static,
+) -> Receiver<Result<Answer, CommandError>> {
+    let (sender, receiver) = crossbeam_channel::bounded(1);
+    let url = url.to_string();
+    thread::spawn(move || {
+        let answer = match request.send(body) {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => read_answer(response, &url),
+            Err(ureq::Error::Transport(e)) => Err(network_error(&url, e)),
+        };
+        let _ = sender.send(answer);
+    });
+    receiver
+}
+
+fn read_answer(response: ureq::Response, url: &str) -> Result<Answer, CommandError> {
+    let status = response.status();
+    let location = response.header("Location").map(str::to_string);
+    let mut body = Vec::new();
+    response
+        .into_reader()
+        .take(MAX_RESPONSE_SIZE + 1)
+        .read_to_end(&mut body)
+        .map_err(|e| network_error(url, e))?;
+    if body.len() as u64 > MAX_RESPONSE_SIZE {
+        return Err(network_error(
+            url,
+            format!("the answer is larger than {MAX_RESPONSE_SIZE} bytes"),
+        ));
+    }
+    Ok(Answer {
+        status,
+        location,
+        body,
+    })
+}
+
+/// Wait for the answer to a request, but fail as soon as the operation is cancelled.
+fn wait_for_answer(
+    answer: &Receiver<Result<Answer, CommandError>>,
+    cancel: &CancelToken,
+) -> Result<Answer, CommandError> {
+    loop {
+        cancel.check()?;
+        match answer.recv_timeout(CANCEL_POLL_INTERVAL) {
+            Ok(answer) => return answer,
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(CommandError::new(
+                    ErrorKind::Network,
+                    "The upload stopped unexpectedly",
+                ))
+            }
+        }
+    }
+}
+
+/// Where to send the archive again after `answer`. Only `307` and `308` keep the method, and
+/// only redirects within the host, which are not downgraded from `https`, get the token.
+fn redirect_target(url: &Url, answer: &Answer) -> Option<Url> {
+    if !matches!(answer.status, 307 | 308) {
+        return None;
+    }
+    let target = url.join(answer.location.as_deref()?).ok()?;
+    let same_host = target.host_str() == url.host_str();
+    let secure = target.scheme() == url.scheme() || target.scheme() == "https";
+    (same_host && secure).then_some(target)
+}
+
+fn network_error(url: &str, reason: impl fmt::Display) -> CommandError {
+    CommandError::new(
+        ErrorKind::Network,
+        format!("Could not upload archive: {reason}"),
+    )
+    .with_path(url)
+}
+
+/// Hands what is written to it in chunks to a [`PipeReader`] on the request thread.
+struct PipeWriter {
+    sender: Sender<Vec<u8>>,
+    chunk: Vec<u8>,
+    cancel: CancelToken,
+    /// Set once the reader is gone, as the request has ended.
+    closed: bool,
+}
+
+impl PipeWriter {
+    fn new(sender: Sender<Vec<u8>>, cancel: CancelToken) -> Self {
+        Self {
+            sender,
+            chunk: Vec::with_capacity(CHUNK_SIZE),
+            cancel,
+            closed: false,
+        }
+    }
+
+    /// Send the last chunk, which [`Write::flush`] holds back, and the empty chunk that ends the
+    /// body.
+    fn finish(mut self) -> io::Result<()> {
+        if !self.chunk.is_empty() {
+            self.send_chunk()?;
+        }
+        self.send_chunk()
+    }
+
+    fn send_chunk(&mut self) -> io::Result<()> {
+        let mut chunk = mem::replace(&mut self.chunk, Vec::with_capacity(CHUNK_SIZE));
+        // a full pipe waits for the connection, which must not hold up cancelling
+        loop {
+            match self.sender.send_timeout(chunk, CANCEL_POLL_INTERVAL) {
+                Ok(()) => return Ok(()),
+                Err(SendTimeoutError::Timeout(rejected)) => {
+                    if self.cancel.is_cancelled() {
+                        return Err(io::Error::new(io::ErrorKind::Other, "cancelled"));
+                    }
+                    chunk = rejected;
+                }
+                Err(SendTimeoutError::Disconnected(_)) => {
+                    self.closed = true;
+                    return Err(io::ErrorKind::BrokenPipe.into());
+                }
+            }
+        }
+    }
+}
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(CHUNK_SIZE - self.chunk.len());
+        self.chunk.extend_from_slice(&buf[..len]);
+        if self.chunk.len() == CHUNK_SIZE {
+            self.send_chunk()?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reads the chunks of a [`PipeWriter`] up to the empty chunk that ends them. A writer gone
+/// without it fails the read, which aborts the request, so the server never gets a complete body
+/// of an archive that failed.
+struct PipeReader {
+    receiver: Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    pos: usize,
+    ended: bool,
+}
+
+impl PipeReader {
+    fn new(receiver: Receiver<Vec<u8>>) -> Self {
+        Self {
+            receiver,
+            chunk: Vec::new(),
+            pos: 0,
+            ended: false,
+        }
+    }
+}
+
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while !self.ended && self.pos == self.chunk.len() {
+            match self.receiver.recv() {
+                Ok(chunk) => {
+                    self.ended = chunk.is_empty();
+                    self.chunk = chunk;
+                    self.pos = 0;
+                }
+                Err(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "the archive ended early",
+                    ))
+                }
+            }
+        }
+        let len = buf.len().min(self.chunk.len() - self.pos);
+        buf[..len].copy_from_slice(&self.chunk[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::compression::Codec;
+    use data_encoding::HEXLOWER;
+    use sha2::{Digest, Sha256};
+    use std::fs;
+    use std::io::{BufRead, BufReader};
+    use std::net::{TcpListener, TcpStream};
+    use std::time::Instant;
+
+    type Request = (String, Vec<(String, String)>, Vec<u8>);
+
+    /// Read a request, with header names in lowercase and a chunked body decoded.
+    fn read_request(socket: &TcpStream) -> Request {
+        let mut reader = BufReader::new(socket);
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let line = line.trim_end().to_string();
+            if line.is_empty() {
+                break;
+            }
+            lines.push(line);
+        }
+        let headers: Vec<_> = lines[1..]
+            .iter()
+            .map(|line| {
+                let (name, value) = line.split_once(':').unwrap();
+                (name.to_ascii_lowercase(), value.trim().to_string())
+            })
+            .collect();
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, value)| value.as_str())
+        };
+        let mut body = Vec::new();
+        if header("transfer-encoding") == Some("chunked") {
+            loop {
+                let mut size = String::new();
+                reader.read_line(&mut size).unwrap();
+                let size = usize::from_str_radix(size.trim_end(), 16).unwrap();
+                let mut chunk = vec![0; size + 2];
+                reader.read_exact(&mut chunk).unwrap();
+                if size == 0 {
+                    break;
+                }
+                body.extend_from_slice(&chunk[..size]);
+            }
+        } else {
+            let length = header("content-length").map_or(0, |value| value.parse().unwrap());
+            body.resize(length, 0);
+            reader.read_exact(&mut body).unwrap();
+        }
+        (lines[0].clone(), headers, body)
+    }
+
+    /// Accepts a single request and never answers it, until the client gives up.
+    fn stalled_server() -> Endpoints {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoints = Endpoints::new(&format!("http://{}", listener.local_addr().unwrap()), "p1");
+        thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            read_request(&socket);
+            let _ = socket.read_to_end(&mut Vec::new());
+        });
+        endpoints.unwrap()
+    }
+
+    fn main_py() -> (tempfile::TempDir, Vec<(String, PathBuf)>) {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::write(tmp.path().join("main.py"), "print('hi')\n".repeat(10_000)).unwrap();
+        let entries = vec![("main.py".to_string(), tmp.path().join("main.py"))];
+        (tmp, entries)
+    }
+
+    fn sign(claims: Value) -> Result<String, CommandError> {
+        Ok(claims.to_string())
+    }
+
+    #[test]
+    fn uploads_archive_then_applies_it_by_hash() {
+        let (_tmp, entries) = main_py();
+
+        // stands in for the server, which moves the archive endpoint once
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let api_url = format!("http://{}/api/", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let answer = |status: &str, body: &str| {
+                format!(
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+            };
+            let answers = [
+                "HTTP/1.1 307 Temporary Redirect\r\nLocation: /api/v2/project/p1/archive\r\n\
+                 Content-Length: 0\r\nConnection: close\r\n\r\n"
+                    .to_string(),
+                answer("201 Created", r#"{"archiveId":"a1"}"#),
+                answer("200 OK", r#"{"_id":"p1","files":[]}"#),
+            ];
+            answers
+                .iter()
+                .map(|answer| {
+                    let (mut socket, _) = listener.accept().unwrap();
+                    let request = read_request(&socket);
+                    socket.write_all(answer.as_bytes()).unwrap();
+                    request
+                })
+                .collect::<Vec<_>>()
+        });
+
+        let options = TarOptions {
+            deterministic: true,
+            codec: Some(Codec::Gzip),
+            level: None,
+        };
+        let response = post_archive(
+            &agent(IO_TIMEOUT),
+            &Endpoints::new(&api_url, "p1").unwrap(),
+            &entries,
+            &["./old.py".to_string()],
+            &options,
+            sign,
+            &ProgressReporter::silent(),
+            &CancelToken::default(),
+        )
+        .unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(
+            response.body,
+            serde_json::json!({ "_id": "p1", "files": [] })
+        );
+        let archive = response.archive.unwrap();
+
+        let requests = server.join().unwrap();
+        let request_lines: Vec<_> = requests.iter().map(|(line, _, _)| line.as_str()).collect();
+        assert_eq!(
+            request_lines,
+            [
+                "POST /api/project/p1/archive HTTP/1.1",
+                "POST /api/v2/project/p1/archive HTTP/1.1",
+                "POST /api/project/p1/files HTTP/1.1",
+            ]
+        );
+        let header = |(_, headers, _): &Request, name: &str| {
+            headers
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, value)| value.clone())
+        };
+        let claims = |request: &Request| -> Value {
+            let token = header(request, "authorization").unwrap();
+            serde_json::from_str(token.strip_prefix("Bearer ").unwrap()).unwrap()
+        };
+        for request in &requests[..2] {
+            assert_eq!(claims(request), serde_json::json!({}));
+            assert_eq!(
+                header(request, "transfer-encoding").as_deref(),
+                Some("chunked")
+            );
+            assert_eq!(header(request, "content-encoding").as_deref(), Some("gzip"));
+
+            let mut tar = Vec::new();
+            flate2::read::GzDecoder::new(&request.2[..])
+                .read_to_end(&mut tar)
+                .unwrap();
+            assert_eq!(archive.tar_hash, HEXLOWER.encode(&Sha256::digest(&tar)));
+        }
+        assert_eq!(
+            claims(&requests[2]),
+            serde_json::json!({
+                "removeFiles": ["./old.py"],
+                "archiveId": "a1",
+                "tarHash": archive.tar_hash,
+            })
+        );
+        assert!(requests[2].2.is_empty());
+    }
+
+    #[test]
+    fn stalled_servers_time_out() {
+        let (_tmp, entries) = main_py();
+        let error = post_archive(
+            &agent(Duration::from_millis(200)),
+            &stalled_server(),
+            &entries,
+            &[],
+            &TarOptions::default(),
+            sign,
+            &ProgressReporter::silent(),
+            &CancelToken::default(),
+        )
+        .unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Network);
+    }
+
+    #[test]
+    fn cancelling_stops_waiting_for_the_answer() {
+        let cancel = CancelToken::default();
+        let canceller = {
+            let cancel = cancel.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(200));
+                cancel.cancel();
+            })
+        };
+        let start = Instant::now();
+        let error = post_archive(
+            &agent(IO_TIMEOUT),
+            &stalled_server(),
+            &[],
+            &[],
+            &TarOptions::default(),
+            sign,
+            &ProgressReporter::silent(),
+            &cancel,
+        )
+        .unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Cancelled);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        canceller.join().unwrap();
+    }
+
+    #[test]
+    fn project_urls_are_built_from_the_api_url() {
+        let url = |api_url: &str, project_id: &str| {
+            project_url(api_url, project_id, "files").map(|url| url.to_string())
+        };
+        assert_eq!(
+            url("http://localhost:3100", "p1").unwrap(),
+            "http://localhost:3100/project/p1/files"
+        );
+        assert_eq!(
+            url("https://example.com/api/", "p1").unwrap(),
+            "https://example.com/api/project/p1/files"
+        );
+        assert_eq!(
+            url("http://[::1]:3100", "p1").unwrap(),
+            "http://[::1]:3100/project/p1/files"
+        );
+        assert_eq!(
+            url("http://localhost:3100", "../admin").unwrap(),
+            "http://localhost:3100/project/..%2Fadmin/files"
+        );
+        assert!(url("file:///etc", "p1").is_err());
+    }
+}
@@ -0,0 +1,245 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::commands::error::CommandError;
+
+/// Longest signature in [`FileKind::detect`].
+const MAGIC_LEN: usize = 8;
+
+/// Where the DOS header of a Windows executable stores the offset of its `PE\0\0` header.
+const PE_OFFSET_POS: u64 = 0x3c;
+
+/// Limits of a single upload, checked before anything is compressed. Limits left out don't apply.
+#[derive(Deserialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadPolicy {
+    pub max_file_size: Option<u64>,
+    /// Of all files together, before compression.
+    pub max_total_size: Option<u64>,
+    pub max_file_count: Option<usize>,
+    #[serde(default)]
+    pub deny: Vec<FileKind>,
+}
+
+/// Binary content recognized by its leading bytes.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum FileKind {
+    /// Linux executables and shared libraries.
+    Elf,
+    /// Windows executables and DLLs.
+    Pe,
+    /// macOS executables and libraries.
+    MachO,
+    /// Compiled Java classes. Their signature is shared by universal Mach-O binaries.
+    JavaClass,
+    Wasm,
+    /// Also `.jar`, `.docx` and other zip-based formats.
+    Zip,
+    Gzip,
+    SevenZip,
+    Rar,
+    Sqlite,
+    Pdf,
+    Png,
+    Jpeg,
+    Gif,
+}
+
+impl FileKind {
+    const SIGNATURES: [(&'static [u8], FileKind); 17] = [
+        (b"\x7fELF", FileKind::Elf),
+        (b"MZ", FileKind::Pe),
+        (b"\xfe\xed\xfa\xce", FileKind::MachO),
+        (b"\xfe\xed\xfa\xcf", FileKind::MachO),
+        (b"\xce\xfa\xed\xfe", FileKind::MachO),
+        (b"\xcf\xfa\xed\xfe", FileKind::MachO),
+        (b"\xca\xfe\xba\xbe", FileKind::JavaClass),
+        (b"\0asm", FileKind::Wasm),
+        (b"PK\x03\x04", FileKind::Zip),
+        (b"\x1f\x8b", FileKind::Gzip),
+        (b"7z\xbc\xaf\x27\x1c", FileKind::SevenZip),
+        (b"Rar!\x1a\x07", FileKind::Rar),
+        (b"SQLite f", FileKind::Sqlite),
+        (b"%PDF-", FileKind::Pdf),
+        (b"\x89PNG\r\n\x1a\n", FileKind::Png),
+        (b"\xff\xd8\xff", FileKind::Jpeg),
+        (b"GIF8", FileKind::Gif),
+    ];
+
+    /// Recognize the content of `file` by its first bytes. As text may well start with `MZ`,
+    /// Windows executables are only recognized by their PE header.
+    pub fn detect<R: Read + Seek>(file: &mut R) -> io::Result<Option<Self>> {
+        let mut head = Vec::with_capacity(MAGIC_LEN);
+        file.take(MAGIC_LEN as u64).read_to_end(&mut head)?;
+        let kind = Self::SIGNATURES
+            .iter()
+            .find(|(magic, _)| head.starts_with(magic))
+            .map(|(_, kind)| *kind);
+        match kind {
+            Some(FileKind::Pe) if !has_pe_header(file)? => Ok(None),
+            kind => Ok(kind),
+        }
+    }
+}
+
+/// Whether the DOS header of `file` points to a `PE\0\0` header.
+fn has_pe_header<R: Read + Seek>(file: &mut R) -> io::Result<bool> {
+    let mut offset = [0; 4];
+    if !read_at(file, PE_OFFSET_POS, &mut offset)? {
+        return Ok(false);
+    }
+    let mut signature = [0; 4];
+    let offset = u32::from_le_bytes(offset).into();
+    Ok(read_at(file, offset, &mut signature)? && &signature == b"PE\0\0")
+}
+
+/// Fill `buf` from `pos` on, `false` if the file ends before.
+fn read_at<R: Read + Seek>(file: &mut R, pos: u64, buf: &mut [u8]) -> io::Result<bool> {
+    file.seek(SeekFrom::Start(pos))?;
+    match file.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+#[derive(Serialize, PartialEq, Eq, Debug)]
+#[serde(tag = "_tag", content = "value", rename_all = "camelCase")]
+pub enum Violation {
+    FileTooLarge { path: String, size: u64, limit: u64 },
+    TotalSizeExceeded { size: u64, limit: u64 },
+    TooManyFiles { count: usize, limit: usize },
+    DeniedContent { path: String, kind: FileKind },
+}
+
+/// The files an upload would contain and the limits they exceed, if any.
+#[derive(Serialize, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyReport {
+    pub file_count: usize,
+    pub total_size: u64,
+    pub violations: Vec<Violation>,
+}
+
+impl UploadPolicy {
+    /// Check the archive entries, given as name and file to read. Only the first bytes of a file
+    /// are read (and the PE header of what looks like a Windows executable), and only if some
+    /// content is denied.
+    pub fn check(&self, entries: &[(String, PathBuf)]) -> Result<PolicyReport, CommandError> {
+        let mut violations = Vec::new();
+        let mut total_size = 0;
+        for (path, abs_path) in entries {
+            let size = fs::metadata(abs_path)
+                .map_err(|e| CommandError::io("Could not read file metadata", abs_path, e))?
+                .len();
+            total_size += size;
+            if let Some(limit) = self.max_file_size.filter(|limit| size > *limit) {
+                violations.push(Violation::FileTooLarge {
+                    path: path.clone(),
+                    size,
+                    limit,
+                });
+            }
+            if self.deny.is_empty() {
+                continue;
+            }
+            let kind = File::open(abs_path)
+                .and_then(|mut file| FileKind::detect(&mut file))
+                .map_err(|e| CommandError::io("Could not read file", abs_path, e))?;
+            if let Some(kind) = kind.filter(|kind| self.deny.contains(kind)) {
+                violations.push(Violation::DeniedContent {
+                    path: path.clone(),
+                    kind,
+                });
+            }
+        }
+        if let Some(limit) = self.max_total_size.filter(|limit| total_size > *limit) {
+            violations.push(Violation::TotalSizeExceeded {
+                size: total_size,
+                limit,
+            });
+        }
+        if let Some(limit) = self.max_file_count.filter(|limit| entries.len() > *limit) {
+            violations.push(Violation::TooManyFiles {
+                count: entries.len(),
+                limit,
+            });
+        }
+        Ok(PolicyReport {
+            file_count: entries.len(),
+            total_size,
+            violations,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_every_violation() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::write(dir.join("main.c"), "int main() {}").unwrap();
+        fs::write(dir.join("a.out"), b"\x7fELF\x02\x01\x01\0padding").unwrap();
+        fs::write(dir.join("Main.class"), b"\xca\xfe\xba\xbe\0\0\0\x3d").unwrap();
+        let entries: Vec<_> = ["main.c", "a.out", "Main.class"]
+            .iter()
+            .map(|name| (format!("./{name}"), dir.join(name)))
+            .collect();
+
+        let policy = UploadPolicy {
+            max_file_size: Some(13),
+            max_total_size: Some(32),
+            max_file_count: Some(2),
+            deny: vec![FileKind::Elf, FileKind::Pe],
+        };
+        assert_eq!(
+            policy.check(&entries).unwrap(),
+            PolicyReport {
+                file_count: 3,
+                total_size: 36,
+                violations: vec![
+                    Violation::FileTooLarge {
+                        path: "./a.out".to_string(),
+                        size: 15,
+                        limit: 13
+                    },
+                    Violation::DeniedContent {
+                        path: "./a.out".to_string(),
+                        kind: FileKind::Elf
+                    },
+                    Violation::TotalSizeExceeded {
+                        size: 36,
+                        limit: 32
+                    },
+                    Violation::TooManyFiles { count: 3, limit: 2 },
+                ],
+            }
+        );
+        assert_eq!(
+            UploadPolicy::default().check(&entries).unwrap().violations,
+            []
+        );
+    }
+
+    #[test]
+    fn windows_executables_need_a_pe_header() {
+        let detect = |content: &[u8]| FileKind::detect(&mut io::Cursor::new(content)).unwrap();
+        let mut exe = b"MZ".to_vec();
+        exe.resize(0x80, 0);
+        exe[0x3c] = 0x80;
+        exe.extend_from_slice(b"PE\0\0");
+        assert_eq!(detect(&exe), Some(FileKind::Pe));
+
+        assert_eq!(detect(b"MZ is a notes file, not a program"), None);
+        exe.truncate(0x82);
+        assert_eq!(detect(&exe), None);
+        assert_eq!(detect(b"\x7fELF"), Some(FileKind::Elf));
+    }
+}
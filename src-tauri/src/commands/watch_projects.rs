@@ -0,0 +1,33 @@
+use std::path::Path;
+
+use tauri::{AppHandle, State};
+
+use crate::commands::error::CommandError;
+use crate::watcher::{ProjectWatcher, WatchedProject};
+
+/// Replace the set of watched projects. Changes are reported through the `project-changed` event.
+#[tauri::command]
+pub fn watch_projects(
+    app_handle: AppHandle,
+    watcher: State<'_, ProjectWatcher>,
+    root_dir: String,
+    projects: Vec<WatchedProject>,
+) -> Result<(), CommandError> {
+    watcher.watch(app_handle, Path::new(&root_dir), projects)
+}
+
+#[tauri::command]
+pub fn unwatch_projects(watcher: State<'_, ProjectWatcher>) {
+    watcher.unwatch()
+}
+
+/// Stop reporting changes of a project while the app writes to it, until `resume_watching`.
+#[tauri::command]
+pub fn pause_watching(watcher: State<'_, ProjectWatcher>, project_id: String) {
+    watcher.pause(&project_id)
+}
+
+#[tauri::command]
+pub fn resume_watching(watcher: State<'_, ProjectWatcher>, project_id: String) {
+    watcher.resume(&project_id)
+}
@@ -2,8 +2,10 @@ use tauri::Manager;
 use tauri_plugin_positioner::{Position, WindowExt};
 
 mod commands;
+mod operations;
 mod system_tray;
 mod utils;
+mod watcher;
 
 // Learn more about Tauri commands at https://tauri.app/v1/guides/features/command
 #[tauri::command]
@@ -29,6 +31,8 @@ fn main() {
         .plugin(tauri_plugin_single_instance::init(|app, argv, cwd| {
             println!("{}, {argv:?}, {cwd}", app.package_info().name);
         }))
+        .manage(watcher::ProjectWatcher::default())
+        .manage(operations::OperationRegistry::default())
         .setup(|app| {
             if let Some(window) = app.get_window("main") {
                 let _ = window.move_window(Position::TopRight);
@@ -43,12 +47,40 @@ fn main() {
             commands::create_keys::create_keys,
             commands::create_jwt_token::create_jwt_token,
             commands::get_file_hash::get_file_hash,
+            commands::get_file_hash::get_file_hashes,
+            commands::hash_cache::invalidate_hash_cache,
+            commands::ignore_rules::explain_ignored,
+            commands::ignore_rules::write_course_ignore_rules,
+            commands::diff_files::diff_files,
+            commands::integrity::check_read_only_files,
+            commands::integrity::repair_project,
+            commands::integrity::verify_project,
+            commands::merge_conflicts::merge_conflicts,
             commands::fs_extra::make_readonly,
             commands::fs_extra::create_project_dir,
             commands::fs_extra::create_project_path,
             commands::fs_extra::write_file,
+            commands::fs_extra::remove_file,
             commands::path::path_remove_ancestor,
-            commands::build_tar::build_tar
+            commands::preserved_files::list_preserved_files,
+            commands::preserved_files::restore_preserved_file,
+            commands::pristine::remove_pristine_files,
+            commands::project_history::commit_project_history,
+            commands::pristine::store_pristine_files,
+            commands::snapshots::create_snapshot,
+            commands::snapshots::diff_snapshot,
+            commands::snapshots::list_snapshots,
+            commands::snapshots::remove_snapshots,
+            commands::snapshots::restore_snapshot,
+            commands::build_tar::build_tar,
+            commands::upload_archive::upload_archive,
+            commands::extract_tar::extract_tar,
+            commands::cancel_operation::cancel_operation,
+            commands::sync::compute_sync_plan,
+            commands::watch_projects::watch_projects,
+            commands::watch_projects::unwatch_projects,
+            commands::watch_projects::pause_watching,
+            commands::watch_projects::resume_watching
         ])
         .build(tauri::generate_context!())
         .expect("error while running tauri application")
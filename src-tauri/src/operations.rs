@@ -0,0 +1,188 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::commands::error::{CommandError, ErrorKind};
+
+/// Flag through which a running operation learns that it has been cancelled. Operations check it
+/// between files and while streaming file contents.
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    pub fn check(&self) -> Result<(), CommandError> {
+        if self.is_cancelled() {
+            Err(cancelled())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Fail reads once cancelled, so a single large file doesn't hold up cancellation.
+    pub fn reader<R: Read>(&self, reader: R) -> CancellableReader<R> {
+        CancellableReader {
+            reader,
+            token: self.clone(),
+        }
+    }
+
+    /// An aborted read surfaces as whatever error the caller wrapped it in, report it as
+    /// cancellation instead.
+    pub fn map_result<T>(&self, result: Result<T, CommandError>) -> Result<T, CommandError> {
+        match result {
+            Err(_) if self.is_cancelled() => Err(cancelled()),
+            result => result,
+        }
+    }
+}
+
+fn cancelled() -> CommandError {
+    CommandError::new(ErrorKind::Cancelled, "Operation was cancelled")
+}
+
+/// Payload of the I/O errors of cancelled reads. Their kind must not be `Interrupted`, which
+/// `io::copy` and `read_exact` retry.
+#[derive(Debug)]
+struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Whether `e` is the error of a read through a [`CancellableReader`] after cancellation.
+pub fn is_cancelled_read(e: &io::Error) -> bool {
+    e.get_ref().map_or(false, |inner| inner.is::<Cancelled>())
+}
+
+pub struct CancellableReader<R: Read> {
+    reader: R,
+    token: CancelToken,
+}
+
+impl<R: Read> Read for CancellableReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.token.is_cancelled() {
+            return Err(io::Error::new(io::ErrorKind::Other, Cancelled));
+        }
+        self.reader.read(buf)
+    }
+}
+
+/// Long-running operations by id. The id is chosen by the frontend, which has to know it while
+/// the command is still pending in order to cancel it. As the command may not have started yet
+/// when it is cancelled, unknown ids are kept as cancelled for the operation that registers them.
+#[derive(Default)]
+pub struct OperationRegistry {
+    operations: Mutex<HashMap<String, CancelToken>>,
+    cancelled: Mutex<HashSet<String>>,
+}
+
+impl OperationRegistry {
+    /// Register operation `id`, if given. It stays registered until the returned guard is dropped.
+    /// Its token is already cancelled if `id` was cancelled before.
+    pub fn start(&self, id: Option<String>) -> Result<Operation<'_>, CommandError> {
+        let token = CancelToken::default();
+        if let Some(id) = &id {
+            let mut operations = self.operations.lock().unwrap();
+            if operations.contains_key(id) {
+                return Err(CommandError::new(
+                    ErrorKind::InvalidInput,
+                    format!("Operation '{id}' is already running"),
+                ));
+            }
+            if self.cancelled.lock().unwrap().remove(id) {
+                token.cancel();
+            }
+            operations.insert(id.clone(), token.clone());
+        }
+        Ok(Operation {
+            registry: self,
+            id,
+            token,
+        })
+    }
+
+    /// Returns whether the operation was running. If not, it is cancelled once it starts.
+    pub fn cancel(&self, id: &str) -> bool {
+        match self.operations.lock().unwrap().get(id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => {
+                self.cancelled.lock().unwrap().insert(id.to_string());
+                false
+            }
+        }
+    }
+}
+
+pub struct Operation<'a> {
+    registry: &'a OperationRegistry,
+    id: Option<String>,
+    token: CancelToken,
+}
+
+impl Operation<'_> {
+    pub fn token(&self) -> CancelToken {
+        self.token.clone()
+    }
+}
+
+impl Drop for Operation<'_> {
+    fn drop(&mut self) {
+        if let Some(id) = &self.id {
+            self.registry.operations.lock().unwrap().remove(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancels_registered_operations_until_they_finish() {
+        let registry = OperationRegistry::default();
+        let operation = registry.start(Some("op".to_string())).unwrap();
+        assert!(registry.start(Some("op".to_string())).is_err());
+
+        let mut reader = operation.token().reader(&b"data"[..]);
+        assert!(registry.cancel("op"));
+        assert!(is_cancelled_read(&reader.read(&mut [0; 4]).unwrap_err()));
+        assert_eq!(
+            operation.token().check().unwrap_err().kind,
+            ErrorKind::Cancelled
+        );
+
+        drop(operation);
+        assert!(!registry.cancel("op"));
+    }
+
+    #[test]
+    fn operations_cancelled_before_they_start_start_cancelled() {
+        let registry = OperationRegistry::default();
+        assert!(!registry.cancel("op"));
+        let operation = registry.start(Some("op".to_string())).unwrap();
+        assert!(operation.token().is_cancelled());
+        drop(operation);
+
+        // the cancellation applies to a single start
+        let operation = registry.start(Some("op".to_string())).unwrap();
+        assert!(!operation.token().is_cancelled());
+    }
+}
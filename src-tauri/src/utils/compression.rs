@@ -0,0 +1,166 @@
+use std::io::{self, Write};
+
+use brotli::CompressorWriter;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+
+/// Up to this many bytes, archives are compressed with brotli, which compresses text the best.
+/// Larger ones are compressed with zstd, which is many times faster.
+const ADAPTIVE_BROTLI_LIMIT: u64 = 8 * 1024 * 1024;
+
+/// Window size of brotli as base-2 logarithm, as the server expects it.
+const BROTLI_LGWIN: u32 = 20;
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum Codec {
+    Brotli,
+    Gzip,
+    Zstd,
+    None,
+}
+
+impl Codec {
+    /// The `Content-Encoding` of data compressed with this codec.
+    pub fn encoding(self) -> Option<&'static str> {
+        match self {
+            Codec::Brotli => Some("br"),
+            Codec::Gzip => Some("gzip"),
+            Codec::Zstd => Some("zstd"),
+            Codec::None => None,
+        }
+    }
+
+    /// Brotli for small payloads, zstd for large ones.
+    pub fn adaptive(total_size: u64) -> Self {
+        if total_size <= ADAPTIVE_BROTLI_LIMIT {
+            Codec::Brotli
+        } else {
+            Codec::Zstd
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum Preset {
+    Fast,
+    Balanced,
+    Max,
+}
+
+/// A preset, or a level in the range of the codec, which is clamped otherwise.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(untagged)]
+pub enum Level {
+    Preset(Preset),
+    Value(u32),
+}
+
+impl Level {
+    pub fn resolve(self, codec: Codec) -> u32 {
+        let (min, max) = match codec {
+            Codec::Brotli => (0, 11),
+            Codec::Gzip => (0, 9),
+            Codec::Zstd => (1, 22),
+            Codec::None => (0, 0),
+        };
+        match self {
+            Level::Value(level) => level.clamp(min, max),
+            Level::Preset(preset) => match (codec, preset) {
+                (Codec::None, _) => 0,
+                (_, Preset::Fast) => 1,
+                (Codec::Brotli, Preset::Balanced) => 5,
+                (Codec::Gzip, Preset::Balanced) => 6,
+                (Codec::Zstd, Preset::Balanced) => 3,
+                (Codec::Zstd, Preset::Max) => 19,
+                (_, Preset::Max) => max,
+            },
+        }
+    }
+}
+
+/// Compresses everything written to it with the codec it was created for.
+pub enum Compressor<W: Write> {
+    Brotli(Box<CompressorWriter<W>>),
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+    None(W),
+}
+
+impl<W: Write> Compressor<W> {
+    pub fn new(writer: W, codec: Codec, level: u32) -> io::Result<Self> {
+        Ok(match codec {
+            Codec::Brotli => Self::Brotli(Box::new(CompressorWriter::new(
+                writer,
+                4096,
+                level,
+                BROTLI_LGWIN,
+            ))),
+            Codec::Gzip => Self::Gzip(GzEncoder::new(writer, flate2::Compression::new(level))),
+            Codec::Zstd => Self::Zstd(zstd::stream::write::Encoder::new(writer, level as i32)?),
+            Codec::None => Self::None(writer),
+        })
+    }
+
+    /// Write the end of the compressed stream and return the underlying writer.
+    pub fn finish(self) -> io::Result<W> {
+        match self {
+            Self::Brotli(w) => Ok(w.into_inner()),
+            Self::Gzip(w) => w.finish(),
+            Self::Zstd(w) => w.finish(),
+            Self::None(w) => Ok(w),
+        }
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            Self::Brotli(w) => w,
+            Self::Gzip(w) => w,
+            Self::Zstd(w) => w,
+            Self::None(w) => w,
+        }
+    }
+}
+
+impl<W: Write> Write for Compressor<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer().flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn every_codec_round_trips() {
+        let data = "fn main() { println!(\"hi\"); }\n".repeat(100);
+        for codec in [Codec::Brotli, Codec::Gzip, Codec::Zstd, Codec::None] {
+            let level = Level::Preset(Preset::Fast).resolve(codec);
+            let mut compressor = Compressor::new(Vec::new(), codec, level).unwrap();
+            compressor.write_all(data.as_bytes()).unwrap();
+            let compressed = compressor.finish().unwrap();
+
+            let mut decoded = String::new();
+            let mut reader: Box<dyn Read> = match codec {
+                Codec::Brotli => Box::new(brotli::Decompressor::new(&compressed[..], 4096)),
+                Codec::Gzip => Box::new(flate2::read::GzDecoder::new(&compressed[..])),
+                Codec::Zstd => Box::new(zstd::stream::read::Decoder::new(&compressed[..]).unwrap()),
+                Codec::None => Box::new(&compressed[..]),
+            };
+            reader.read_to_string(&mut decoded).unwrap();
+            assert_eq!(decoded, data, "{codec:?}");
+        }
+
+        assert_eq!(Level::Value(30).resolve(Codec::Brotli), 11);
+        assert_eq!(Level::Preset(Preset::Max).resolve(Codec::Gzip), 9);
+        assert_eq!(Codec::adaptive(1024), Codec::Brotli);
+        assert_eq!(Codec::adaptive(64 * 1024 * 1024), Codec::Zstd);
+    }
+}
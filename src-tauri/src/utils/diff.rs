@@ -0,0 +1,172 @@
+use std::ops::Range;
+
+/// Beyond this many differing lines the search for a minimal diff is given up and the remaining
+/// lines are treated as replaced, which bounds time and memory for unrelated inputs.
+const MAX_EDIT_DISTANCE: usize = 2000;
+
+/// Split text into lines, keeping the line terminators so that joining them restores the text.
+pub fn lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+/// Index pairs `(i, j)` of a longest common subsequence of `a` and `b` (`a[i] == b[j]`), in
+/// ascending order. Uses Myers' O(ND) algorithm after stripping the common prefix and suffix.
+pub fn matching_lines<T: PartialEq>(a: &[T], b: &[T]) -> Vec<(usize, usize)> {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a_mid, b_mid) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut pairs: Vec<(usize, usize)> = (0..prefix).map(|i| (i, i)).collect();
+    pairs.extend(
+        myers(a_mid, b_mid)
+            .into_iter()
+            .map(|(i, j)| (i + prefix, j + prefix)),
+    );
+    pairs.extend((0..suffix).map(|s| (a.len() - suffix + s, b.len() - suffix + s)));
+    pairs
+}
+
+/// One step of turning `a` into `b`, with the indices of the lines involved.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Edit {
+    Keep(usize, usize),
+    Remove(usize),
+    Insert(usize),
+}
+
+/// The edits turning `a` into `b` along [`matching_lines`]. Between two kept lines, removals come
+/// before insertions, as in a unified diff.
+pub fn edit_script<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Edit> {
+    let mut edits = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    for (mi, mj) in matching_lines(a, b).into_iter().chain([(a.len(), b.len())]) {
+        edits.extend((i..mi).map(Edit::Remove));
+        edits.extend((j..mj).map(Edit::Insert));
+        if mi < a.len() {
+            edits.push(Edit::Keep(mi, mj));
+        }
+        (i, j) = (mi + 1, mj + 1);
+    }
+    edits
+}
+
+/// Ranges of `edits` forming the hunks of a unified diff: every change with up to `context` kept
+/// lines around it, merging changes whose context would touch or overlap.
+pub fn hunks(edits: &[Edit], context: usize) -> Vec<Range<usize>> {
+    let mut hunks: Vec<Range<usize>> = Vec::new();
+    let changes = edits
+        .iter()
+        .enumerate()
+        .filter(|(_, edit)| !matches!(edit, Edit::Keep(..)))
+        .map(|(index, _)| index);
+    for index in changes {
+        let start = index.saturating_sub(context);
+        let end = (index + context + 1).min(edits.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.end => last.end = end,
+            _ => hunks.push(start..end),
+        }
+    }
+    hunks
+}
+
+fn myers<T: PartialEq>(a: &[T], b: &[T]) -> Vec<(usize, usize)> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (a.len() + b.len()).min(MAX_EDIT_DISTANCE) as isize;
+    // furthest x reached on diagonal k = x - y, stored at index k + max + 1
+    let mut v = vec![0isize; 2 * max as usize + 3];
+    let index = |k: isize| (k + max + 1) as usize;
+    // the part of `v` each round started from, needed to walk the path back
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    let mut end = None;
+    'search: for d in 0..=max {
+        trace.push(v[index(-d - 1)..=index(d + 1)].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+                v[index(k + 1)]
+            } else {
+                v[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index(k)] = x;
+            if x >= n && y >= m {
+                end = Some(d);
+                break 'search;
+            }
+        }
+    }
+    let Some(end) = end else {
+        return Vec::new();
+    };
+
+    let mut pairs = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (0..=end).rev() {
+        let round = &trace[d as usize];
+        let at = |k: isize| round[(k + d + 1) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let (prev_x, prev_y) = if d == 0 {
+            (0, 0)
+        } else {
+            (at(prev_k), at(prev_k) - prev_k)
+        };
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            pairs.push((x as usize, y as usize));
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    pairs.reverse();
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_a_longest_common_subsequence() {
+        let a: Vec<char> = "ABCABBA".chars().collect();
+        let b: Vec<char> = "CBABAC".chars().collect();
+        let pairs = matching_lines(&a, &b);
+        assert_eq!(pairs.len(), 4);
+        assert!(pairs.iter().all(|&(i, j)| a[i] == b[j]));
+        assert!(pairs.windows(2).all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1));
+
+        assert_eq!(matching_lines::<char>(&[], &[]), []);
+        assert_eq!(matching_lines(&a, &[]), []);
+        assert_eq!(lines("a\nb"), ["a\n", "b"]);
+    }
+
+    #[test]
+    fn groups_edits_into_hunks() {
+        let a = ["1", "2", "3", "4", "5", "6", "7", "8", "9"];
+        let b = ["1", "x", "3", "4", "5", "6", "7", "8"];
+        let edits = edit_script(&a, &b);
+        assert_eq!(
+            edits[..3],
+            [Edit::Keep(0, 0), Edit::Remove(1), Edit::Insert(1)]
+        );
+        assert_eq!(edits.last(), Some(&Edit::Remove(8)));
+        assert_eq!(hunks(&edits, 1), [0..4, 8..10]);
+        assert_eq!(hunks(&edits, 3), vec![0..edits.len()]);
+        assert!(hunks(&edit_script(&a, &a), 3).is_empty());
+    }
+}
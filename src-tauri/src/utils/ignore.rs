@@ -0,0 +1,258 @@
+use serde::Serialize;
+
+/// Where an ignore rule was defined, in increasing order of precedence.
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum RuleSource {
+    Default,
+    Course,
+    Project,
+}
+
+/// One line of a gitignore-style rule file.
+#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Rule {
+    pub source: RuleSource,
+    /// 1-based line in the rule file.
+    pub line: usize,
+    pub pattern: String,
+    #[serde(skip)]
+    negated: bool,
+    #[serde(skip)]
+    dir_only: bool,
+    /// Without a `/` other than a trailing one, a pattern matches names at any depth.
+    #[serde(skip)]
+    anchored: bool,
+    #[serde(skip)]
+    segments: Vec<String>,
+}
+
+impl Rule {
+    fn parse(source: RuleSource, line: usize, text: &str) -> Option<Self> {
+        let pattern = trim_trailing_spaces(text);
+        if pattern.is_empty() || pattern.starts_with('#') {
+            return None;
+        }
+        let (negated, glob) = match pattern.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, pattern),
+        };
+        let (dir_only, glob) = match glob.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, glob),
+        };
+        let anchored = glob.contains('/');
+        let glob = glob.strip_prefix('/').unwrap_or(glob);
+        if glob.is_empty() {
+            return None;
+        }
+        Some(Self {
+            source,
+            line,
+            pattern: pattern.to_string(),
+            negated,
+            dir_only,
+            anchored,
+            segments: glob.split('/').map(str::to_string).collect(),
+        })
+    }
+
+    pub fn is_negated(&self) -> bool {
+        self.negated
+    }
+
+    fn matches(&self, components: &[&str], is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        match components.last() {
+            None => false,
+            Some(name) if !self.anchored => {
+                match_name(self.segments[0].as_bytes(), name.as_bytes())
+            }
+            Some(_) => match_segments(&self.segments, components),
+        }
+    }
+}
+
+/// Trailing spaces are ignored unless escaped with a backslash.
+fn trim_trailing_spaces(text: &str) -> &str {
+    let mut end = text.trim_end_matches(['\r', '\n']).len();
+    while text[..end].ends_with(' ') && !text[..end - 1].ends_with('\\') {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// Gitignore-style rules, where the last matching rule decides and `!` re-includes a path.
+/// A path below an ignored directory stays ignored, as the directory is never entered.
+#[derive(Clone, Default, Debug)]
+pub struct IgnoreRules {
+    rules: Vec<Rule>,
+}
+
+impl IgnoreRules {
+    /// Append the rules of a file, which take precedence over the ones added before.
+    pub fn add(&mut self, source: RuleSource, text: &str) {
+        self.rules.extend(
+            text.lines()
+                .enumerate()
+                .filter_map(|(i, line)| Rule::parse(source, i + 1, line)),
+        );
+    }
+
+    pub fn with(mut self, source: RuleSource, text: &str) -> Self {
+        self.add(source, text);
+        self
+    }
+
+    /// The last rule matching the entry itself. Its parent directories are not considered.
+    pub fn matching_rule(&self, path: &str, is_dir: bool) -> Option<&Rule> {
+        let components = components(path);
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(&components, is_dir))
+    }
+
+    /// Whether a single entry is ignored, for use while walking the tree from its root.
+    pub fn is_ignored_entry(&self, path: &str, is_dir: bool) -> bool {
+        self.matching_rule(path, is_dir)
+            .map_or(false, |rule| !rule.negated)
+    }
+
+    /// Whether an entry is ignored, either itself or by one of its parent directories.
+    pub fn is_ignored(&self, path: &str, is_dir: bool) -> bool {
+        self.explain(path, is_dir)
+            .map_or(false, |(_, rule)| !rule.negated)
+    }
+
+    /// The path and rule deciding whether `path` is ignored: the first ignored parent directory, or
+    /// otherwise the last rule matching the entry itself.
+    pub fn explain(&self, path: &str, is_dir: bool) -> Option<(String, &Rule)> {
+        let components = components(path);
+        for end in 1..components.len() {
+            let dir = components[..end].join("/");
+            if let Some(rule) = self.matching_rule(&dir, true).filter(|r| !r.negated) {
+                return Some((format!("./{dir}"), rule));
+            }
+        }
+        self.matching_rule(path, is_dir)
+            .map(|rule| (format!("./{}", components.join("/")), rule))
+    }
+}
+
+/// Project paths have the `./dir/file` form, the rules are relative to the project root.
+fn components(path: &str) -> Vec<&str> {
+    path.split('/')
+        .filter(|c| !c.is_empty() && *c != ".")
+        .collect()
+}
+
+/// `**` matches any number of directories, a trailing one only the contents of a directory.
+fn match_segments(segments: &[String], components: &[&str]) -> bool {
+    match segments.split_first() {
+        None => components.is_empty(),
+        Some((first, [])) if first == "**" => !components.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=components.len()).any(|skip| match_segments(rest, &components[skip..]))
+        }
+        Some((first, rest)) => match components.split_first() {
+            Some((name, names)) => {
+                match_name(first.as_bytes(), name.as_bytes()) && match_segments(rest, names)
+            }
+            None => false,
+        },
+    }
+}
+
+/// Shell-style matching of a single name with `*`, `?`, `[a-z]`, `[!a-z]` and `\` escapes.
+fn match_name(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|skip| match_name(rest, &name[skip..])),
+        Some((b'?', rest)) => !name.is_empty() && match_name(rest, &name[1..]),
+        Some((b'[', rest)) => match (name.split_first(), class_end(rest)) {
+            (Some((c, name)), Some(end)) => {
+                in_class(&rest[..end], *c) && match_name(&rest[end + 1..], name)
+            }
+            // an unterminated class is taken literally
+            (Some((b'[', name)), None) => match_name(rest, name),
+            _ => false,
+        },
+        Some((b'\\', [escaped, rest @ ..])) => {
+            name.first() == Some(escaped) && match_name(rest, &name[1..])
+        }
+        Some((c, rest)) => name.first() == Some(c) && match_name(rest, &name[1..]),
+    }
+}
+
+/// Position of the `]` closing a class, where a `]` right after the opening bracket (and its
+/// negation) is part of the class.
+fn class_end(pattern: &[u8]) -> Option<usize> {
+    let first = match pattern.first() {
+        Some(b'!' | b'^') => 2,
+        _ => 1,
+    };
+    pattern
+        .iter()
+        .skip(first)
+        .position(|c| *c == b']')
+        .map(|i| i + first)
+}
+
+fn in_class(class: &[u8], c: u8) -> bool {
+    let (negated, class) = match class.first() {
+        Some(b'!' | b'^') => (true, &class[1..]),
+        _ => (false, class),
+    };
+    let mut found = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == b'-' {
+            found |= class[i] <= c && c <= class[i + 2];
+            i += 3;
+        } else {
+            found |= class[i] == c;
+            i += 1;
+        }
+    }
+    found != negated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn later_rules_take_precedence() {
+        let rules = IgnoreRules::default()
+            .with(RuleSource::Default, "__pycache__/\n*.pyc\ntarget/\n")
+            .with(
+                RuleSource::Project,
+                "# keep the solution\n!solution.pyc\n/build/**\ndocs/**/*.tmp \n[ab]?.o\n",
+            );
+        assert!(rules.is_ignored("./__pycache__/main.cpython-311.pyc", false));
+        assert!(rules.is_ignored("./src/__pycache__/x", false));
+        assert!(rules.is_ignored("./lib/util.pyc", false));
+        assert!(!rules.is_ignored("./solution.pyc", false));
+        assert!(!rules.is_ignored("./target", false), "only directories");
+        assert!(rules.is_ignored("./target", true));
+        assert!(rules.is_ignored("./target/debug/app", false));
+        assert!(rules.is_ignored("./build/out/a.bin", false));
+        assert!(!rules.is_ignored("./src/build/a.bin", false));
+        assert!(rules.is_ignored("./docs/a.tmp", false));
+        assert!(rules.is_ignored("./docs/x/y/a.tmp", false));
+        assert!(rules.is_ignored("./b1.o", false));
+        assert!(!rules.is_ignored("./c1.o", false));
+        assert!(!rules.is_ignored("./main.py", false));
+
+        let (path, rule) = rules.explain("./target/debug/app", false).unwrap();
+        assert_eq!(path, "./target");
+        assert_eq!((rule.source, rule.line), (RuleSource::Default, 3));
+        let (_, rule) = rules.explain("./solution.pyc", false).unwrap();
+        assert!(rule.is_negated());
+        assert_eq!(rule.pattern, "!solution.pyc");
+    }
+}
@@ -0,0 +1,104 @@
+use crate::utils::diff::{lines, matching_lines};
+
+pub const LOCAL_MARKER: &str = "<<<<<<< local";
+pub const SEPARATOR_MARKER: &str = "=======";
+pub const REMOTE_MARKER: &str = ">>>>>>> remote";
+
+#[derive(PartialEq, Eq, Debug)]
+pub struct Merge {
+    pub text: String,
+    /// Number of regions edited differently on both sides, marked up in `text` like git does.
+    pub conflicts: usize,
+}
+
+/// Three-way merge of two texts derived from `base`, line by line. Regions changed on one side
+/// only take that side, identical changes are taken once.
+pub fn merge(base: &str, local: &str, remote: &str) -> Merge {
+    let (base, local, remote) = (lines(base), lines(local), lines(remote));
+    let to_local = matches_by_base(&base, &local);
+    let to_remote = matches_by_base(&base, &remote);
+
+    let mut merge = Merge {
+        text: String::new(),
+        conflicts: 0,
+    };
+    let (mut i, mut j, mut k) = (0, 0, 0);
+    loop {
+        // the next base line kept by both sides, everything before it is a changed chunk
+        let stable = (i..base.len()).find_map(|o| Some((o, to_local[o]?, to_remote[o]?)));
+        let (o, oj, ok) = stable.unwrap_or((base.len(), local.len(), remote.len()));
+        merge.chunk(&base[i..o], &local[j..oj], &remote[k..ok]);
+        if stable.is_none() {
+            return merge;
+        }
+        merge.text.push_str(base[o]);
+        (i, j, k) = (o + 1, oj + 1, ok + 1);
+    }
+}
+
+impl Merge {
+    fn chunk(&mut self, base: &[&str], local: &[&str], remote: &[&str]) {
+        if local == remote || remote == base {
+            self.push_lines(local);
+        } else if local == base {
+            self.push_lines(remote);
+        } else {
+            self.conflicts += 1;
+            self.push_marker(LOCAL_MARKER);
+            self.push_lines(local);
+            self.push_marker(SEPARATOR_MARKER);
+            self.push_lines(remote);
+            self.push_marker(REMOTE_MARKER);
+        }
+    }
+
+    fn push_lines(&mut self, lines: &[&str]) {
+        lines.iter().for_each(|line| self.text.push_str(line));
+    }
+
+    fn push_marker(&mut self, marker: &str) {
+        if !self.text.is_empty() && !self.text.ends_with('\n') {
+            self.text.push('\n');
+        }
+        self.text.push_str(marker);
+        self.text.push('\n');
+    }
+}
+
+/// For every line of `base`, the index of the line in `other` it was matched with, if any.
+fn matches_by_base(base: &[&str], other: &[&str]) -> Vec<Option<usize>> {
+    let mut matches = vec![None; base.len()];
+    for (i, j) in matching_lines(base, other) {
+        matches[i] = Some(j);
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_separate_edits_and_marks_overlapping_ones() {
+        let base = "a\nb\nc\nd\n";
+        assert_eq!(
+            merge(base, "A\nb\nc\nd\n", "a\nb\nc\nD\n"),
+            Merge {
+                text: "A\nb\nc\nD\n".to_string(),
+                conflicts: 0
+            }
+        );
+        assert_eq!(
+            merge(base, "a\nX\nc\nd\n", "a\nX\nc\nd\n").text,
+            "a\nX\nc\nd\n"
+        );
+        assert_eq!(merge(base, "b\nc\n", "a\nb\nx\nc\nd\n").text, "b\nx\nc\n");
+        assert_eq!(
+            merge(base, "a\nL\nc\nd\n", "a\nR\nc\nd"),
+            Merge {
+                text: "a\n<<<<<<< local\nL\n=======\nR\n>>>>>>> remote\nc\nd".to_string(),
+                conflicts: 1
+            }
+        );
+    }
+}
@@ -1,2 +1,13 @@
+pub mod compression;
+pub mod diff;
+pub mod ignore;
+pub mod merge;
+pub mod object_store;
+pub mod parallel;
+pub mod path_guard;
+pub mod progress;
+pub mod project_data;
+pub mod settings;
+pub mod tee_reader;
 pub mod tee_writer;
 pub mod window;
@@ -0,0 +1,243 @@
+use std::collections::HashSet;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use data_encoding::HEXLOWER;
+use sha2::{Digest, Sha256};
+
+use crate::commands::error::{CommandError, ErrorKind};
+use crate::commands::fs_extra::{self, FilePermissions};
+use crate::utils::project_data::{app_local_data_dir, check_project_id};
+use crate::utils::tee_reader::TeeReader;
+
+/// Serializes changes to the store, so garbage collection never sees an object whose reference
+/// has not been recorded yet.
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// Content-addressed store of file versions, keyed by their SHA-256 as returned by
+/// `get_file_hash`. Objects live in `objects/{first two hex digits}/{hash}` and are shared between
+/// projects. Each project lists the hashes it refers to in `refs/{project_id}.json`; objects no
+/// project refers to are removed by garbage collection.
+pub struct ObjectStore {
+    root: PathBuf,
+}
+
+impl ObjectStore {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    pub fn from_app(app_handle: &tauri::AppHandle) -> Result<Self, CommandError> {
+        app_local_data_dir(app_handle).map(|dir| Self::new(dir.join("pristine")))
+    }
+
+    pub fn object_path(&self, hash: &str) -> Result<PathBuf, CommandError> {
+        check_hash(hash)?;
+        Ok(self.root.join("objects").join(&hash[..2]).join(hash))
+    }
+
+    /// Open an object, or return `None` if it is not stored.
+    pub fn open(&self, hash: &str) -> Result<Option<File>, CommandError> {
+        let path = self.object_path(hash)?;
+        match File::open(&path) {
+            Ok(file) => Ok(Some(file)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(CommandError::io("Could not open stored file", &path, e)),
+        }
+    }
+
+    /// Store the content of `reader` under `hash`, unless it is stored already. Content that
+    /// doesn't match the hash is discarded and `false` is returned.
+    pub fn insert(&self, hash: &str, reader: &mut impl Read) -> Result<bool, CommandError> {
+        let path = self.object_path(hash)?;
+        if path.is_file() {
+            return Ok(true);
+        }
+        let dir = path.parent().expect("objects are stored in a subdirectory");
+        fs::create_dir_all(dir)
+            .map_err(|e| CommandError::io("Could not create object store", dir, e))?;
+        let mut tee = TeeReader::new(reader, Sha256::new());
+        fs_extra::write_file_from(&path, &mut tee, FilePermissions::R)?;
+        let (_, hasher) = tee.into_inner();
+        if HEXLOWER.encode(hasher.finalize().as_ref()) != hash {
+            remove_object(&path)?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// Store the files of a project, given as `(hash, path)`, and make them the only objects the
+    /// project refers to. Returns the paths that no longer exist or whose content no longer
+    /// matched their hash. Objects only the previous version of the project referred to are
+    /// removed.
+    pub fn update_project(
+        &self,
+        project_id: &str,
+        files: &[(String, PathBuf)],
+    ) -> Result<Vec<PathBuf>, CommandError> {
+        let _lock = lock();
+        self.write_refs(project_id, files.iter().map(|(hash, _)| hash.as_str()))?;
+        let mut changed = Vec::new();
+        for (hash, path) in files {
+            let stored = match File::open(path) {
+                Ok(mut reader) => self.insert(hash, &mut reader)?,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => false,
+                Err(e) => return Err(CommandError::io("Could not open file", path, e)),
+            };
+            if !stored {
+                changed.push(path.clone());
+            }
+        }
+        self.collect_garbage()?;
+        Ok(changed)
+    }
+
+    /// Drop the references of a removed project and every object only it referred to.
+    pub fn remove_project(&self, project_id: &str) -> Result<(), CommandError> {
+        let _lock = lock();
+        let refs = self.refs_path(project_id)?;
+        match fs::remove_file(&refs) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                return Err(CommandError::io("Could not remove references", &refs, e))
+            }
+            _ => {}
+        }
+        self.collect_garbage()
+    }
+
+    fn refs_path(&self, project_id: &str) -> Result<PathBuf, CommandError> {
+        check_project_id(project_id)?;
+        Ok(self.root.join("refs").join(format!("{project_id}.json")))
+    }
+
+    fn write_refs<'a>(
+        &self,
+        project_id: &str,
+        hashes: impl Iterator<Item = &'a str>,
+    ) -> Result<(), CommandError> {
+        let refs = self.refs_path(project_id)?;
+        let hashes: HashSet<&str> = hashes.collect();
+        let json = serde_json::to_vec(&hashes).map_err(|e| {
+            CommandError::new(
+                ErrorKind::Io,
+                format!("Could not serialize references: {e}"),
+            )
+        })?;
+        let dir = refs
+            .parent()
+            .expect("references are stored in a subdirectory");
+        fs::create_dir_all(dir)
+            .map_err(|e| CommandError::io("Could not create object store", dir, e))?;
+        fs_extra::write_file_from(&refs, &mut json.as_slice(), FilePermissions::Rw)
+    }
+
+    /// Remove every object that no project refers to. Must be called with the lock held.
+    fn collect_garbage(&self) -> Result<(), CommandError> {
+        let mut referenced = HashSet::new();
+        for refs in read_dir_or_empty(&self.root.join("refs"))? {
+            let bytes = fs::read(&refs)
+                .map_err(|e| CommandError::io("Could not read references", &refs, e))?;
+            // an unreadable reference file aborts the collection, losing a pristine copy is worse
+            // than keeping a few stale objects
+            let hashes: Vec<String> = serde_json::from_slice(&bytes).map_err(|e| {
+                CommandError::new(ErrorKind::Io, format!("Invalid references: {e}"))
+                    .with_path(&refs)
+            })?;
+            referenced.extend(hashes);
+        }
+        for dir in read_dir_or_empty(&self.root.join("objects"))? {
+            for object in read_dir_or_empty(&dir)? {
+                let name = object.file_name().unwrap_or_default().to_string_lossy();
+                // temp files of writes in progress start with a dot
+                if !name.starts_with('.') && !referenced.contains(name.as_ref()) {
+                    remove_object(&object)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn lock() -> MutexGuard<'static, ()> {
+    // the guarded state lives on disk, a panic while holding the lock doesn't corrupt it
+    LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn read_dir_or_empty(dir: &Path) -> Result<Vec<PathBuf>, CommandError> {
+    match fs::read_dir(dir) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(CommandError::io("Could not read dir", dir, e)),
+        Ok(entries) => entries
+            .map(|entry| {
+                entry
+                    .map(|e| e.path())
+                    .map_err(|e| CommandError::io("Could not read dir entry", dir, e))
+            })
+            .collect(),
+    }
+}
+
+fn remove_object(path: &Path) -> Result<(), CommandError> {
+    // read-only files cannot be removed on Windows
+    fs_extra::remove_read_only(path)?;
+    fs::remove_file(path).map_err(|e| CommandError::io("Could not remove stored file", path, e))
+}
+
+/// Hashes name files in the store, so they must not contain path separators.
+fn check_hash(hash: &str) -> Result<(), CommandError> {
+    if hash.len() == 64 && hash.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f')) {
+        Ok(())
+    } else {
+        Err(CommandError::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid file hash '{hash}'"),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(content: &str) -> String {
+        HEXLOWER.encode(Sha256::digest(content.as_bytes()).as_ref())
+    }
+
+    #[test]
+    fn keeps_objects_while_referenced() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let store = ObjectStore::new(dir.join("store"));
+        let (a, b) = (dir.join("a.txt"), dir.join("b.txt"));
+        fs::write(&a, "a").unwrap();
+        fs::write(&b, "b").unwrap();
+
+        let changed = store
+            .update_project("p1", &[(hash("a"), a.clone()), (hash("x"), b.clone())])
+            .unwrap();
+        assert_eq!(changed, vec![b.clone()]);
+        store
+            .update_project("p2", &[(hash("a"), a.clone())])
+            .unwrap();
+        store.update_project("p1", &[(hash("b"), b)]).unwrap();
+
+        let read = |h: &str| {
+            store.open(h).unwrap().map(|mut f| {
+                let mut content = String::new();
+                f.read_to_string(&mut content).unwrap();
+                content
+            })
+        };
+        assert_eq!(read(&hash("a")).as_deref(), Some("a"));
+        assert_eq!(read(&hash("b")).as_deref(), Some("b"));
+        assert_eq!(read(&hash("x")), None);
+
+        store.remove_project("p2").unwrap();
+        assert_eq!(read(&hash("a")), None);
+        assert!(store.open("../../etc/passwd").is_err());
+    }
+}
@@ -0,0 +1,85 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::commands::error::{CommandError, ErrorKind};
+
+/// Upper bound for worker threads, so a large batch doesn't starve the rest of the app.
+const MAX_WORKERS: usize = 8;
+
+/// Apply `f` to every item on a bounded number of scoped worker threads. The results are
+/// returned in the order of `items`.
+pub fn map_bounded<T, R, F>(items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let workers = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(MAX_WORKERS)
+        .min(items.len());
+    if workers <= 1 {
+        return items.iter().map(f).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, R)> = thread::scope(|s| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                s.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(item) = items.get(i) else {
+                            return results;
+                        };
+                        results.push((i, f(item)));
+                    }
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().expect("worker thread panicked"))
+            .collect()
+    });
+    results.sort_unstable_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, r)| r).collect()
+}
+
+/// Run blocking work such as hashing or compression on Tauri's blocking thread pool, so async
+/// commands don't stall the runtime that also serves IPC.
+pub async fn run_blocking<R, F>(f: F) -> Result<R, CommandError>
+where
+    R: Send + 'static,
+    F: FnOnce() -> Result<R, CommandError> + Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(f)
+        .await
+        .map_err(|e| CommandError::new(ErrorKind::Io, format!("Worker thread failed: {e}")))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn map_bounded_keeps_order_and_bounds_workers() {
+        let active = AtomicUsize::new(0);
+        let most = AtomicUsize::new(0);
+        let items: Vec<usize> = (0..64).collect();
+        let results = map_bounded(&items, |&i| {
+            let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+            most.fetch_max(now, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(2));
+            active.fetch_sub(1, Ordering::SeqCst);
+            i * 2
+        });
+        assert_eq!(results, items.iter().map(|i| i * 2).collect::<Vec<_>>());
+        assert!(most.load(Ordering::SeqCst) <= MAX_WORKERS);
+
+        assert_eq!(map_bounded(&[] as &[usize], |&i| i), Vec::<usize>::new());
+        assert_eq!(map_bounded(&[3], |&i| i + 1), [4]);
+    }
+}
@@ -0,0 +1,162 @@
+use std::path::{Component, Path, PathBuf};
+
+use crate::commands::error::{CommandError, ErrorKind};
+use crate::utils::settings::read_setting;
+
+fn path_escape(path: &Path, message: &str) -> CommandError {
+    CommandError::new(ErrorKind::PathEscape, message).with_path(path)
+}
+
+/// Restricts filesystem commands to the configured project directory and the app's own data
+/// directories. Targets are canonicalized, so symlinks pointing elsewhere are rejected as well.
+pub struct PathGuard {
+    roots: Vec<PathBuf>,
+}
+
+impl PathGuard {
+    pub fn new(roots: impl IntoIterator<Item = PathBuf>) -> Self {
+        Self {
+            roots: roots
+                .into_iter()
+                .filter_map(|root| root.canonicalize().ok())
+                .collect(),
+        }
+    }
+
+    /// Allow the project directory (`projectDir` setting) and the app data directories.
+    pub fn from_app(app_handle: &tauri::AppHandle) -> Self {
+        let resolver = app_handle.path_resolver();
+        let project_dir =
+            read_setting(app_handle, "projectDir").and_then(|dir| dir.as_str().map(PathBuf::from));
+        Self::new(
+            project_dir
+                .into_iter()
+                .chain(resolver.app_local_data_dir())
+                .chain(resolver.app_data_dir()),
+        )
+    }
+
+    /// Additionally allow the temp dir, where archives are built before being uploaded.
+    pub fn with_temp_dir(mut self) -> Self {
+        if let Ok(temp_dir) = std::env::temp_dir().canonicalize() {
+            self.roots.push(temp_dir);
+        }
+        self
+    }
+
+    /// Check an absolute path and return its canonical form. The path does not need to exist.
+    pub fn check(&self, path: &Path) -> Result<PathBuf, CommandError> {
+        if !path.is_absolute() {
+            return Err(path_escape(path, "Path must be absolute"));
+        }
+        if path.components().any(|c| c == Component::ParentDir) {
+            return Err(path_escape(path, "Path must not contain '..'"));
+        }
+        let resolved = canonicalize_existing(path)
+            .map_err(|e| CommandError::io("Could not resolve path", path, e))?;
+        if self.roots.iter().any(|root| resolved.starts_with(root)) {
+            Ok(resolved)
+        } else {
+            Err(path_escape(
+                path,
+                "Path is outside of the project directory",
+            ))
+        }
+    }
+
+    /// Check a path relative to `root`, e.g. a file name from a tar file list.
+    pub fn check_relative(&self, root: &Path, relative: &str) -> Result<PathBuf, CommandError> {
+        self.check(&root.join(relative_path(relative)?))
+    }
+}
+
+/// Check that `relative` stays below the directory it is relative to, without touching the file
+/// system. Unlike [`PathGuard::check_relative`], symlinks are not resolved.
+pub fn relative_path(relative: &str) -> Result<&Path, CommandError> {
+    let relative_path = Path::new(relative);
+    if relative_path.has_root()
+        || relative_path
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(path_escape(
+            relative_path,
+            "Path must be relative to the project",
+        ));
+    }
+    Ok(relative_path)
+}
+
+/// Canonicalize the longest existing prefix of `path` and append the rest unchanged.
+fn canonicalize_existing(path: &Path) -> std::io::Result<PathBuf> {
+    let mut missing = Vec::new();
+    let mut existing = path;
+    loop {
+        match existing.canonicalize() {
+            Ok(canonical) => {
+                return Ok(missing
+                    .iter()
+                    .rev()
+                    .fold(canonical, |acc: PathBuf, name| acc.join(name)))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let (Some(parent), Some(name)) = (existing.parent(), existing.file_name()) else {
+                    return Err(e);
+                };
+                missing.push(name);
+                existing = parent;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn setup() -> (tempfile::TempDir, PathGuard) {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("projects/course")).unwrap();
+        fs::create_dir_all(dir.path().join("elsewhere")).unwrap();
+        let guard = PathGuard::new([dir.path().join("projects")]);
+        (dir, guard)
+    }
+
+    #[test]
+    fn accepts_paths_inside_root() {
+        let (tmp, guard) = setup();
+        let dir = tmp.path();
+        assert!(guard.check(&dir.join("projects/course/main.py")).is_ok());
+        assert!(guard.check(&dir.join("projects/new/dir/file.py")).is_ok());
+        assert!(guard
+            .check_relative(&dir.join("projects/course"), "./sub/main.py")
+            .is_ok());
+    }
+
+    #[test]
+    fn rejects_escapes() {
+        let (tmp, guard) = setup();
+        let dir = tmp.path();
+        assert!(guard.check(&dir.join("elsewhere/file.py")).is_err());
+        assert!(guard
+            .check(&dir.join("projects/../elsewhere/file.py"))
+            .is_err());
+        assert!(guard
+            .check_relative(&dir.join("projects"), "../elsewhere/file.py")
+            .is_err());
+        assert!(guard
+            .check_relative(&dir.join("projects"), "/etc/passwd")
+            .is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_symlink_escapes() {
+        let (tmp, guard) = setup();
+        let dir = tmp.path();
+        std::os::unix::fs::symlink(dir.join("elsewhere"), dir.join("projects/link")).unwrap();
+        assert!(guard.check(&dir.join("projects/link/file.py")).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::commands::error::{CommandError, ErrorKind};
+
+/// Hashing many small files would otherwise flood the webview with events.
+const EMIT_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Progress {
+    pub bytes: u64,
+    pub total: u64,
+    pub current_file: Option<String>,
+}
+
+type Sink = Box<dyn Fn(Progress) + Send + Sync>;
+
+#[derive(Default)]
+struct State {
+    current_file: Option<String>,
+    last_emit: Option<Instant>,
+}
+
+/// Counts the bytes processed by an operation, possibly on several worker threads, and reports
+/// them to a sink at most every [`EMIT_INTERVAL`].
+pub struct ProgressReporter {
+    sink: Option<Sink>,
+    bytes: AtomicU64,
+    total: AtomicU64,
+    state: Mutex<State>,
+}
+
+impl ProgressReporter {
+    pub fn new(sink: impl Fn(Progress) + Send + Sync + 'static) -> Self {
+        Self {
+            sink: Some(Box::new(sink)),
+            ..Self::silent()
+        }
+    }
+
+    pub fn silent() -> Self {
+        Self {
+            sink: None,
+            bytes: AtomicU64::new(0),
+            total: AtomicU64::new(0),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Emit progress to the frontend as events named `channel`. The frontend picks a unique
+    /// channel per operation, without one progress is not reported.
+    pub fn for_channel(
+        app_handle: &AppHandle,
+        channel: Option<String>,
+    ) -> Result<Self, CommandError> {
+        let Some(channel) = channel else {
+            return Ok(Self::silent());
+        };
+        if channel.is_empty()
+            || !channel
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '/' | ':' | '_'))
+        {
+            return Err(CommandError::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid progress channel '{channel}'"),
+            ));
+        }
+        let app_handle = app_handle.clone();
+        Ok(Self::new(move |progress| {
+            if let Err(e) = app_handle.emit_all(&channel, progress) {
+                eprintln!("Could not emit progress: {e}");
+            }
+        }))
+    }
+
+    pub fn set_total(&self, total: u64) {
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    pub fn start_file(&self, file: &str) {
+        self.state.lock().unwrap().current_file = Some(file.to_string());
+        self.emit(false);
+    }
+
+    pub fn advance(&self, bytes: u64) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.emit(false);
+    }
+
+    /// Report the final state regardless of when progress was last emitted.
+    pub fn finish(&self) {
+        self.emit(true);
+    }
+
+    /// Count everything read through `reader`.
+    pub fn reader<R: Read>(&self, reader: R) -> ProgressReader<'_, R> {
+        ProgressReader {
+            reader,
+            reporter: self,
+        }
+    }
+
+    fn emit(&self, force: bool) {
+        let Some(sink) = &self.sink else {
+            return;
+        };
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        if !force
+            && state
+                .last_emit
+                .map_or(false, |last| now.duration_since(last) < EMIT_INTERVAL)
+        {
+            return;
+        }
+        state.last_emit = Some(now);
+        sink(Progress {
+            bytes: self.bytes.load(Ordering::Relaxed),
+            total: self.total.load(Ordering::Relaxed),
+            current_file: state.current_file.clone(),
+        });
+    }
+}
+
+pub struct ProgressReader<'a, R: Read> {
+    reader: R,
+    reporter: &'a ProgressReporter,
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        self.reporter.advance(n as u64);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn reports_bytes_read_and_the_current_file() {
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&reported);
+        let reporter = ProgressReporter::new(move |p| sink.lock().unwrap().push(p));
+        reporter.set_total(5);
+        reporter.start_file("./a.txt");
+        io::copy(&mut reporter.reader(&b"hello"[..]), &mut io::sink()).unwrap();
+        reporter.finish();
+
+        let reported = reported.lock().unwrap();
+        assert_eq!(
+            reported.last(),
+            Some(&Progress {
+                bytes: 5,
+                total: 5,
+                current_file: Some("./a.txt".to_string()),
+            })
+        );
+        // throttled: the first event and the final one, nothing in between
+        assert_eq!(reported.len(), 2);
+    }
+}
@@ -0,0 +1,44 @@
+use std::path::PathBuf;
+
+use crate::commands::error::{CommandError, ErrorKind};
+
+/// Location of per-project app data, `{app-local data dir}/{store}/{project_id}`.
+pub fn project_data_path(
+    app_handle: &tauri::AppHandle,
+    store: &str,
+    project_id: &str,
+) -> Result<PathBuf, CommandError> {
+    check_project_id(project_id)?;
+    app_local_data_dir(app_handle).map(|mut path| {
+        path.push(store);
+        path.push(project_id);
+        path
+    })
+}
+
+pub fn app_local_data_dir(app_handle: &tauri::AppHandle) -> Result<PathBuf, CommandError> {
+    app_handle
+        .path_resolver()
+        .app_local_data_dir()
+        .ok_or_else(|| {
+            CommandError::new(
+                ErrorKind::NotFound,
+                "Unable to determine app-local data dir",
+            )
+        })
+}
+
+/// Project ids become path components, so they are limited to a safe set of characters.
+pub fn check_project_id(project_id: &str) -> Result<(), CommandError> {
+    if project_id.is_empty()
+        || !project_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(CommandError::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid project id '{project_id}'"),
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,16 @@
+use serde_json::Value;
+use std::fs;
+
+/// File the frontend persists its settings to through `tauri-plugin-store`.
+const SETTINGS_FILE: &str = "settings.json";
+
+/// Read a setting written by the frontend. Missing or unreadable settings yield `None`.
+pub fn read_setting(app_handle: &tauri::AppHandle, key: &str) -> Option<Value> {
+    let path = app_handle
+        .path_resolver()
+        .app_data_dir()?
+        .join(SETTINGS_FILE);
+    let contents = fs::read(path).ok()?;
+    let mut settings: serde_json::Map<String, Value> = serde_json::from_slice(&contents).ok()?;
+    settings.remove(key)
+}
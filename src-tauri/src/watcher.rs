@@ -0,0 +1,396 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::commands::error::{CommandError, ErrorKind};
+use crate::commands::ignore_rules::{course_rules_file, load_rules_from, PROJECT_RULES_FILE};
+use crate::commands::sync::{is_visible, to_project_path};
+use crate::utils::ignore::IgnoreRules;
+
+pub const PROJECT_CHANGED_EVENT: &str = "project-changed";
+
+/// Bursts of changes (e.g. an IDE saving and reformatting) are reported as a single event.
+const DEBOUNCE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How long events of a project are still dropped after it was resumed: changes made while it was
+/// paused are only delivered once the debounce timeout has passed.
+const RESUME_GRACE: Duration = Duration::from_millis(2 * DEBOUNCE_TIMEOUT.as_millis() as u64);
+
+/// How often a removed root dir is checked for being recreated.
+const REARM_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchedProject {
+    pub project_id: String,
+    /// Relative to the project root directory.
+    pub base_path: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectChanged {
+    pub project_id: String,
+    pub paths: Vec<String>,
+}
+
+/// A registered project with the rules deciding which of its changes are reported.
+struct WatchedDir {
+    project_id: String,
+    base_path: PathBuf,
+    course_rules: PathBuf,
+    rules: IgnoreRules,
+}
+
+impl WatchedDir {
+    /// Pick up edits of the `.cxignore` of the project.
+    fn reload_rules(&mut self, paths: &[PathBuf]) {
+        if paths.contains(&self.base_path.join(PROJECT_RULES_FILE)) {
+            match load_rules_from(&self.course_rules, &self.base_path) {
+                Ok(rules) => self.rules = rules,
+                Err(e) => eprintln!("Could not reload ignore rules: {e}"),
+            }
+        }
+    }
+}
+
+/// Whether the changes of a project are dropped because the app itself writes to it.
+enum Mute {
+    /// By the number of operations in progress.
+    Paused(usize),
+    Until(Instant),
+}
+
+#[derive(Default)]
+struct MutedProjects(HashMap<String, Mute>);
+
+impl MutedProjects {
+    fn is_muted(&self, project_id: &str, now: Instant) -> bool {
+        match self.0.get(project_id) {
+            Some(Mute::Paused(_)) => true,
+            Some(Mute::Until(until)) => now < *until,
+            None => false,
+        }
+    }
+
+    fn pause(&mut self, project_id: &str) {
+        let count = match self.0.get(project_id) {
+            Some(Mute::Paused(count)) => count + 1,
+            _ => 1,
+        };
+        self.0.insert(project_id.to_string(), Mute::Paused(count));
+    }
+
+    fn resume(&mut self, project_id: &str, now: Instant) {
+        match self.0.get_mut(project_id) {
+            Some(Mute::Paused(count)) if *count > 1 => *count -= 1,
+            Some(mute) => *mute = Mute::Until(now + RESUME_GRACE),
+            None => {}
+        }
+    }
+}
+
+struct Watch {
+    root_dir: PathBuf,
+    /// Set once the root dir itself disappeared, after which the watch has to be re-established.
+    stale: Arc<AtomicBool>,
+    // dropping the debouncer stops watching
+    _debouncer: Debouncer<RecommendedWatcher>,
+}
+
+/// State shared with the callback of the watch.
+#[derive(Default, Clone)]
+struct Shared {
+    watch: Arc<Mutex<Option<Watch>>>,
+    projects: Arc<Mutex<Vec<WatchedDir>>>,
+    muted: Arc<Mutex<MutedProjects>>,
+}
+
+/// Watches the project root directory recursively and emits [`PROJECT_CHANGED_EVENT`] for every
+/// registered project with changed visible files that are not ignored, or a changed `.cxignore`.
+/// Watching the root rather than the individual
+/// project directories keeps working when a project directory is deleted and recreated.
+/// If the root itself is removed, it is watched again as soon as it is recreated, and every
+/// project is reported as changed. Changes of paused projects are not reported, see
+/// [`ProjectWatcher::pause`].
+#[derive(Default)]
+pub struct ProjectWatcher {
+    shared: Shared,
+}
+
+impl ProjectWatcher {
+    pub fn watch(
+        &self,
+        app_handle: AppHandle,
+        root_dir: &Path,
+        projects: Vec<WatchedProject>,
+    ) -> Result<(), CommandError> {
+        let root_dir = canonicalize(root_dir)
+            .map_err(|e| CommandError::io("Could not resolve root dir", root_dir, e))?;
+
+        *self.shared.projects.lock().unwrap() = projects
+            .into_iter()
+            .map(|p| {
+                let base_path = root_dir.join(p.base_path);
+                let course_rules = course_rules_file(&app_handle, &p.project_id)?;
+                Ok(WatchedDir {
+                    rules: load_rules_from(&course_rules, &base_path)?,
+                    project_id: p.project_id,
+                    base_path,
+                    course_rules,
+                })
+            })
+            .collect::<Result<_, CommandError>>()?;
+
+        let mut watch = self.shared.watch.lock().unwrap();
+        if watch.as_ref().map_or(false, |w| {
+            w.root_dir == root_dir && !w.stale.load(Ordering::SeqCst)
+        }) {
+            return Ok(());
+        }
+        *watch = Some(start(app_handle, root_dir, self.shared.clone())?);
+        Ok(())
+    }
+
+    pub fn unwatch(&self) {
+        *self.shared.watch.lock().unwrap() = None;
+        self.shared.projects.lock().unwrap().clear();
+    }
+
+    /// Stop reporting the changes of a project while the app writes to it, e.g. during a sync.
+    /// Pauses nest, each must be ended with [`ProjectWatcher::resume`].
+    pub fn pause(&self, project_id: &str) {
+        self.shared.muted.lock().unwrap().pause(project_id);
+    }
+
+    pub fn resume(&self, project_id: &str) {
+        self.shared
+            .muted
+            .lock()
+            .unwrap()
+            .resume(project_id, Instant::now());
+    }
+}
+
+fn start(app_handle: AppHandle, root_dir: PathBuf, shared: Shared) -> Result<Watch, CommandError> {
+    let stale = Arc::new(AtomicBool::new(false));
+    let watched_root = root_dir.clone();
+    let stale_flag = Arc::clone(&stale);
+    let callback_shared = shared.clone();
+    let callback_handle = app_handle.clone();
+    let mut debouncer = new_debouncer(DEBOUNCE_TIMEOUT, move |res: DebounceEventResult| {
+        match res {
+            Ok(events) => {
+                let paths: Vec<_> = events.into_iter().map(|e| e.path).collect();
+                let mut projects = callback_shared.projects.lock().unwrap();
+                for project in projects.iter_mut() {
+                    project.reload_rules(&paths);
+                }
+                let muted = callback_shared.muted.lock().unwrap();
+                let now = Instant::now();
+                for changed in group_by_project(&projects, paths) {
+                    if muted.is_muted(&changed.project_id, now) {
+                        continue;
+                    }
+                    if let Err(e) = callback_handle.emit_all(PROJECT_CHANGED_EVENT, changed) {
+                        eprintln!("Could not emit project change: {e}");
+                    }
+                }
+            }
+            Err(e) => eprintln!("Error while watching projects: {e}"),
+        }
+        if !watched_root.exists() && !stale_flag.swap(true, Ordering::SeqCst) {
+            rearm_when_recreated(
+                callback_handle.clone(),
+                watched_root.clone(),
+                Arc::clone(&stale_flag),
+                callback_shared.clone(),
+            );
+        }
+    })
+    .map_err(|e| watch_error("Could not create watcher", &root_dir, e))?;
+    debouncer
+        .watcher()
+        .watch(&root_dir, RecursiveMode::Recursive)
+        .map_err(|e| watch_error("Could not watch root dir", &root_dir, e))?;
+
+    Ok(Watch {
+        root_dir,
+        stale,
+        _debouncer: debouncer,
+    })
+}
+
+/// Wait for a removed root dir to reappear and watch it again, unless the watch `stale` belongs
+/// to was replaced or removed in the meantime. Changes made while it was missing went unseen, so
+/// every project is reported as changed.
+fn rearm_when_recreated(
+    app_handle: AppHandle,
+    root_dir: PathBuf,
+    stale: Arc<AtomicBool>,
+    shared: Shared,
+) {
+    thread::spawn(move || loop {
+        thread::sleep(REARM_INTERVAL);
+        let mut watch = shared.watch.lock().unwrap();
+        if !watch
+            .as_ref()
+            .map_or(false, |w| Arc::ptr_eq(&w.stale, &stale))
+        {
+            return;
+        }
+        if !root_dir.exists() {
+            continue;
+        }
+        match start(app_handle.clone(), root_dir.clone(), shared.clone()) {
+            Ok(rearmed) => *watch = Some(rearmed),
+            Err(e) => {
+                eprintln!("Could not watch recreated root dir: {e}");
+                continue;
+            }
+        }
+        drop(watch);
+        let projects = shared.projects.lock().unwrap();
+        for project in projects.iter() {
+            let changed = ProjectChanged {
+                project_id: project.project_id.clone(),
+                paths: vec![".".to_string()],
+            };
+            if let Err(e) = app_handle.emit_all(PROJECT_CHANGED_EVENT, changed) {
+                eprintln!("Could not emit project change: {e}");
+            }
+        }
+        return;
+    });
+}
+
+fn watch_error(message: &str, root_dir: &Path, e: notify::Error) -> CommandError {
+    match e.kind {
+        notify::ErrorKind::Io(e) => CommandError::io(message, root_dir, e),
+        notify::ErrorKind::PathNotFound => {
+            CommandError::new(ErrorKind::NotFound, message).with_path(root_dir)
+        }
+        other => {
+            CommandError::new(ErrorKind::Io, format!("{message}: {other:?}")).with_path(root_dir)
+        }
+    }
+}
+
+/// Like `Path::canonicalize`, but without the verbatim `\\?\` prefix on Windows, which the
+/// paths reported by the watcher don't carry.
+fn canonicalize(path: &Path) -> std::io::Result<PathBuf> {
+    let canonical = path.canonicalize()?;
+    #[cfg(windows)]
+    {
+        let s = canonical.to_string_lossy();
+        if let Some(stripped) = s.strip_prefix(r"\\?\") {
+            return Ok(PathBuf::from(stripped));
+        }
+    }
+    Ok(canonical)
+}
+
+/// Hidden and ignored entries are skipped together with everything below them, like in change
+/// detection. A changed `.cxignore` is reported, as it changes which files are synced.
+fn is_reported(rules: &IgnoreRules, relative: &Path, is_dir: bool) -> bool {
+    if relative == Path::new(PROJECT_RULES_FILE) {
+        return true;
+    }
+    is_visible_path(relative) && !rules.is_ignored(&to_project_path(relative), is_dir)
+}
+
+fn is_visible_path(relative: &Path) -> bool {
+    relative.components().all(|c| match c {
+        Component::Normal(name) => is_visible(&name.to_string_lossy()),
+        _ => true,
+    })
+}
+
+fn group_by_project(
+    projects: &[WatchedDir],
+    paths: impl IntoIterator<Item = PathBuf>,
+) -> Vec<ProjectChanged> {
+    let mut changes: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for path in paths {
+        for project in projects {
+            let Ok(relative) = path.strip_prefix(&project.base_path) else {
+                continue;
+            };
+            if is_reported(&project.rules, relative, path.is_dir()) {
+                changes
+                    .entry(&project.project_id)
+                    .or_default()
+                    .push(to_project_path(relative));
+            }
+        }
+    }
+    changes
+        .into_iter()
+        .map(|(project_id, mut paths)| {
+            paths.sort();
+            paths.dedup();
+            ProjectChanged {
+                project_id: project_id.to_string(),
+                paths,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::ignore::RuleSource;
+
+    #[test]
+    fn changes_are_grouped_by_project_without_hidden_files() {
+        let project = |project_id: &str, base_path: &str| WatchedDir {
+            project_id: project_id.to_string(),
+            base_path: PathBuf::from(base_path),
+            course_rules: PathBuf::new(),
+            rules: IgnoreRules::default().with(RuleSource::Default, "__pycache__/\n*.pyc\n"),
+        };
+        let projects = vec![project("a", "/root/a"), project("b", "/root/b")];
+        let paths = vec![
+            PathBuf::from("/root/a/main.py"),
+            PathBuf::from("/root/a/.idea/workspace.xml"),
+            PathBuf::from("/root/a/main.pyc"),
+            PathBuf::from("/root/a/__pycache__/main.cpython-311.pyc"),
+            PathBuf::from("/root/a/main.py"),
+            PathBuf::from("/root/b"),
+            PathBuf::from("/root/c/other.py"),
+        ];
+        let changes = group_by_project(&projects, paths);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].project_id, "a");
+        assert_eq!(changes[0].paths, vec!["./main.py"]);
+        assert_eq!(changes[1].project_id, "b");
+        assert_eq!(changes[1].paths, vec!["."]);
+    }
+
+    #[test]
+    fn paused_projects_stay_muted_until_the_grace_period_ends() {
+        let mut muted = MutedProjects::default();
+        let now = Instant::now();
+        muted.pause("a");
+        muted.pause("a");
+        muted.resume("a", now);
+        assert!(muted.is_muted("a", now + RESUME_GRACE * 10));
+        assert!(!muted.is_muted("b", now));
+
+        muted.resume("a", now);
+        assert!(muted.is_muted("a", now));
+        assert!(!muted.is_muted("a", now + RESUME_GRACE));
+        // resuming a project that was never paused changes nothing
+        muted.resume("b", now);
+        assert!(!muted.is_muted("b", now));
+    }
+}
@@ -5,7 +5,6 @@ import { relaunch } from '@tauri-apps/api/process';
 import { Store as TauriStore } from 'tauri-plugin-store-api';
 import {
   constVoid,
-  either,
   iots,
   option,
   pipe,
@@ -13,27 +12,176 @@ import {
   taskEither,
   taskOption,
 } from '@code-expert/prelude';
+import { config } from '@/config';
+import { Dir, File, FilePermissions } from '@/domain/File';
+import {
+  DiffSource,
+  FileDiff,
+  FileHash,
+  IgnoreExplanation,
+  LocalFileChange,
+  MergeReport,
+  PreserveMode,
+  PreservedFile,
+  ProjectReport,
+  SnapshotInfo,
+  SyncPlan,
+  TamperedFile,
+  TarOptions,
+  UploadOutcome,
+  UploadPolicy,
+} from '@/domain/FileState';
+import { ProjectId } from '@/domain/Project';
 import { os, path } from '@/lib/tauri';
 import { TauriException, fromTauriError } from '@/lib/tauri/TauriException';
 import { removeFile } from '@/lib/tauri/fs';
+import { OperationOptions, withOperation } from '@/lib/tauri/operation';
+import { OnProgress } from '@/lib/tauri/progress';
 import { panic } from '@/utils/error';
 
 const store = new TauriStore('settings.json');
 
+/** Keep the local content a write or removal replaces, unless it still has `syncedHash`. */
+export interface Preserve {
+  projectId: ProjectId;
+  rootDir: string;
+  syncedHash: string | null;
+  mode: PreserveMode;
+}
+
 export interface Api {
   getVersion: task.Task<string>;
   create_keys: task.Task<string>;
   create_jwt_tokens(claims: Record<string, unknown>): taskEither.TaskEither<string, string>;
-  buildTar(fileName: string, rootDir: string, files: Array<string>): task.Task<string>;
+  /**
+   * Archive the files and stream them to the API, then apply them with the signed upload request
+   * of the project, which also removes `removeFiles` on the server.
+   */
+  uploadArchive(
+    projectId: ProjectId,
+    rootDir: string,
+    files: Array<string>,
+    removeFiles: Array<string>,
+    policy: UploadPolicy,
+    options: TarOptions,
+    operation?: OperationOptions,
+  ): taskEither.TaskEither<TauriException, UploadOutcome>;
+  extractTar(
+    rootDir: string,
+    archive: { path: string } | { bytes: Array<number> },
+    tarHash: string,
+    permissions: Record<string, FilePermissions>,
+    operation?: OperationOptions,
+  ): taskEither.TaskEither<TauriException, void>;
   settingRead<T>(key: string, decoder: iots.Decoder<unknown, T>): taskOption.TaskOption<T>;
   settingWrite(key: string, value: unknown): task.Task<void>;
   writeProjectFile(
     filePath: string,
     content: string,
-    readOnly: boolean,
+    permissions: FilePermissions,
+    preserve?: Preserve,
+  ): taskEither.TaskEither<TauriException, option.Option<PreservedFile>>;
+  removeProjectFile(
+    filePath: string,
+    preserve?: Preserve,
+  ): taskEither.TaskEither<TauriException, option.Option<PreservedFile>>;
+  listPreservedFiles(
+    projectId: ProjectId,
+    rootDir: string,
+  ): taskEither.TaskEither<TauriException, Array<PreservedFile>>;
+  restorePreservedFile(
+    projectId: ProjectId,
+    rootDir: string,
+    id: string,
   ): taskEither.TaskEither<TauriException, void>;
   removeDir(filePath: string): taskEither.TaskEither<TauriException, void>;
-  getFileHash(filePath: string): taskEither.TaskEither<TauriException, string>;
+  getFileHash(
+    filePath: string,
+    onProgress?: OnProgress,
+  ): taskEither.TaskEither<TauriException, string>;
+  getFileHashes(
+    rootDir: string,
+    paths: Array<string>,
+    operation?: OperationOptions,
+  ): taskEither.TaskEither<TauriException, Record<string, FileHash>>;
+  computeSyncPlan(
+    projectId: ProjectId,
+    rootDir: string,
+    previous: option.Option<Array<File>>,
+    remote: Array<Omit<File, 'hash'>>,
+    operation?: OperationOptions,
+  ): taskEither.TaskEither<TauriException, SyncPlan>;
+  invalidateHashCache(projectId: ProjectId): taskEither.TaskEither<TauriException, void>;
+  checkReadOnlyFiles(
+    projectId: ProjectId,
+    rootDir: string,
+    files: Array<File>,
+    restore: boolean,
+  ): taskEither.TaskEither<TauriException, Array<TamperedFile>>;
+  storePristineFiles(
+    projectId: ProjectId,
+    rootDir: string,
+    files: Array<File>,
+  ): taskEither.TaskEither<TauriException, Array<string>>;
+  removePristineFiles(projectId: ProjectId): taskEither.TaskEither<TauriException, void>;
+  commitProjectHistory(
+    projectId: ProjectId,
+    rootDir: string,
+    changes: { uploaded: Array<string>; downloaded: Array<string>; deleted: Array<string> },
+  ): taskEither.TaskEither<TauriException, option.Option<string>>;
+  createSnapshot(
+    projectId: ProjectId,
+    rootDir: string,
+  ): taskEither.TaskEither<TauriException, option.Option<SnapshotInfo>>;
+  listSnapshots(projectId: ProjectId): taskEither.TaskEither<TauriException, Array<SnapshotInfo>>;
+  diffSnapshot(
+    projectId: ProjectId,
+    rootDir: string,
+    id: string,
+  ): taskEither.TaskEither<TauriException, Array<LocalFileChange>>;
+  restoreSnapshot(
+    projectId: ProjectId,
+    rootDir: string,
+    id: string,
+    paths?: Array<string>,
+  ): taskEither.TaskEither<TauriException, Array<string>>;
+  removeSnapshots(projectId: ProjectId): taskEither.TaskEither<TauriException, void>;
+  mergeConflicts(
+    rootDir: string,
+    files: Array<{ path: string; baseHash: string; remote: Array<number> }>,
+  ): taskEither.TaskEither<TauriException, Array<MergeReport>>;
+  writeCourseIgnoreRules(
+    projectId: ProjectId,
+    rules: string,
+  ): taskEither.TaskEither<TauriException, void>;
+  explainIgnored(
+    projectId: ProjectId,
+    rootDir: string,
+    path: string,
+  ): taskEither.TaskEither<TauriException, IgnoreExplanation>;
+  diffFiles(
+    rootDir: string,
+    path: string,
+    other: DiffSource,
+  ): taskEither.TaskEither<TauriException, FileDiff>;
+  verifyProject(
+    rootDir: string,
+    files: Array<File>,
+    dirs: Array<Dir>,
+  ): taskEither.TaskEither<TauriException, ProjectReport>;
+  repairProject(
+    rootDir: string,
+    files: Array<File>,
+    dirs: Array<Dir>,
+  ): taskEither.TaskEither<TauriException, ProjectReport>;
+  watchProjects(
+    rootDir: string,
+    projects: Array<{ projectId: ProjectId; basePath: string }>,
+  ): taskEither.TaskEither<TauriException, void>;
+  unwatchProjects: taskEither.TaskEither<TauriException, void>;
+  /** Keep the watcher from reporting changes of the project until `resumeWatching`. */
+  pauseWatching(projectId: ProjectId): taskEither.TaskEither<TauriException, void>;
+  resumeWatching(projectId: ProjectId): taskEither.TaskEither<TauriException, void>;
   createProjectDir(
     filePath: string,
     readOnly: boolean,
@@ -49,10 +197,33 @@ export const api: Api = {
   create_keys: () => invoke('create_keys', {}),
   create_jwt_tokens: (claims) =>
     pipe(
-      () => invoke<AlmostEither>('create_jwt_token', { claims }),
-      task.map((ae) => (ae._tag === 'Left' ? either.left(ae.value) : either.right(ae.value))),
+      taskEither.tryCatch(() => invoke<string>('create_jwt_token', { claims }), fromTauriError),
+      taskEither.mapLeft(({ message }) => message),
+    ),
+  uploadArchive: (projectId, rootDir, files, removeFiles, policy, options, operation) =>
+    withOperation(operation)((args) =>
+      taskEither.tryCatch(
+        () =>
+          invoke('upload_archive', {
+            apiUrl: config.CX_API_URL,
+            projectId,
+            rootDir,
+            files,
+            removeFiles,
+            policy,
+            options,
+            ...args,
+          }),
+        fromTauriError,
+      ),
+    ),
+  extractTar: (rootDir, archive, tarHash, permissions, operation) =>
+    withOperation(operation)((args) =>
+      taskEither.tryCatch(
+        () => invoke('extract_tar', { rootDir, archive, tarHash, permissions, ...args }),
+        fromTauriError,
+      ),
     ),
-  buildTar: (fileName, rootDir, files) => () => invoke('build_tar', { fileName, rootDir, files }),
   settingRead: (key, decoder) =>
     pipe(() => store.get(key), task.map(decoder.decode), taskOption.fromTaskEither),
   settingWrite: (key, value) => () =>
@@ -61,8 +232,97 @@ export const api: Api = {
       : store.delete(key).then(() => store.save()),
   removeDir: (filePath) =>
     taskEither.tryCatch(() => removeDir(filePath, { recursive: true }), fromTauriError),
-  getFileHash: (path) =>
-    taskEither.tryCatch(() => invoke('get_file_hash', { path }), fromTauriError),
+  getFileHash: (path, onProgress) =>
+    withOperation({ onProgress })(({ progressChannel }) =>
+      taskEither.tryCatch(() => invoke('get_file_hash', { path, progressChannel }), fromTauriError),
+    ),
+  getFileHashes: (root, paths, operation) =>
+    withOperation(operation)((args) =>
+      taskEither.tryCatch(
+        () => invoke('get_file_hashes', { root, paths, ...args }),
+        fromTauriError,
+      ),
+    ),
+  computeSyncPlan: (projectId, rootDir, previous, remote, operation) =>
+    withOperation(operation)((args) =>
+      taskEither.tryCatch(
+        () =>
+          invoke('compute_sync_plan', {
+            projectId,
+            rootDir,
+            previous: option.toNullable(previous),
+            remote,
+            ...args,
+          }),
+        fromTauriError,
+      ),
+    ),
+  invalidateHashCache: (projectId) =>
+    taskEither.tryCatch(() => invoke('invalidate_hash_cache', { projectId }), fromTauriError),
+  checkReadOnlyFiles: (projectId, rootDir, files, restore) =>
+    taskEither.tryCatch(
+      () => invoke('check_read_only_files', { projectId, rootDir, files, restore }),
+      fromTauriError,
+    ),
+  storePristineFiles: (projectId, rootDir, files) =>
+    taskEither.tryCatch(
+      () => invoke('store_pristine_files', { projectId, rootDir, files }),
+      fromTauriError,
+    ),
+  removePristineFiles: (projectId) =>
+    taskEither.tryCatch(() => invoke('remove_pristine_files', { projectId }), fromTauriError),
+  commitProjectHistory: (projectId, rootDir, changes) =>
+    pipe(
+      taskEither.tryCatch(
+        () => invoke<string | null>('commit_project_history', { projectId, rootDir, changes }),
+        fromTauriError,
+      ),
+      taskEither.map(option.fromNullable),
+    ),
+  createSnapshot: (projectId, rootDir) =>
+    pipe(
+      taskEither.tryCatch(
+        () => invoke<SnapshotInfo | null>('create_snapshot', { projectId, rootDir }),
+        fromTauriError,
+      ),
+      taskEither.map(option.fromNullable),
+    ),
+  listSnapshots: (projectId) =>
+    taskEither.tryCatch(() => invoke('list_snapshots', { projectId }), fromTauriError),
+  diffSnapshot: (projectId, rootDir, id) =>
+    taskEither.tryCatch(() => invoke('diff_snapshot', { projectId, rootDir, id }), fromTauriError),
+  restoreSnapshot: (projectId, rootDir, id, paths) =>
+    taskEither.tryCatch(
+      () => invoke('restore_snapshot', { projectId, rootDir, id, paths }),
+      fromTauriError,
+    ),
+  removeSnapshots: (projectId) =>
+    taskEither.tryCatch(() => invoke('remove_snapshots', { projectId }), fromTauriError),
+  mergeConflicts: (rootDir, files) =>
+    taskEither.tryCatch(() => invoke('merge_conflicts', { rootDir, files }), fromTauriError),
+  writeCourseIgnoreRules: (projectId, rules) =>
+    taskEither.tryCatch(
+      () => invoke('write_course_ignore_rules', { projectId, rules }),
+      fromTauriError,
+    ),
+  explainIgnored: (projectId, rootDir, path) =>
+    taskEither.tryCatch(
+      () => invoke('explain_ignored', { projectId, rootDir, path }),
+      fromTauriError,
+    ),
+  diffFiles: (rootDir, path, other) =>
+    taskEither.tryCatch(() => invoke('diff_files', { rootDir, path, other }), fromTauriError),
+  verifyProject: (rootDir, files, dirs) =>
+    taskEither.tryCatch(() => invoke('verify_project', { rootDir, files, dirs }), fromTauriError),
+  repairProject: (rootDir, files, dirs) =>
+    taskEither.tryCatch(() => invoke('repair_project', { rootDir, files, dirs }), fromTauriError),
+  watchProjects: (rootDir, projects) =>
+    taskEither.tryCatch(() => invoke('watch_projects', { rootDir, projects }), fromTauriError),
+  unwatchProjects: taskEither.tryCatch(() => invoke('unwatch_projects'), fromTauriError),
+  pauseWatching: (projectId) =>
+    taskEither.tryCatch(() => invoke('pause_watching', { projectId }), fromTauriError),
+  resumeWatching: (projectId) =>
+    taskEither.tryCatch(() => invoke('resume_watching', { projectId }), fromTauriError),
   createProjectPath: (path) =>
     pipe(
       api.settingRead('projectDir', iots.string),
@@ -73,14 +333,36 @@ export const api: Api = {
     ),
   createProjectDir: (path, readOnly) =>
     taskEither.tryCatch(() => invoke('create_project_dir', { path, readOnly }), fromTauriError),
-  writeProjectFile: (filePath, content, readOnly) =>
+  writeProjectFile: (filePath, content, permissions, preserve) =>
+    pipe(
+      taskEither.tryCatch(
+        () =>
+          invoke<PreservedFile | null>('write_file', {
+            path: filePath,
+            contents: Array.from(new TextEncoder().encode(content)),
+            permissions,
+            preserve,
+          }),
+        fromTauriError,
+      ),
+      taskEither.map(option.fromNullable),
+    ),
+  removeProjectFile: (filePath, preserve) =>
+    pipe(
+      taskEither.tryCatch(
+        () => invoke<PreservedFile | null>('remove_file', { path: filePath, preserve }),
+        fromTauriError,
+      ),
+      taskEither.map(option.fromNullable),
+    ),
+  listPreservedFiles: (projectId, rootDir) =>
+    taskEither.tryCatch(
+      () => invoke('list_preserved_files', { projectId, rootDir }),
+      fromTauriError,
+    ),
+  restorePreservedFile: (projectId, rootDir, id) =>
     taskEither.tryCatch(
-      () =>
-        invoke('write_file', {
-          path: filePath,
-          contents: Array.from(new TextEncoder().encode(content)),
-          readOnly,
-        }),
+      () => invoke('restore_preserved_file', { projectId, rootDir, id }),
       fromTauriError,
     ),
   logout: () =>
@@ -95,8 +377,3 @@ export const api: Api = {
   getSystemInfo: async () => option.fromNullable<string>(await invoke('system_info')),
   restart: () => relaunch(),
 };
-
-interface AlmostEither {
-  _tag: 'Left' | 'Right';
-  value: string;
-}
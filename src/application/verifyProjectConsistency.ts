@@ -1,18 +1,48 @@
-import { boolean, constVoid, pipe, taskEither } from '@code-expert/prelude';
+import { api } from 'api';
+import { iots, pipe, taskEither } from '@code-expert/prelude';
+import { ProjectReport } from '@/domain/FileState';
 import { LocalProject } from '@/domain/Project';
-import { SyncException, syncExceptionADT } from '@/domain/SyncException';
-import { exists } from '@/lib/tauri/fs';
+import { SyncException, fromTauriException, syncExceptionADT } from '@/domain/SyncException';
+import { path } from '@/lib/tauri';
 
-export const verifyProjectConsistency = ({
+const getProjectDir = ({
   value: { basePath },
-}: LocalProject): taskEither.TaskEither<SyncException, void> =>
+}: LocalProject): taskEither.TaskEither<SyncException, string> =>
   pipe(
-    taskEither.fromTask(exists(basePath)),
-    taskEither.filterOrElse(boolean.isTrue, () =>
-      syncExceptionADT.fileSystemCorrupted({
-        path: basePath,
-        reason: 'Project directory does not exist.',
-      }),
+    api.settingRead('projectDir', iots.string),
+    taskEither.fromTaskOption(() => syncExceptionADT.projectDirMissing()),
+    taskEither.chainTaskK((rootDir) => path.join(rootDir, basePath)),
+  );
+
+/**
+ * Compare the project directory against the files and directories recorded after the last sync.
+ */
+export const verifyProjectConsistency = (
+  project: LocalProject,
+): taskEither.TaskEither<SyncException, ProjectReport> =>
+  pipe(
+    getProjectDir(project),
+    taskEither.chain((projectDir) =>
+      pipe(
+        api.verifyProject(projectDir, project.value.files, project.value.dirs),
+        taskEither.mapLeft(fromTauriException(projectDir)),
+      ),
+    ),
+  );
+
+/**
+ * Recreate missing directories and reapply the recorded permissions, then report what is still
+ * different.
+ */
+export const repairProjectConsistency = (
+  project: LocalProject,
+): taskEither.TaskEither<SyncException, ProjectReport> =>
+  pipe(
+    getProjectDir(project),
+    taskEither.chain((projectDir) =>
+      pipe(
+        api.repairProject(projectDir, project.value.files, project.value.dirs),
+        taskEither.mapLeft(fromTauriException(projectDir)),
+      ),
     ),
-    taskEither.map(constVoid),
   );
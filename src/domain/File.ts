@@ -10,9 +10,12 @@ import {
   tree,
 } from '@code-expert/prelude';
 
-export const FilePermissionsC = iots.keyof({ r: null, rw: null });
+export const FilePermissionsC = iots.keyof({ r: null, rw: null, rx: null, rwx: null });
 export type FilePermissions = iots.TypeOf<typeof FilePermissionsC>;
 
+export const isWritable = (permissions: FilePermissions) =>
+  permissions === 'rw' || permissions === 'rwx';
+
 export const FileEntryTypeC = iots.keyof({ file: null, dir: null });
 export type FileEntryType = iots.TypeOf<typeof FileEntryTypeC>;
 
@@ -31,6 +34,13 @@ export const FileC = iots.strict({
 
 export type File = iots.TypeOf<typeof FileC>;
 
+export const DirC = iots.strict({
+  path: iots.string,
+  permissions: FilePermissionsC,
+});
+
+export type Dir = iots.TypeOf<typeof DirC>;
+
 // -------------------------------------------------------------------------------------------------
 
 export const isFile = <A extends { type: FileEntryType }>(a: A): a is A & { type: 'file' } =>
@@ -1,101 +1,5 @@
-import {
-  array,
-  eq,
-  monoid,
-  nonEmptyArray,
-  option,
-  pipe,
-  string,
-  tagged,
-} from '@code-expert/prelude';
-import { FileEntryType, File as FileInfo, isFile } from '@/domain/File';
-
-export interface RemoteFileState {
-  path: string;
-  type: FileEntryType;
-  version: number;
-}
-
-export const getRemoteChanges = (
-  previous: Array<RemoteFileState>,
-  latest: Array<RemoteFileState>,
-): option.Option<NonEmptyArray<RemoteFileChange>> => {
-  const previousFiles = pipe(previous, array.filter(isFile));
-  const latestFiles = pipe(latest, array.filter(isFile));
-  const removed: Array<RemoteFileChange> = pipe(
-    previousFiles,
-    array.difference<RemoteFileState>(eqPath)(latestFiles),
-    array.map(({ path }) => ({ path, change: remoteFileChange.removed() })),
-  );
-  const added: Array<RemoteFileChange> = pipe(
-    latestFiles,
-    array.difference<RemoteFileState>(eqPath)(previousFiles),
-    array.map(({ path, version }) => ({ path, change: remoteFileChange.added(version) })),
-  );
-  const updated: Array<RemoteFileChange> = pipe(
-    previousFiles,
-    array.filter((ls) =>
-      pipe(
-        latestFiles,
-        array.findFirst((cs) => cs.path === ls.path),
-        option.exists((cs) => cs.version !== ls.version),
-      ),
-    ),
-    array.map(({ path, version }) => ({ path, change: remoteFileChange.updated(version) })),
-  );
-  return pipe(
-    [removed, added, updated],
-    monoid.concatAll(array.getMonoid()),
-    nonEmptyArray.fromArray,
-  );
-};
-
-export interface LocalFileState {
-  path: string;
-  type: FileEntryType;
-  hash: string;
-}
-
-export const getLocalChanges = (
-  previous: Array<FileInfo>,
-  latest: Array<LocalFileState>,
-): option.Option<NonEmptyArray<LocalFileChange>> => {
-  const previousFiles = pipe(previous, array.filter(isFile));
-  const latestFiles = pipe(latest, array.filter(isFile));
-  const removed: Array<LocalFileChange> = pipe(
-    previousFiles,
-    array.difference<LocalFileState>(eqPath)(latestFiles),
-    array.map(({ path }) => ({ path, change: localFileChange.removed() })),
-  );
-  const added: Array<LocalFileChange> = pipe(
-    latestFiles,
-    array.difference<LocalFileState>(eqPath)(previousFiles),
-    array.map(({ path }) => ({ path, change: localFileChange.added() })),
-  );
-  const updated: Array<LocalFileChange> = pipe(
-    previousFiles,
-    array.bindTo('previous'),
-    array.bind('latest', ({ previous }) =>
-      pipe(
-        latestFiles,
-        array.findFirst((latest) => eqPath.equals(previous, latest)),
-        option.fold(() => [], array.of),
-      ),
-    ),
-    array.filter(({ latest, previous }) => latest.hash !== previous.hash),
-    array.map(({ latest: { path } }) => ({
-      path,
-      change: localFileChange.updated(),
-    })),
-  );
-  return pipe(
-    [removed, added, updated],
-    monoid.concatAll(array.getMonoid()),
-    nonEmptyArray.fromArray,
-  );
-};
-
-// -------------------------------------------------------------------------------------------------
+import { tagged } from '@code-expert/prelude';
+import { CommandError } from '@/lib/tauri/TauriException';
 
 export interface RemoteFileChange {
   path: string;
@@ -125,29 +29,203 @@ export interface Conflict {
   changeLocal: LocalFileChange['change'];
 }
 
-export const getConflicts = (
-  local: NonEmptyArray<LocalFileChange>,
-  remote: NonEmptyArray<RemoteFileChange>,
-): option.Option<NonEmptyArray<Conflict>> => {
-  const conflicts: Array<Conflict> = pipe(
-    local,
-    array.intersection<LocalFileChange>(eqPath)(remote),
-    array.map((changeLocal) => ({
-      path: changeLocal.path,
-      changeLocal: changeLocal.change,
-      changeRemote: pipe(
-        remote,
-        array.findFirst((changeRemote) => eqPath.equals(changeLocal, changeRemote)),
-        option.map(({ change }) => change),
-        option.getOrElseW(() => remoteFileChange.noChange()),
-      ),
-    })),
-  );
-  return pipe(conflicts, nonEmptyArray.fromArray);
-};
-
-// -------------------------------------------------------------------------------------------------
-
-const eqPath = eq.struct({
-  path: string.Eq,
-});
+/**
+ * A read-only file that no longer matches the last sync (see `check_read_only_files`). `restored`
+ * is set if it was rewritten from its pristine copy or made read-only again.
+ */
+export interface TamperedFile {
+  path: string;
+  tampering: 'modified' | 'removed' | 'writable';
+  restored: boolean;
+}
+
+export interface MergeReport {
+  path: string;
+  outcome:
+    | tagged.Tagged<'merged'>
+    | tagged.Tagged<'conflicting', { regions: number; text: string }>
+    | tagged.Tagged<'unmergeable', string>;
+}
+
+export const mergeOutcome = tagged.build<MergeReport['outcome']>();
+
+export interface WordSegment {
+  text: string;
+  changed: boolean;
+}
+
+export interface DiffLine {
+  kind: 'context' | 'removed' | 'added';
+  text: string;
+  /** Set on changed lines paired with a counterpart, to highlight the changed words. */
+  words: Array<WordSegment> | null;
+}
+
+/** A hunk of a unified diff (see `diff_files`), with 1-based line numbers. */
+export interface DiffHunk {
+  oldStart: number;
+  oldLines: number;
+  newStart: number;
+  newLines: number;
+  lines: Array<DiffLine>;
+}
+
+export type FileDiff =
+  | tagged.Tagged<'identical'>
+  | tagged.Tagged<'text', { hunks: Array<DiffHunk>; truncated: boolean }>
+  | tagged.Tagged<'binary'>
+  | tagged.Tagged<'tooLarge', number>;
+
+export const fileDiff = tagged.build<FileDiff>();
+
+/** What a local file is diffed against: remote content or the pristine copy with a given hash. */
+export type DiffSource = tagged.Tagged<'remote', string> | tagged.Tagged<'pristine', string>;
+
+export const diffSource = tagged.build<DiffSource>();
+
+/** Result of `get_file_hashes` for one file: its hash, or why it could not be hashed. */
+export type FileHash = tagged.Tagged<'hashed', string> | tagged.Tagged<'failed', CommandError>;
+
+export const fileHash = tagged.build<FileHash>();
+
+/** A rule of the defaults, the course or the `.cxignore` of a project, and the path it matched. */
+export interface MatchedIgnoreRule {
+  path: string;
+  rule: { source: 'default' | 'course' | 'project'; line: number; pattern: string };
+}
+
+/**
+ * Why a path is not synced (see `explain_ignored`): it or a parent directory is hidden or ignored
+ * by a rule. `reincluded` names the `!` rule including a path excluded by an earlier one.
+ */
+export type IgnoreExplanation =
+  | tagged.Tagged<'included'>
+  | tagged.Tagged<'hidden', string>
+  | tagged.Tagged<'ignored', MatchedIgnoreRule>
+  | tagged.Tagged<'reincluded', MatchedIgnoreRule>;
+
+export const ignoreExplanation = tagged.build<IgnoreExplanation>();
+
+/** Binary content recognized by its leading bytes (see `FileKind` in `upload_policy.rs`). */
+export type BinaryFileKind =
+  | 'elf'
+  | 'pe'
+  | 'machO'
+  | 'javaClass'
+  | 'wasm'
+  | 'zip'
+  | 'gzip'
+  | 'sevenZip'
+  | 'rar'
+  | 'sqlite'
+  | 'pdf'
+  | 'png'
+  | 'jpeg'
+  | 'gif';
+
+/** Limits of a single upload in bytes and files. Limits left out don't apply. */
+export interface UploadPolicy {
+  maxFileSize?: number;
+  maxTotalSize?: number;
+  maxFileCount?: number;
+  deny?: Array<BinaryFileKind>;
+}
+
+export type UploadViolation =
+  | tagged.Tagged<'fileTooLarge', { path: string; size: number; limit: number }>
+  | tagged.Tagged<'totalSizeExceeded', { size: number; limit: number }>
+  | tagged.Tagged<'tooManyFiles', { count: number; limit: number }>
+  | tagged.Tagged<'deniedContent', { path: string; kind: BinaryFileKind }>;
+
+export const uploadViolation = tagged.build<UploadViolation>();
+
+export interface UploadReport {
+  fileCount: number;
+  totalSize: number;
+  violations: Array<UploadViolation>;
+}
+
+export type Codec = 'brotli' | 'gzip' | 'zstd' | 'none';
+
+/**
+ * How `build_tar` writes archives. A deterministic archive has sorted entries and normalized
+ * metadata, so equal content yields an equal hash on every machine. Without a codec, it is chosen
+ * by the size of the files; without a level, the codec's balanced one is used.
+ */
+export interface TarOptions {
+  deterministic?: boolean;
+  codec?: Codec;
+  level?: number | 'fast' | 'balanced' | 'max';
+}
+
+/** An archive written by `build_tar`, to be uploaded with the given `Content-Encoding`. */
+export interface ArchiveInfo {
+  tarHash: string;
+  codec: Codec;
+  level: number;
+  encoding: string | null;
+  /** Requested files left out, as they are ignored or lie in the project's `.git` directory */
+  ignored: Array<string>;
+}
+
+/** The answer of the server to an archive sent by `upload_archive`. */
+export interface UploadResponse {
+  /** `null` if there were no files to upload */
+  archive: ArchiveInfo | null;
+  status: number;
+  body: unknown;
+}
+
+/** Result of `upload_archive`: the answer of the server, or why nothing was sent. */
+export type UploadOutcome =
+  | tagged.Tagged<'uploaded', UploadResponse>
+  | tagged.Tagged<'rejected', UploadReport>;
+
+export const uploadOutcome = tagged.build<UploadOutcome>();
+
+/** A snapshot of a project directory (see `create_snapshot`). */
+export interface SnapshotInfo {
+  id: string;
+  createdAt: string;
+  fileCount: number;
+}
+
+/**
+ * Where local edits replaced by a forced pull are kept: a `.conflict-{timestamp}` copy next to the
+ * file, or the app-local trash.
+ */
+export type PreserveMode = 'conflictCopy' | 'trash';
+
+/** A local version kept by `write_file` or `remove_file`, restorable by its `id`. */
+export interface PreservedFile {
+  id: string;
+  path: string;
+  mode: PreserveMode;
+  /** Project path of the conflict copy, or the name of the file in the trash. */
+  copy: string;
+  preservedAt: string;
+}
+
+/**
+ * Differences between a project directory and its manifest (see `verify_project`), as lists of
+ * project paths.
+ */
+export interface ProjectReport {
+  missing: Array<string>;
+  extra: Array<string>;
+  hashMismatches: Array<string>;
+  wrongPermissions: Array<string>;
+  writableDirs: Array<string>;
+  symlinks: Array<string>;
+}
+
+/**
+ * Result of the native change detection (see `compute_sync_plan`): local changes relative to the
+ * previously synced state, remote changes relative to the same state, and the paths changed on both
+ * sides.
+ */
+export interface SyncPlan {
+  local: Array<LocalFileChange>;
+  remote: Array<RemoteFileChange>;
+  conflicts: Array<Conflict>;
+}
@@ -1,4 +1,4 @@
-import { File } from '@/domain/File';
+import { Dir, File } from '@/domain/File';
 import { ProjectId } from '@/domain/Project';
 import { SyncState } from '@/domain/SyncState';
 
@@ -6,6 +6,7 @@ export interface ProjectFiles {
   projectId: ProjectId;
   basePath: string;
   files: Array<File>;
+  dirs: Array<Dir>;
   syncedAt: Date;
   syncState: SyncState;
 }
@@ -1,18 +1,53 @@
 import { tagged } from '@code-expert/prelude';
+import { MergeReport, TamperedFile, UploadViolation } from '@/domain/FileState';
+import { TauriException } from '@/lib/tauri/TauriException';
 import { apiError } from '@/utils/api';
 import { panic } from '@/utils/error';
 
 export type SyncException =
-  | tagged.Tagged<'conflictingChanges'>
+  | tagged.Tagged<'conflictingChanges', Array<MergeReport>>
   | tagged.Tagged<'readOnlyFilesChanged', { path: string; reason: string }>
+  | tagged.Tagged<'readOnlyFilesModified', Array<TamperedFile>>
   | tagged.Tagged<'invalidFilename', string>
-  // | tagged.Tagged<'fileSizeExceeded'>
+  | tagged.Tagged<'uploadRejected', Array<UploadViolation>>
   | tagged.Tagged<'fileSystemCorrupted', { path: string; reason: string }>
   | tagged.Tagged<'projectDirMissing'>
-  | tagged.Tagged<'networkError', { reason: string }>;
+  | tagged.Tagged<'networkError', { reason: string }>
+  | tagged.Tagged<'cancelled'>;
 
 export const syncExceptionADT = tagged.build<SyncException>();
 
+/** Paths with `/` and without leading `./`, so that reported and manifest paths compare equal. */
+const normalizePath = (path: string) => path.replace(/\\/g, '/').replace(/^(\.\/)+/, '');
+
+/**
+ * Map a command error to the sync exception shown to the user. Denied access to one of the
+ * `readOnlyFiles` of the project in `projectDir` means a file made read-only by the sync was
+ * tampered with, a failed upload is a network error, and everything else, including denied access
+ * to other files, leaves the project in an unknown state. Errors are attributed to `projectDir`
+ * unless the command reports a path.
+ */
+export const fromTauriException =
+  (projectDir: string, readOnlyFiles: ReadonlyArray<string> = []) =>
+  ({ message: reason, kind, path = projectDir }: TauriException): SyncException => {
+    switch (kind) {
+      case 'permission': {
+        const dir = `${normalizePath(projectDir).replace(/\/$/, '')}/`;
+        const file = normalizePath(path);
+        const projectPath = file.startsWith(dir) ? file.slice(dir.length) : file;
+        return readOnlyFiles.map(normalizePath).includes(projectPath)
+          ? syncExceptionADT.readOnlyFilesChanged({ path, reason })
+          : syncExceptionADT.fileSystemCorrupted({ path, reason });
+      }
+      case 'cancelled':
+        return syncExceptionADT.cancelled();
+      case 'network':
+        return syncExceptionADT.networkError({ reason });
+      default:
+        return syncExceptionADT.fileSystemCorrupted({ path, reason });
+    }
+  };
+
 export const fromHttpError = apiError.fold({
   notReady: panic,
   noNetwork: () => syncExceptionADT.wide.networkError({ reason: 'Could not connect to server' }),
@@ -142,6 +142,21 @@ export const mkProjectRepositoryTauri = (): task.Task<ProjectRepository> => {
           taskEither.fromTask,
         );
 
+        const removeHashCache: taskEither.TaskEither<Array<string>, void> = pipe(
+          api.invalidateHashCache(projectId),
+          taskEither.mapLeft((e) => [e.message]),
+        );
+
+        const removePristineFiles: taskEither.TaskEither<Array<string>, void> = pipe(
+          api.removePristineFiles(projectId),
+          taskEither.mapLeft((e) => [e.message]),
+        );
+
+        const removeSnapshots: taskEither.TaskEither<Array<string>, void> = pipe(
+          api.removeSnapshots(projectId),
+          taskEither.mapLeft((e) => [e.message]),
+        );
+
         const removeFromDb: taskEither.TaskEither<Array<string>, void> = pipe(
           () => projectsDb.modify(flow(array.filter(({ value }) => value.projectId !== projectId))),
           taskEither.fromIO,
@@ -160,6 +175,9 @@ export const mkProjectRepositoryTauri = (): task.Task<ProjectRepository> => {
             removeProjectAccess,
             removeMetadata,
             removeConfig,
+            removeHashCache,
+            removePristineFiles,
+            removeSnapshots,
             removeFromDb,
           ]),
           taskEither.match(logDebugErrors, constVoid),
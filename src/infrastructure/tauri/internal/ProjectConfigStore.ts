@@ -1,11 +1,13 @@
 import { BaseDirectory, readTextFile, removeFile, writeTextFile } from '@tauri-apps/api/fs';
 import { flow, iots, option, pipe, task, taskOption } from '@code-expert/prelude';
-import { FileC } from '@/domain/File';
+import { DirC, FileC } from '@/domain/File';
 import { LocalProject, ProjectId } from '@/domain/Project';
 
 const ProjectConfigC = iots.strict({
   basePath: iots.string,
   files: iots.array(FileC),
+  // not recorded by versions before directory permissions were verified
+  dirs: iots.withFallback(iots.array(DirC), []),
   syncedAt: iots.DateFromISOString,
 });
 
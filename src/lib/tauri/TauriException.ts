@@ -1,13 +1,47 @@
 import { fromThrown } from '@/utils/error';
 
+/** Mirrors `ErrorKind` in `src-tauri/src/commands/error.rs` */
+export type CommandErrorKind =
+  | 'io'
+  | 'permission'
+  | 'notFound'
+  | 'invalidKey'
+  | 'invalidInput'
+  | 'archive'
+  | 'pathEscape'
+  | 'cancelled'
+  | 'network';
+
+/** The error every Rust command rejects with, see `src-tauri/src/commands/error.rs` */
+export interface CommandError {
+  kind: CommandErrorKind;
+  message: string;
+  path: string | null;
+  code: number | null;
+}
+
 export class TauriException extends Error {
   name = 'TauriException' as const;
+  /** Error kind of a command error, missing for errors of Tauri's own APIs */
+  kind?: CommandErrorKind;
+  /** The path a command error refers to */
+  path?: string;
+  /** OS error code of the I/O error behind a command error */
+  code?: number;
 }
 
+const isCommandError = (e: unknown): e is CommandError =>
+  typeof e === 'object' && e != null && 'kind' in e && 'message' in e;
+
 export const fromTauriError = (e: unknown): TauriException => {
   const error = fromThrown(e);
   const exception = new TauriException(error.message);
   exception.stack = error.stack;
   exception.cause = error.cause;
+  if (isCommandError(e)) {
+    exception.kind = e.kind;
+    exception.path = e.path ?? undefined;
+    exception.code = e.code ?? undefined;
+  }
   return exception;
 };
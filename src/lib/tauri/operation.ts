@@ -0,0 +1,44 @@
+import { invoke } from '@tauri-apps/api';
+import { listen } from '@tauri-apps/api/event';
+import { nanoid } from 'nanoid/non-secure';
+import { constVoid, task } from '@code-expert/prelude';
+import { OnProgress, Progress } from '@/lib/tauri/progress';
+
+export interface OperationOptions {
+  onProgress?: OnProgress;
+  /** Aborting cancels the operation through `cancel_operation` */
+  signal?: AbortSignal;
+}
+
+/** Arguments to pass on to a long-running command */
+export interface OperationArgs {
+  progressChannel: string | null;
+  operationId: string | null;
+}
+
+/**
+ * Run a long-running command under a fresh operation id. Progress events are forwarded to
+ * `onProgress` and aborting `signal` cancels the command until it completes. Without either,
+ * the command runs as a plain invocation.
+ */
+export const withOperation =
+  ({ onProgress, signal }: OperationOptions = {}) =>
+  <A>(run: (args: OperationArgs) => task.Task<A>): task.Task<A> =>
+  async () => {
+    const id = nanoid();
+    const progressChannel = onProgress != null ? `progress:${id}` : null;
+    const unlisten =
+      progressChannel != null
+        ? await listen<Progress>(progressChannel, ({ payload }) => onProgress?.(payload))
+        : constVoid;
+    const cancel = () => void invoke('cancel_operation', { id });
+    signal?.addEventListener('abort', cancel);
+    // the command starts out cancelled if the signal was aborted before
+    if (signal?.aborted === true) cancel();
+    try {
+      return await run({ progressChannel, operationId: signal != null ? id : null })();
+    } finally {
+      signal?.removeEventListener('abort', cancel);
+      unlisten();
+    }
+  };
@@ -0,0 +1,9 @@
+/** Mirrors `Progress` in `src-tauri/src/utils/progress.rs` */
+export interface Progress {
+  bytes: number;
+  total: number;
+  currentFile: string | null;
+}
+
+export type OnProgress = (progress: Progress) => void;
+
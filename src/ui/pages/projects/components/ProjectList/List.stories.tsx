@@ -1,10 +1,12 @@
 import { Meta, StoryObj } from '@storybook/react';
 import { nonEmptyArray } from '@code-expert/prelude';
 import {
+  listPreservedFiles,
   localProject,
   openProject,
   remoteProject,
   removeProject,
+  restorePreservedFile,
   syncProject,
 } from '@/ui/pages/projects/components/ProjectList/testData';
 import { List } from './List';
@@ -18,6 +20,8 @@ const meta = {
     onOpen: openProject,
     onSync: syncProject,
     onRemove: removeProject,
+    onListPreserved: listPreservedFiles,
+    onRestorePreserved: restorePreservedFile,
   },
 } satisfies Meta<typeof List>;
 
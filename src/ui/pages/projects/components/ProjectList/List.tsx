@@ -1,11 +1,12 @@
 import { List as AntList, Card, Typography } from 'antd';
 import React from 'react';
 import { array, pipe, task, taskEither } from '@code-expert/prelude';
+import { PreservedFile } from '@/domain/FileState';
 import { Project, ProjectId, ordProjectTask } from '@/domain/Project';
 import { SyncException } from '@/domain/SyncException';
 import { VStack } from '@/ui/foundation/Layout';
 import { styled } from '@/ui/foundation/Theme';
-import { ForceSyncDirection } from '@/ui/pages/projects/hooks/useProjectSync';
+import { SyncOptions } from '@/ui/pages/projects/hooks/useProjectSync';
 import { ListItem } from './ListItem';
 
 const StyledCard = styled(Card, ({ tokens }) => ({
@@ -19,11 +20,21 @@ export interface ListProps {
   exerciseName: string;
   projects: Array<Project>;
   onOpen(id: ProjectId): taskEither.TaskEither<string, void>;
-  onSync(id: ProjectId, force?: ForceSyncDirection): taskEither.TaskEither<SyncException, void>;
+  onSync(id: ProjectId, options?: SyncOptions): taskEither.TaskEither<SyncException, void>;
   onRemove(id: ProjectId): task.Task<void>;
+  onListPreserved(id: ProjectId): taskEither.TaskEither<string, Array<PreservedFile>>;
+  onRestorePreserved(id: ProjectId, preservedId: string): taskEither.TaskEither<string, void>;
 }
 
-export const List = ({ exerciseName, projects, onOpen, onSync, onRemove }: ListProps) => (
+export const List = ({
+  exerciseName,
+  projects,
+  onOpen,
+  onSync,
+  onRemove,
+  onListPreserved,
+  onRestorePreserved,
+}: ListProps) => (
   <VStack gap={'xs'}>
     <Typography.Text strong>{exerciseName}</Typography.Text>
     <StyledCard size="small">
@@ -31,7 +42,14 @@ export const List = ({ exerciseName, projects, onOpen, onSync, onRemove }: ListP
         size="small"
         dataSource={pipe(projects, array.sort(ordProjectTask))}
         renderItem={(project) => (
-          <ListItem project={project} onOpen={onOpen} onSync={onSync} onRemove={onRemove} />
+          <ListItem
+            project={project}
+            onOpen={onOpen}
+            onSync={onSync}
+            onRemove={onRemove}
+            onListPreserved={onListPreserved}
+            onRestorePreserved={onRestorePreserved}
+          />
         )}
         locale={{
           emptyText: 'No projects',
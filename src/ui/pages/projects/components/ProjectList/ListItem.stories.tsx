@@ -4,9 +4,11 @@ import React from 'react';
 import { either, flow, taskEither } from '@code-expert/prelude';
 import { syncExceptionADT } from '@/domain/SyncException';
 import {
+  listPreservedFiles,
   localProject,
   openProject,
   removeProject,
+  restorePreservedFile,
   syncProject,
 } from '@/ui/pages/projects/components/ProjectList/testData';
 import { ListItem } from './ListItem';
@@ -19,6 +21,8 @@ const meta = {
     onOpen: openProject,
     onSync: syncProject,
     onRemove: removeProject,
+    onListPreserved: listPreservedFiles,
+    onRestorePreserved: restorePreservedFile,
   },
   render: (props) => (
     <List size="small">
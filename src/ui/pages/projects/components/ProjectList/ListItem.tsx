@@ -1,9 +1,17 @@
 import { Alert, Button, Collapse, List, Typography } from 'antd';
 import React from 'react';
-import { constNull, remoteEither, task, taskEither } from '@code-expert/prelude';
+import { constNull, pipe, remoteEither, task, taskEither } from '@code-expert/prelude';
 import { invalidFileNameMessage } from '@/domain/File';
+import {
+  MergeReport,
+  PreservedFile,
+  UploadViolation,
+  mergeOutcome,
+  uploadViolation,
+} from '@/domain/FileState';
 import { Project, ProjectId, projectADT } from '@/domain/Project';
 import { SyncException, syncExceptionADT } from '@/domain/SyncException';
+import { Progress } from '@/lib/tauri/progress';
 import { ActionMenu } from '@/ui/components/ActionMenu';
 import { GuardRemoteEither } from '@/ui/components/GuardRemoteData';
 import { useTimeContext } from '@/ui/contexts/TimeContext';
@@ -12,8 +20,9 @@ import { HStack, VStack } from '@/ui/foundation/Layout';
 import { styled } from '@/ui/foundation/Theme';
 import { useTask } from '@/ui/hooks/useTask';
 import { fromProject } from '@/ui/pages/projects/components/ProjectList/model/SyncButtonState';
-import { ForceSyncDirection } from '@/ui/pages/projects/hooks/useProjectSync';
+import { SyncOptions } from '@/ui/pages/projects/hooks/useProjectSync';
 import { routes, useRoute } from '@/ui/routes';
+import { PreservedFileList } from './PreservedFileList';
 import { SyncButton } from './SyncButton';
 
 const StyledListItem = styled(List.Item, () => ({
@@ -46,25 +55,58 @@ const StyledButton = styled(Button, ({ tokens }) => ({
 export interface ListItemProps {
   project: Project;
   onOpen(id: ProjectId): taskEither.TaskEither<string, void>;
-  onSync(id: ProjectId, force?: ForceSyncDirection): taskEither.TaskEither<SyncException, void>;
+  onSync(id: ProjectId, options?: SyncOptions): taskEither.TaskEither<SyncException, void>;
   onRemove(id: ProjectId): task.Task<void>;
+  onListPreserved(id: ProjectId): taskEither.TaskEither<string, Array<PreservedFile>>;
+  onRestorePreserved(id: ProjectId, preservedId: string): taskEither.TaskEither<string, void>;
 }
 
-export const ListItem = ({ project, onOpen, onSync, onRemove }: ListItemProps) => {
+export const ListItem = ({
+  project,
+  onOpen,
+  onSync,
+  onRemove,
+  onListPreserved,
+  onRestorePreserved,
+}: ListItemProps) => {
   const { now } = useTimeContext();
   const { navigateTo } = useRoute();
 
   const [openStateRD, runOpen] = useTask(onOpen);
   const [syncStateRD, runSync] = useTask(onSync);
   const [removalStateRD, runRemove] = useTask(onRemove);
+  // restores the given preserved file first, if any
+  const [preservedRD, runPreserved] = useTask((preservedId?: string) =>
+    pipe(
+      preservedId == null
+        ? taskEither.of<string, void>(undefined)
+        : onRestorePreserved(project.value.projectId, preservedId),
+      taskEither.chain(() => onListPreserved(project.value.projectId)),
+    ),
+  );
+  const [showPreserved, setShowPreserved] = React.useState(false);
+  const [progress, setProgress] = React.useState<Progress>();
+  const syncAbort = React.useRef<AbortController>();
+
+  const sync = (options?: Pick<SyncOptions, 'force' | 'restoreReadOnly'>) => {
+    const abortController = new AbortController();
+    syncAbort.current = abortController;
+    setProgress(undefined);
+    runSync(project.value.projectId, {
+      ...options,
+      onProgress: setProgress,
+      signal: abortController.signal,
+    });
+  };
 
   // All states combined. Order matters: the first failure gets precedence.
   const actionStates = remoteEither.sequenceT(
     viewFromStringException(openStateRD),
     viewFromSyncException({
       choseProjectDir: () => navigateTo(routes.settings()),
-      forcePush: () => runSync(project.value.projectId, 'push'),
-      forcePull: () => runSync(project.value.projectId, 'pull'),
+      forcePush: () => sync({ force: 'push' }),
+      forcePull: () => sync({ force: 'pull' }),
+      restoreReadOnly: () => sync({ restoreReadOnly: true }),
     })(syncStateRD),
   );
 
@@ -86,7 +128,8 @@ export const ListItem = ({ project, onOpen, onSync, onRemove }: ListItemProps) =
             <SyncButton
               now={now}
               state={syncButtonState}
-              onClick={() => runSync(project.value.projectId)}
+              progress={progress}
+              onClick={() => sync()}
             />
             <ActionMenu
               label={'Actions'}
@@ -99,11 +142,28 @@ export const ListItem = ({ project, onOpen, onSync, onRemove }: ListItemProps) =
                     icon: <Icon name="folder-open-regular" />,
                     onClick: () => runOpen(project.value.projectId),
                   },
+                  remoteEither.isPending(syncStateRD)
+                    ? {
+                        label: 'Cancel sync',
+                        key: 'cancel-sync',
+                        icon: <Icon name="times-circle" />,
+                        onClick: () => syncAbort.current?.abort(),
+                      }
+                    : {
+                        label: 'Sync to local computer',
+                        key: 'sync',
+                        icon: <Icon name="sync" />,
+                        onClick: () => sync(),
+                      },
                   {
-                    label: 'Sync to local computer',
-                    key: 'sync',
-                    icon: <Icon name="sync" />,
-                    onClick: () => runSync(project.value.projectId),
+                    label: 'Replaced local changes',
+                    key: 'preserved',
+                    disabled: projectADT.is.remote(project),
+                    icon: <Icon name="history" />,
+                    onClick: () => {
+                      setShowPreserved(true);
+                      runPreserved();
+                    },
                   },
                   { type: 'divider' },
                   {
@@ -125,6 +185,19 @@ export const ListItem = ({ project, onOpen, onSync, onRemove }: ListItemProps) =
           pending={constNull}
           failure={(err) => <Alert type={'warning'} description={err} />}
         />
+        {showPreserved && (
+          <GuardRemoteEither
+            value={preservedRD}
+            render={(files) => (
+              <PreservedFileList
+                files={files}
+                onRestore={runPreserved}
+                onClose={() => setShowPreserved(false)}
+              />
+            )}
+            failure={(err) => <Alert type={'warning'} description={err} />}
+          />
+        )}
       </VStack>
     </StyledListItem>
   );
@@ -136,25 +209,71 @@ const viewFromStringException: <A>(
   e: remoteEither.RemoteEither<string, A>,
 ) => remoteEither.RemoteEither<React.ReactElement, A> = remoteEither.mapLeft((x) => <>{x}</>);
 
+const formatMegabytes = (bytes: number) => `${(bytes / (1024 * 1024)).toFixed(1)} MB`;
+
+const describeViolation = uploadViolation.fold<string>({
+  fileTooLarge: ({ path, size, limit }) =>
+    `${path} is ${formatMegabytes(size)}, files may be at most ${formatMegabytes(limit)}`,
+  totalSizeExceeded: ({ size, limit }) =>
+    `The changed files are ${formatMegabytes(size)} in total, at most ${formatMegabytes(
+      limit,
+    )} can be uploaded at once`,
+  tooManyFiles: ({ count, limit }) =>
+    `${count} files changed, at most ${limit} can be uploaded at once`,
+  deniedContent: ({ path }) => `${path} is a compiled or binary file, which can’t be uploaded`,
+});
+
+const describeMergeReport = ({ path, outcome }: MergeReport): string =>
+  mergeOutcome.fold(outcome, {
+    merged: () => `${path} was merged`,
+    conflicting: ({ regions }) =>
+      `${path} was edited in ${regions} ${regions === 1 ? 'place' : 'places'} on both sides`,
+    unmergeable: (reason) => `${path} can’t be merged: ${reason}`,
+  });
+
 const viewFromSyncException: (env: {
   choseProjectDir(): void;
   forcePush(): void;
   forcePull(): void;
+  restoreReadOnly(): void;
 }) => <A>(
   e: remoteEither.RemoteEither<SyncException, A>,
 ) => remoteEither.RemoteEither<React.ReactElement, A> = ({
   choseProjectDir,
   forcePush,
   forcePull,
+  restoreReadOnly,
 }) =>
   remoteEither.mapLeft(
     syncExceptionADT.fold({
-      conflictingChanges: () => (
+      conflictingChanges: (reports) => (
         <>
           <Typography.Paragraph>
             There are conflicting changes between your local copy and the remote project. This can
             be resolved by keeping either one of them.
           </Typography.Paragraph>
+          {reports.length > 0 && (
+            <Typography.Paragraph>
+              {reports.map((report) => (
+                <React.Fragment key={report.path}>
+                  <HStack align="center" gap="xs">
+                    <Icon name={'file'} />
+                    {describeMergeReport(report)}
+                  </HStack>
+                  {mergeOutcome.is.conflicting(report.outcome) && (
+                    <Collapse>
+                      <Collapse.Panel
+                        key="merged"
+                        header="Show both versions with conflict markers"
+                      >
+                        <pre>{report.outcome.value.text}</pre>
+                      </Collapse.Panel>
+                    </Collapse>
+                  )}
+                </React.Fragment>
+              ))}
+            </Typography.Paragraph>
+          )}
           <HStack gap="xs" justify="center">
             <Button onClick={forcePush}>Use local copy</Button>
             <Button onClick={forcePull}>Reset from remote</Button>
@@ -166,6 +285,24 @@ const viewFromSyncException: (env: {
           Read-only files changed: {reason} ({path})
         </>
       ),
+      readOnlyFilesModified: (files) => (
+        <>
+          <Typography.Paragraph>
+            The following files are read-only, but were changed or removed:
+          </Typography.Paragraph>
+          <Typography.Paragraph>
+            {files.map(({ path }) => (
+              <HStack key={path} align="center" gap="xs">
+                <Icon name={'file'} />
+                <strong>{path}</strong>
+              </HStack>
+            ))}
+          </Typography.Paragraph>
+          <HStack gap="xs" justify="center">
+            <Button onClick={restoreReadOnly}>Restore original files</Button>
+          </HStack>
+        </>
+      ),
       invalidFilename: (filename) => (
         <>
           <Typography.Paragraph>
@@ -179,11 +316,27 @@ const viewFromSyncException: (env: {
           <Typography.Paragraph>{invalidFileNameMessage}</Typography.Paragraph>
         </>
       ),
+      uploadRejected: (violations: Array<UploadViolation>) => (
+        <>
+          <Typography.Paragraph>
+            The local changes can’t be uploaded. Please remove or shrink these files:
+          </Typography.Paragraph>
+          <Typography.Paragraph>
+            {violations.map((violation) => (
+              <HStack key={describeViolation(violation)} align="center" gap="xs">
+                <Icon name={'file'} />
+                {describeViolation(violation)}
+              </HStack>
+            ))}
+          </Typography.Paragraph>
+        </>
+      ),
       fileSystemCorrupted: ({ path, reason }) => (
         <>
           Problems with the file system: {reason} ({path})
         </>
       ),
+      cancelled: () => <>The sync was cancelled.</>,
       projectDirMissing: () => (
         <>
           <Typography.Paragraph>
@@ -0,0 +1,39 @@
+import { Button, Typography } from 'antd';
+import React from 'react';
+import { PreservedFile } from '@/domain/FileState';
+import { Icon } from '@/ui/foundation/Icons';
+import { HStack, VStack } from '@/ui/foundation/Layout';
+
+export interface PreservedFileListProps {
+  files: Array<PreservedFile>;
+  onRestore(id: string): void;
+  onClose(): void;
+}
+
+/** Local versions replaced by a sync, each of which can be put back. */
+export const PreservedFileList = ({ files, onRestore, onClose }: PreservedFileListProps) => (
+  <VStack gap="xs">
+    <Typography.Paragraph>
+      {files.length === 0
+        ? 'No local changes were replaced by a sync.'
+        : 'These local changes were replaced by a sync and can be restored:'}
+    </Typography.Paragraph>
+    {files.map(({ id, path, preservedAt }) => (
+      <HStack key={id} align="center" justify="space-between" gap="xs">
+        <HStack align="center" gap="xs">
+          <Icon name={'file'} />
+          <strong>{path}</strong>
+          {new Date(preservedAt).toLocaleString()}
+        </HStack>
+        <Button size="small" onClick={() => onRestore(id)}>
+          Restore
+        </Button>
+      </HStack>
+    ))}
+    <HStack justify="center">
+      <Button size="small" onClick={onClose}>
+        Close
+      </Button>
+    </HStack>
+  </VStack>
+);
@@ -1,5 +1,6 @@
 import React from 'react';
 import { io } from '@code-expert/prelude';
+import { Progress } from '@/lib/tauri/progress';
 import { ActionButton } from '@/ui/components/ActionButton';
 import { Icon } from '@/ui/foundation/Icons';
 import { keyframes, styled, useTheme } from '@/ui/foundation/Theme';
@@ -19,10 +20,17 @@ const RotatingIcon = styled(Icon, () => ({
 export interface SyncButtonProps {
   now: io.IO<Date>;
   state: SyncButtonState;
+  /** Progress of the running sync, if it reported any */
+  progress?: Progress;
   onClick: io.IO<void>;
 }
 
-export const SyncButton = ({ now, state, onClick }: SyncButtonProps) =>
+const progressLabel = (progress: Progress | undefined) =>
+  progress != null && progress.total > 0
+    ? ` ${Math.min(100, Math.floor((100 * progress.bytes) / progress.total))} %`
+    : '';
+
+export const SyncButton = ({ now, state, progress, onClick }: SyncButtonProps) =>
   syncButtonStateADT.fold(state, {
     remote: () => (
       <ActionButton
@@ -34,7 +42,7 @@ export const SyncButton = ({ now, state, onClick }: SyncButtonProps) =>
     synced: (date) => <Synced now={now()} syncedAt={date} onClick={onClick} />,
     syncing: () => (
       <ActionButton
-        label="Sync in progress …"
+        label={`Sync in progress …${progressLabel(progress)}`}
         icon={<RotatingIcon name={'sync'} />}
         onClick={onClick}
         style={{ cursor: 'wait' }}
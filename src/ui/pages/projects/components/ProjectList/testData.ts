@@ -1,4 +1,5 @@
 import { constVoid, task, taskEither } from '@code-expert/prelude';
+import { PreservedFile } from '@/domain/FileState';
 import { ProjectId, projectADT } from '@/domain/Project';
 import { ProjectMetadata } from '@/domain/ProjectMetadata';
 import { SyncException } from '@/domain/SyncException';
@@ -21,6 +22,7 @@ export const localProject = projectADT.local({
   ...metadata,
   basePath: '/tmp/cxexample',
   files: [],
+  dirs: [],
   syncedAt: new Date('2023-05-06T11:00:00Z'),
   syncState: syncStateADT.synced(changesADT.unknown()),
 });
@@ -36,3 +38,23 @@ export const syncProject: (id: ProjectId) => taskEither.TaskEither<SyncException
 
 export const removeProject: (id: ProjectId) => task.Task<void> = () =>
   task.delay(DELAY)(task.of(undefined));
+
+export const listPreservedFiles: (
+  id: ProjectId,
+) => taskEither.TaskEither<string, Array<PreservedFile>> = () =>
+  task.delay(DELAY)(
+    taskEither.right([
+      {
+        id: '20230506110000000-0',
+        path: './main.py',
+        mode: 'trash',
+        copy: '20230506110000000-0',
+        preservedAt: '2023-05-06T13:00:00+02:00',
+      },
+    ]),
+  );
+
+export const restorePreservedFile: (
+  id: ProjectId,
+  preservedId: string,
+) => taskEither.TaskEither<string, void> = () => task.delay(DELAY)(taskEither.fromIO(constVoid));
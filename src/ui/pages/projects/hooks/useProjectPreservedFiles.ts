@@ -0,0 +1,61 @@
+import { api } from 'api';
+import React from 'react';
+import { iots, pipe, taskEither, taskOption } from '@code-expert/prelude';
+import { PreservedFile } from '@/domain/FileState';
+import { ProjectId, projectPrism } from '@/domain/Project';
+import { path } from '@/lib/tauri';
+import { useGlobalContext } from '@/ui/GlobalContext';
+
+/**
+ * List the local versions a sync replaced in a project, and put one of them back in place of the
+ * file it replaced.
+ */
+export const useProjectPreservedFiles = () => {
+  const { projectRepository } = useGlobalContext();
+
+  const projectDir = React.useCallback(
+    (projectId: ProjectId): taskEither.TaskEither<string, string> =>
+      pipe(
+        taskOption.sequenceS({
+          rootDir: api.settingRead('projectDir', iots.string),
+          project: pipe(
+            projectRepository.getProject(projectId),
+            taskOption.chainOptionK(projectPrism.local.getOption),
+          ),
+        }),
+        taskOption.chainTaskK(({ rootDir, project }) => path.join(rootDir, project.value.basePath)),
+        taskEither.fromTaskOption(() => 'The project has not been synced yet'),
+      ),
+    [projectRepository],
+  );
+
+  const list = React.useCallback(
+    (projectId: ProjectId): taskEither.TaskEither<string, Array<PreservedFile>> =>
+      pipe(
+        projectDir(projectId),
+        taskEither.chain((dir) =>
+          pipe(
+            api.listPreservedFiles(projectId, dir),
+            taskEither.mapLeft(({ message }) => message),
+          ),
+        ),
+      ),
+    [projectDir],
+  );
+
+  const restore = React.useCallback(
+    (projectId: ProjectId, id: string): taskEither.TaskEither<string, void> =>
+      pipe(
+        projectDir(projectId),
+        taskEither.chain((dir) =>
+          pipe(
+            api.restorePreservedFile(projectId, dir, id),
+            taskEither.mapLeft(({ message }) => message),
+          ),
+        ),
+      ),
+    [projectDir],
+  );
+
+  return { list, restore };
+};
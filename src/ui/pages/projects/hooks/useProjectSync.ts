@@ -1,5 +1,5 @@
 import { ResponseType } from '@tauri-apps/api/http';
-import { api } from 'api';
+import { Preserve, api } from 'api';
 import React from 'react';
 import {
   array,
@@ -10,10 +10,12 @@ import {
   either,
   flow,
   iots,
+  nonEmptyArray,
   number,
   option,
   ord,
   pipe,
+  record,
   task,
   taskEither,
   taskOption,
@@ -24,31 +26,41 @@ import {
   FileEntryTypeC,
   FilePermissions,
   FilePermissionsC,
-  fileArrayFromTree,
   isFile,
   isValidDirName,
   isValidFileName,
-  isVisibleFile,
+  isWritable,
 } from '@/domain/File';
 import {
+  Conflict,
   LocalFileChange,
-  LocalFileState,
+  MergeReport,
+  PreserveMode,
   RemoteFileChange,
-  getConflicts,
-  getLocalChanges,
-  getRemoteChanges,
+  SyncPlan,
+  TamperedFile,
+  UploadPolicy,
+  fileHash,
   localFileChange,
+  mergeOutcome,
   remoteFileChange,
+  uploadOutcome,
 } from '@/domain/FileState';
-import { LocalProject, Project, ProjectId, projectADT, projectPrism } from '@/domain/Project';
+import { Project, ProjectId, projectADT, projectPrism } from '@/domain/Project';
 import { ProjectMetadata } from '@/domain/ProjectMetadata';
-import { SyncException, fromHttpError, syncExceptionADT } from '@/domain/SyncException';
+import {
+  SyncException,
+  fromHttpError,
+  fromTauriException,
+  syncExceptionADT,
+} from '@/domain/SyncException';
 import { changesADT, syncStateADT } from '@/domain/SyncState';
-import { fs as libFs, os as libOs, path as libPath } from '@/lib/tauri';
-import { removeFile } from '@/lib/tauri/fs';
+import { path as libPath } from '@/lib/tauri';
+import { CommandError, TauriException } from '@/lib/tauri/TauriException';
+import { OperationOptions } from '@/lib/tauri/operation';
 import { useGlobalContext } from '@/ui/GlobalContext';
 import { useTimeContext } from '@/ui/contexts/TimeContext';
-import { apiGetSigned, apiPostSigned, requestBody } from '@/utils/api';
+import { apiGetSigned, decodeApiResponse } from '@/utils/api';
 import { panic } from '@/utils/error';
 
 function updateDir({
@@ -65,15 +77,65 @@ function updateDir({
     taskEither.bindTaskK('systemFilePath', () => libPath.join(projectDir, projectDirPath)),
     taskEither.chainW(({ systemFilePath }) =>
       pipe(
-        api.createProjectDir(systemFilePath, permissions === 'r'),
-        taskEither.mapLeft(({ message: reason }) =>
-          syncExceptionADT.fileSystemCorrupted({ path: projectDir, reason }),
-        ),
+        api.createProjectDir(systemFilePath, !isWritable(permissions)),
+        taskEither.mapLeft(fromTauriException(projectDir)),
       ),
     ),
   );
 }
 
+const getRemoteFileContent = (
+  projectId: ProjectId,
+  path: string,
+): taskEither.TaskEither<SyncException, string> =>
+  pipe(
+    apiGetSigned({
+      path: `project/${projectId}/file`,
+      jwtPayload: { path },
+      codec: iots.string,
+      responseType: ResponseType.Text,
+    }),
+    taskEither.mapLeft(fromHttpError),
+  );
+
+/** The remote content as it is stored, as decoding it as text would hide binary files. */
+const getRemoteFileBytes = (
+  projectId: ProjectId,
+  path: string,
+): taskEither.TaskEither<SyncException, Array<number>> =>
+  pipe(
+    apiGetSigned({
+      path: `project/${projectId}/file`,
+      jwtPayload: { path },
+      codec: iots.array(iots.number),
+      responseType: ResponseType.Binary,
+    }),
+    taskEither.mapLeft(fromHttpError),
+  );
+
+/**
+ * Replaced local content is kept unless it is still the version of the last sync, so a forced pull
+ * never destroys edits.
+ */
+const preserveLocal =
+  (
+    projectId: ProjectId,
+    projectDir: string,
+    previous: option.Option<Array<File>>,
+    mode: PreserveMode,
+  ) =>
+  (path: string): Preserve => ({
+    projectId,
+    rootDir: projectDir,
+    syncedHash: pipe(
+      previous,
+      option.chain(array.findFirst((file) => file.path === path)),
+      option.map(({ hash }) => hash),
+      option.toNullable,
+    ),
+    mode,
+  });
+
 const writeSingeFile = ({
   projectFilePath,
   projectId,
@@ -81,6 +143,7 @@ const writeSingeFile = ({
   version,
   permissions,
   type,
+  preserve,
 }: {
   projectFilePath: string;
   projectId: ProjectId;
@@ -88,6 +151,7 @@ const writeSingeFile = ({
   version: number;
   permissions: FilePermissions;
   type: FileEntryType;
+  preserve: Preserve;
 }): taskEither.TaskEither<SyncException, File> =>
   pipe(
     taskEither.Do,
@@ -95,26 +159,12 @@ const writeSingeFile = ({
     taskEither.chainFirst(({ systemFilePath }) =>
       pipe(
         api.createProjectPath(projectDir),
-        taskEither.mapLeft(({ message: reason }) =>
-          syncExceptionADT.wide.fileSystemCorrupted({ path: projectDir, reason }),
-        ),
-        taskEither.chain(() =>
-          pipe(
-            apiGetSigned({
-              path: `project/${projectId}/file`,
-              jwtPayload: { path: projectFilePath },
-              codec: iots.string,
-              responseType: ResponseType.Text,
-            }),
-            taskEither.mapLeft(fromHttpError),
-          ),
-        ),
+        taskEither.mapLeft(fromTauriException(projectDir)),
+        taskEither.chain(() => getRemoteFileContent(projectId, projectFilePath)),
         taskEither.chain((fileContent) =>
           pipe(
-            api.writeProjectFile(systemFilePath, fileContent, permissions === 'r'),
-            taskEither.mapLeft(({ message: reason }) =>
-              syncExceptionADT.wide.fileSystemCorrupted({ path: projectDir, reason }),
-            ),
+            api.writeProjectFile(systemFilePath, fileContent, permissions, preserve),
+            taskEither.mapLeft(fromTauriException(projectDir)),
           ),
         ),
       ),
@@ -122,9 +172,7 @@ const writeSingeFile = ({
     taskEither.bind('hash', ({ systemFilePath }) =>
       pipe(
         api.getFileHash(systemFilePath),
-        taskEither.mapLeft(({ message: reason }) =>
-          syncExceptionADT.wide.fileSystemCorrupted({ path: projectDir, reason }),
-        ),
+        taskEither.mapLeft(fromTauriException(projectDir)),
       ),
     ),
     taskEither.map(({ hash }) => ({
@@ -139,84 +187,16 @@ const writeSingeFile = ({
 const deleteSingeFile = ({
   projectFilePath,
   projectDir,
+  preserve,
 }: {
   projectFilePath: string;
   projectDir: string;
+  preserve: Preserve;
 }): task.Task<void> =>
-  pipe(libPath.join(projectDir, projectFilePath), task.chainFirst(removeFile), task.map(constVoid));
-
-const addHash =
-  (projectDir: string) =>
-  ({
-    path,
-    type,
-  }: {
-    path: string;
-    type: 'file';
-  }): task.Task<{ path: string; type: 'file'; hash: string }> =>
-    pipe(
-      libPath.join(projectDir, path),
-      task.chain(
-        flow(
-          api.getFileHash,
-          taskEither.getOrElse((e) => {
-            throw e;
-          }),
-        ),
-      ),
-      task.map((hash) => ({ path, type, hash })),
-    );
-
-const getProjectFilesLocal = (
-  projectDir: string,
-): taskEither.TaskEither<SyncException, ReadonlyArray<{ path: string; type: 'file' }>> =>
-  pipe(
-    libFs.readDirTree(projectDir),
-    taskEither.mapLeft((e) =>
-      syncExceptionADT.fileSystemCorrupted({
-        path: projectDir,
-        reason: e.message,
-      }),
-    ),
-    taskEither.map(fileArrayFromTree),
-    taskEither.chainTaskK(array.filterE(task.ApplicativePar)(isVisibleFile(libPath))),
-    taskEither.map(array.filter(isFile)),
-    taskEither.chain(
-      taskEither.traverseArray(({ path, type }) =>
-        pipe(
-          libPath.stripAncestor(projectDir)(path),
-          taskEither.bimap(
-            (e) =>
-              syncExceptionADT.fileSystemCorrupted({
-                path: projectDir,
-                reason: e.message,
-              }),
-            (relative) => ({ path: relative, type }),
-          ),
-        ),
-      ),
-    ),
-  );
-
-/**
- * Possible Exceptions:
- * - has been synced before, but dir not present
- * - can't read dir or subdir
- *
- * By requiring {@link LocalProject} we have handled case 1. Case 2 & 3 are textbook exception, no need to differentiate
- */
-const getProjectInfoLocal = (
-  projectDir: string,
-  _: LocalProject,
-): taskEither.TaskEither<SyncException, Array<LocalFileState>> =>
   pipe(
-    getProjectFilesLocal(projectDir),
-    taskEither.chainW(
-      flow(
-        taskEither.traverseArray(flow(addHash(projectDir), taskEither.fromTask)),
-        taskEither.map(array.unsafeFromReadonly),
-      ),
-    ),
+    libPath.join(projectDir, projectFilePath),
+    task.chainFirst((systemFilePath) => api.removeProjectFile(systemFilePath, preserve)),
+    task.map(constVoid),
   );
 
 const getDirToUpdate = (projectInfoRemote: Array<RemoteFileInfo>): Array<RemoteFileInfo> =>
@@ -234,16 +214,22 @@ const RemoteFileInfoC = iots.strict({
 
 type RemoteFileInfo = iots.TypeOf<typeof RemoteFileInfoC>;
 
+const ProjectInfoRemoteC = iots.strict({
+  _id: ProjectId,
+  files: iots.array(RemoteFileInfoC),
+  /** `.cxignore` rules of the course, if it has any. */
+  ignore: iots.union([iots.string, iots.undefined]),
+});
+
+type ProjectInfoRemote = iots.TypeOf<typeof ProjectInfoRemoteC>;
+
 const getProjectInfoRemote = (
   projectId: ProjectId,
-): taskEither.TaskEither<SyncException, { _id: ProjectId; files: Array<RemoteFileInfo> }> =>
+): taskEither.TaskEither<SyncException, ProjectInfoRemote> =>
   pipe(
     apiGetSigned({
       path: `project/${projectId}/info`,
-      codec: iots.strict({
-        _id: ProjectId,
-        files: iots.array(RemoteFileInfoC),
-      }),
+      codec: ProjectInfoRemoteC,
     }),
     taskEither.mapLeft(fromHttpError),
   );
@@ -301,7 +287,7 @@ const checkClosestExistingAncestorIsWritable: (
           pipe(
             remote,
             array.findFirst((i) => i.path === closestPath),
-            option.map((i) => i.permissions === 'rw'),
+            option.map((i) => isWritable(i.permissions)),
           ),
         ),
         taskEither.filterOrElse(boolean.isTrue, () =>
@@ -386,7 +372,7 @@ const isFileWritable =
     pipe(
       remote,
       array.findFirst((i) => i.path === c.path),
-      option.fold(constFalse, (i) => i.permissions === 'rw'),
+      option.fold(constFalse, (i) => isWritable(i.permissions)),
     );
 
 // TODO: filter localChanges that are in conflict with remoteChanges
@@ -430,13 +416,19 @@ const getFilesToUpload = (
           removed: () =>
             flow(
               taskEither.fromPredicate(isFileWritable(remote), () =>
-                syncExceptionADT.readOnlyFilesChanged({ path: '', reason: 'File is read-only' }),
+                syncExceptionADT.readOnlyFilesChanged({
+                  path: x.path,
+                  reason: 'File is read-only',
+                }),
               ),
               taskEither.chain(checkClosestExistingAncestorIsWritable(remote)),
             ),
           updated: () =>
             taskEither.fromPredicate(isFileWritable(remote), () =>
-              syncExceptionADT.readOnlyFilesChanged({ path: '', reason: 'File is read-only' }),
+              syncExceptionADT.readOnlyFilesChanged({
+                path: x.path,
+                reason: 'File is read-only',
+              }),
             ),
         }),
       ),
@@ -478,11 +470,19 @@ const getFilesToDelete = (remoteChanges: Array<RemoteFileChange>): Array<RemoteF
     ),
   );
 
+/** Checked before an upload is compressed, to fail early instead of being refused by the server. */
+const uploadPolicy: UploadPolicy = {
+  maxFileSize: 10 * 1024 * 1024,
+  maxTotalSize: 50 * 1024 * 1024,
+  maxFileCount: 1000,
+  deny: ['elf', 'pe', 'machO', 'javaClass', 'wasm'],
+};
+
 export const uploadChangedFiles = (
-  fileName: string,
   projectId: ProjectId,
   projectDir: string,
   localChanges: Array<LocalFileChange>,
+  operation?: OperationOptions,
 ): taskEither.TaskEither<SyncException, void> =>
   pipe(
     taskEither.Do,
@@ -500,18 +500,6 @@ export const uploadChangedFiles = (
         array.map(({ path }) => path),
       ),
     ),
-    taskEither.bindTaskK('archivePath', () =>
-      pipe(
-        libOs.tempDir,
-        taskEither.getOrElse((e) => {
-          throw e;
-        }),
-        task.chain((tempDir) => libPath.join(tempDir, fileName)),
-      ),
-    ),
-    taskEither.bindTaskK('tarHash', ({ uploadFiles, archivePath }) =>
-      api.buildTar(archivePath, projectDir, uploadFiles),
-    ),
     taskEither.let('removeFiles', () =>
       pipe(
         localChanges,
@@ -526,61 +514,207 @@ export const uploadChangedFiles = (
         array.map(({ path }) => path),
       ),
     ),
-    taskEither.bindTaskK('body', ({ uploadFiles, archivePath }) =>
+    taskEither.chain(({ uploadFiles, removeFiles }) =>
       pipe(
-        array.isEmpty(uploadFiles)
-          ? taskEither.of(new Uint8Array())
-          : libFs.readBinaryFile(archivePath),
-        taskEither.getOrElse((e) => {
-          throw e;
-        }),
+        // the archive is streamed to the server; equal submissions get equal hashes, whichever
+        // machine they come from
+        api.uploadArchive(
+          projectId,
+          projectDir,
+          uploadFiles,
+          removeFiles,
+          uploadPolicy,
+          { deterministic: true },
+          operation,
+        ),
+        taskEither.mapLeft(fromTauriException(projectDir)),
+        taskEither.chainEitherK(
+          uploadOutcome.fold<either.Either<SyncException, unknown>>({
+            uploaded: flow(
+              (response) => {
+                const ignored = response.archive?.ignored ?? [];
+                if (ignored.length > 0) {
+                  console.warn(`[upload] Ignored files were not uploaded: ${ignored.join(', ')}`);
+                }
+                return response;
+              },
+              decodeApiResponse(
+                iots.strict({
+                  _id: ProjectId,
+                  files: iots.array(RemoteFileInfoC),
+                }),
+              ),
+              either.mapLeft(fromHttpError),
+            ),
+            rejected: ({ violations }) => either.left(syncExceptionADT.uploadRejected(violations)),
+          }),
+        ),
       ),
     ),
-    taskEither.chain(({ body, tarHash, removeFiles, uploadFiles }) =>
+    taskEither.map(constVoid),
+  );
+
+/**
+ * Check the read-only files against the previous sync. Without `restore`, fail if any of them
+ * differ. With it, they are rewritten from the pristine copies kept by the app, and the paths of
+ * those without an intact copy are returned to be downloaded again.
+ */
+const checkReadOnlyFiles = (
+  projectId: ProjectId,
+  projectDir: string,
+  files: Array<File>,
+  restore: boolean,
+): taskEither.TaskEither<SyncException, Array<string>> =>
+  pipe(
+    api.checkReadOnlyFiles(projectId, projectDir, files, restore),
+    taskEither.mapLeft(fromTauriException(projectDir)),
+    taskEither.map(array.filter((file: TamperedFile) => !file.restored)),
+    taskEither.filterOrElseW(
+      (unrestored) => restore || array.isEmpty(unrestored),
+      syncExceptionADT.wide.readOnlyFilesModified,
+    ),
+    taskEither.map(array.map(({ path }) => path)),
+  );
+
+/**
+ * Download the read-only files at `paths` again instead of syncing their local changes, which
+ * could not be undone otherwise.
+ */
+const redownloadFiles =
+  (remote: Array<RemoteFileInfo>, paths: Array<string>) =>
+  ({ local, remote: remoteChanges, conflicts }: SyncPlan): SyncPlan => ({
+    local: local.filter(({ path }) => !paths.includes(path)),
+    remote: [
+      ...remoteChanges,
+      ...pipe(
+        remote,
+        array.filter(isFile),
+        array.filter(
+          ({ path }) => paths.includes(path) && !remoteChanges.some((c) => c.path === path),
+        ),
+        array.map(({ path, version }) => ({ path, change: remoteFileChange.updated(version) })),
+      ),
+    ],
+    conflicts: conflicts.filter(({ path }) => !paths.includes(path)),
+  });
+
+const isEditedOnBothSides = ({ changeLocal, changeRemote }: Conflict): boolean =>
+  localFileChange.is.updated(changeLocal) && remoteFileChange.is.updated(changeRemote);
+
+/**
+ * Merge files edited on both sides since the last sync, with the last synced version as base.
+ * Files whose edits did not overlap are reported as merged; their local file now holds the merged
+ * text, which is uploaded instead of downloading the remote version.
+ */
+const mergeConflicts = (
+  projectId: ProjectId,
+  projectDir: string,
+  previous: option.Option<Array<File>>,
+  conflicts: Array<Conflict>,
+): taskEither.TaskEither<SyncException, Array<MergeReport>> =>
+  pipe(
+    conflicts,
+    array.filter(isEditedOnBothSides),
+    array.filterMap(({ path }) =>
       pipe(
-        apiPostSigned({
-          path: `project/${projectId}/files`,
-          jwtPayload: array.isEmpty(uploadFiles) ? { removeFiles } : { tarHash, removeFiles },
-          body: requestBody.binary({
-            body,
-            type: 'application/x-tar',
-            encoding: option.some('br'),
-          }),
-          codec: iots.strict({
-            _id: ProjectId,
-            files: iots.array(RemoteFileInfoC),
-          }),
-        }),
-        taskEither.mapLeft(fromHttpError),
+        previous,
+        option.chain(array.findFirst((file) => file.path === path)),
+        option.map(({ hash }) => ({ path, baseHash: hash })),
       ),
     ),
-    taskEither.map(constVoid),
+    taskEither.traverseArray(({ path, baseHash }) =>
+      pipe(
+        getRemoteFileBytes(projectId, path),
+        taskEither.map((remote) => ({ path, baseHash, remote })),
+      ),
+    ),
+    taskEither.chain((files) =>
+      files.length === 0
+        ? taskEither.of<SyncException, Array<MergeReport>>([])
+        : pipe(
+            api.mergeConflicts(projectDir, array.unsafeFromReadonly(files)),
+            taskEither.mapLeft(fromTauriException(projectDir)),
+          ),
+    ),
   );
 
-const checkConflicts = <R>({
-  localChanges,
-  remoteChanges,
-}: {
-  localChanges: option.Option<NonEmptyArray<LocalFileChange>>;
-  remoteChanges: option.Option<NonEmptyArray<RemoteFileChange>>;
-} & R): either.Either<SyncException, void> =>
+/**
+ * Commit the project directory to its local git repository, if the `projectHistory` setting is
+ * enabled. The sync has completed by then, so a failed commit is only logged.
+ */
+const commitProjectHistory = (
+  projectId: ProjectId,
+  projectDir: string,
+  changes: { uploaded: Array<string>; downloaded: Array<string>; deleted: Array<string> },
+): task.Task<void> =>
+  pipe(
+    api.settingRead('projectHistory', iots.boolean),
+    taskOption.filter(boolean.isTrue),
+    taskOption.fold(
+      () => task.of(undefined),
+      () =>
+        pipe(
+          api.commitProjectHistory(projectId, projectDir, changes),
+          taskEither.match((e) => {
+            console.warn(`[projectHistory] The sync could not be committed: ${e.message}`);
+          }, constVoid),
+        ),
+    ),
+  );
+
+/** Fail on conflicts that remain, reporting why those that were attempted did not merge. */
+const checkConflicts = (
+  conflicts: Array<Conflict>,
+  mergeReports: Array<MergeReport>,
+  force: ForceSyncDirection | undefined,
+): either.Either<SyncException, void> =>
   pipe(
-    option.sequenceS({ local: localChanges, remote: remoteChanges }),
-    option.chain(({ local, remote }) => getConflicts(local, remote)),
+    conflicts,
+    nonEmptyArray.fromArray,
+    // a forced sync deliberately discards one side's changes
+    option.filter(() => force == null),
     option.fold(
       () => either.right(undefined),
       (conflicts) => {
         console.log(conflicts);
-        return either.left(syncExceptionADT.conflictingChanges());
+        return either.left(
+          syncExceptionADT.conflictingChanges(
+            mergeReports.filter(({ path }) => conflicts.some((c) => c.path === path)),
+          ),
+        );
       },
     ),
   );
 
 export type ForceSyncDirection = 'push' | 'pull';
 
+export interface SyncOptions extends OperationOptions {
+  force?: ForceSyncDirection;
+  /** Restore modified read-only files from their pristine copies instead of failing. */
+  restoreReadOnly?: boolean;
+  /** Where local edits overwritten or removed by the download are kept, next to them by default. */
+  preserveMode?: PreserveMode;
+}
+
+/** Keep the watcher from reporting the writes of a sync as local changes of the project. */
+const whileWatchingPaused =
+  (projectId: ProjectId) =>
+  <A>(sync: taskEither.TaskEither<SyncException, A>): taskEither.TaskEither<SyncException, A> => {
+    const ignoreError = (action: string) =>
+      taskEither.orElseW((e: TauriException) => {
+        console.debug(`[useProjectSync] Could not ${action} watching: ${e.message}`);
+        return taskEither.right<never, void>(undefined);
+      });
+    return taskEither.bracketW(
+      pipe(api.pauseWatching(projectId), ignoreError('pause')),
+      () => sync,
+      () => pipe(api.resumeWatching(projectId), ignoreError('resume')),
+    );
+  };
+
 export type RunProjectSync = (
   project: Project,
-  options?: { force?: ForceSyncDirection },
+  options?: SyncOptions,
 ) => taskEither.TaskEither<SyncException, void>;
 
 export const useProjectSync = () => {
@@ -588,7 +722,10 @@ export const useProjectSync = () => {
   const { projectRepository } = useGlobalContext();
 
   return React.useCallback<RunProjectSync>(
-    (project, { force } = {}) =>
+    (
+      project,
+      { force, restoreReadOnly = false, preserveMode = 'conflictCopy', ...operation } = {},
+    ) =>
       pipe(
         taskEither.Do,
 
@@ -619,34 +756,114 @@ export const useProjectSync = () => {
             option.map(({ value: { files } }) => files),
           ),
         ),
-        taskEither.bindW('projectInfoRemote', () => getProjectInfoRemote(project.value.projectId)),
-        taskEither.bind('projectInfoLocal', ({ projectDir }) =>
+        // denied access to these means the student tampered with them
+        taskEither.let('readOnlyFiles', ({ projectInfoPrevious }) =>
           pipe(
-            projectPrism.local.getOption(project),
-            option.traverse(taskEither.ApplicativePar)((project) =>
-              getProjectInfoLocal(projectDir, project),
+            projectInfoPrevious,
+            option.fold(
+              () => [],
+              flow(
+                array.filter(({ permissions }) => !isWritable(permissions)),
+                array.map(({ path }) => path),
+              ),
             ),
           ),
         ),
-        taskEither.let('remoteChanges', ({ projectInfoRemote, projectInfoPrevious }) =>
+        // pulling by force resets read-only files like any other instead of failing
+        taskEither.bind('redownloadPaths', ({ projectDir, projectInfoPrevious }) =>
           pipe(
             projectInfoPrevious,
             option.fold(
-              // the project has never been synced before
-              () => getRemoteChanges([], projectInfoRemote.files),
-              (previous) => getRemoteChanges(previous, projectInfoRemote.files),
+              () => taskEither.of<SyncException, Array<string>>([]),
+              (files) =>
+                checkReadOnlyFiles(
+                  project.value.projectId,
+                  projectDir,
+                  files,
+                  restoreReadOnly || force === 'pull',
+                ),
             ),
+          ),
+        ),
+        // lets students recover their work should the sync go wrong, but doesn't hold it up
+        taskEither.chainFirstTaskK(({ projectDir, projectInfoPrevious }) =>
+          option.isSome(projectInfoPrevious)
+            ? pipe(
+                api.createSnapshot(project.value.projectId, projectDir),
+                taskEither.match((e) => {
+                  console.warn(`[snapshot] The project could not be snapshotted: ${e.message}`);
+                }, constVoid),
+              )
+            : task.of(undefined),
+        ),
+        taskEither.bindW('projectInfoRemote', () => getProjectInfoRemote(project.value.projectId)),
+        // the rules decide which local files take part in the sync
+        taskEither.chainFirst(({ projectDir, projectInfoRemote, readOnlyFiles }) =>
+          pipe(
+            api.writeCourseIgnoreRules(project.value.projectId, projectInfoRemote.ignore ?? ''),
+            taskEither.mapLeft(fromTauriException(projectDir, readOnlyFiles)),
+          ),
+        ),
+        taskEither.bind(
+          'syncPlan',
+          ({
+            projectDir,
+            projectInfoPrevious,
+            projectInfoRemote,
+            readOnlyFiles,
+            redownloadPaths,
+          }) =>
+            pipe(
+              api.computeSyncPlan(
+                project.value.projectId,
+                projectDir,
+                projectInfoPrevious,
+                projectInfoRemote.files,
+                operation,
+              ),
+              taskEither.mapLeft(fromTauriException(projectDir, readOnlyFiles)),
+              taskEither.map(redownloadFiles(projectInfoRemote.files, redownloadPaths)),
+            ),
+        ),
+        // a forced sync discards one side anyway
+        taskEither.bind('mergeReports', ({ projectDir, projectInfoPrevious, syncPlan }) =>
+          force == null
+            ? mergeConflicts(
+                project.value.projectId,
+                projectDir,
+                projectInfoPrevious,
+                syncPlan.conflicts,
+              )
+            : taskEither.of<SyncException, Array<MergeReport>>([]),
+        ),
+        taskEither.let('mergedPaths', ({ mergeReports }) =>
+          pipe(
+            mergeReports,
+            array.filter(({ outcome }) => mergeOutcome.is.merged(outcome)),
+            array.map(({ path }) => path),
+          ),
+        ),
+        taskEither.let('remoteChanges', ({ syncPlan, mergedPaths }) =>
+          pipe(
+            syncPlan.remote,
+            array.filter(({ path }) => !mergedPaths.includes(path)),
+            nonEmptyArray.fromArray,
             option.filter(() => force == null || force === 'pull'),
           ),
         ),
-        taskEither.let('localChanges', ({ projectInfoLocal, projectInfoPrevious }) =>
+        taskEither.let('localChanges', ({ syncPlan }) =>
           pipe(
-            option.sequenceS({ local: projectInfoLocal, previous: projectInfoPrevious }),
-            option.chain(({ local, previous }) => getLocalChanges(previous, local)),
+            nonEmptyArray.fromArray(syncPlan.local),
             option.filter(() => force == null || force === 'push'),
           ),
         ),
-        taskEither.chainFirstEitherKW(checkConflicts),
+        taskEither.chainFirstEitherKW(({ syncPlan, mergeReports, mergedPaths }) =>
+          checkConflicts(
+            syncPlan.conflicts.filter(({ path }) => !mergedPaths.includes(path)),
+            mergeReports,
+            force,
+          ),
+        ),
         taskEither.bind('filesToUpload', ({ localChanges, projectInfoRemote }) =>
           pipe(
             localChanges,
@@ -669,16 +886,19 @@ export const useProjectSync = () => {
               () => taskEither.of(undefined),
               (filesToUpload) =>
                 uploadChangedFiles(
-                  `project_${project.value.projectId}_${time.now().getTime()}.tar.br`,
                   project.value.projectId,
                   projectDir,
                   filesToUpload,
+                  operation,
                 ),
             ),
           ),
         ),
         // download and write added and updated files
-        taskEither.chainFirst(({ filesToDownload, projectDir, projectInfoRemote }) =>
+        taskEither.let('preserve', ({ projectDir, projectInfoPrevious }) =>
+          preserveLocal(project.value.projectId, projectDir, projectInfoPrevious, preserveMode),
+        ),
+        taskEither.chainFirst(({ filesToDownload, projectDir, projectInfoRemote, preserve }) =>
           pipe(
             getDirToUpdate(projectInfoRemote.files),
             array.sort(
@@ -708,6 +928,7 @@ export const useProjectSync = () => {
                             type,
                             version,
                             permissions,
+                            preserve: preserve(path),
                           }),
                       ),
                       taskEither.map(constVoid),
@@ -718,7 +939,7 @@ export const useProjectSync = () => {
           ),
         ),
         //delete remote removed files
-        taskEither.chainFirstTaskK(({ filesToDelete, projectDir }) =>
+        taskEither.chainFirstTaskK(({ filesToDelete, projectDir, preserve }) =>
           pipe(
             filesToDelete,
             option.foldW(
@@ -730,6 +951,7 @@ export const useProjectSync = () => {
                     deleteSingeFile({
                       projectFilePath: path,
                       projectDir,
+                      preserve: preserve(path),
                     }),
                   ),
                 ),
@@ -737,35 +959,76 @@ export const useProjectSync = () => {
           ),
         ),
         // update all project metadata
-        taskEither.bindW('updatedProjectInfo', ({ projectDir }) => {
-          const hashF = addHash(projectDir);
-          return pipe(
+        taskEither.bindW('updatedProjectInfo', ({ projectDir, readOnlyFiles }) =>
+          pipe(
             getProjectInfoRemote(project.value.projectId),
-            taskEither.chainW((projectInfoRemote) =>
-              pipe(
+            taskEither.chainW((projectInfoRemote) => {
+              const files = pipe(projectInfoRemote.files, array.filter(isFile));
+              const dirs = pipe(
                 projectInfoRemote.files,
-                array.filter(isFile),
-                array.traverse(task.ApplicativeSeq)((file) =>
+                array.filter((file) => !isFile(file)),
+                array.map(({ path, permissions }) => ({ path, permissions })),
+              );
+              return pipe(
+                api.getFileHashes(
+                  projectDir,
+                  files.map(({ path }) => path),
+                  operation,
+                ),
+                taskEither.mapLeft(fromTauriException(projectDir, readOnlyFiles)),
+                taskEither.chainEitherK((hashes) =>
                   pipe(
-                    hashF({ path: file.path, type: 'file' }),
-                    task.map(({ hash }) => ({ ...file, hash })),
+                    files,
+                    array.traverse(either.Applicative)((file) =>
+                      pipe(
+                        hashes,
+                        record.lookup(file.path),
+                        either.fromOption(() =>
+                          syncExceptionADT.fileSystemCorrupted({
+                            path: file.path,
+                            reason: 'File was not hashed',
+                          }),
+                        ),
+                        either.chain(
+                          flow(
+                            fileHash.fold<either.Either<CommandError, string>>({
+                              hashed: either.right,
+                              failed: either.left,
+                            }),
+                            either.bimap(
+                              ({ message: reason }) =>
+                                syncExceptionADT.fileSystemCorrupted({ path: file.path, reason }),
+                              (hash) => ({ ...file, hash }),
+                            ),
+                          ),
+                        ),
+                      ),
+                    ),
                   ),
                 ),
-                task.map((files) => ({
+                taskEither.map((files) => ({
                   ...projectInfoRemote,
                   files,
+                  dirs,
                 })),
-                taskEither.fromTask,
-              ),
-            ),
-          );
-        }),
+              );
+            }),
+          ),
+        ),
+        // keep the synced version of every file, e.g. to restore read-only files later
+        taskEither.chainFirstW(({ updatedProjectInfo, projectDir, readOnlyFiles }) =>
+          pipe(
+            api.storePristineFiles(project.value.projectId, projectDir, updatedProjectInfo.files),
+            taskEither.mapLeft(fromTauriException(projectDir, readOnlyFiles)),
+          ),
+        ),
         // store new state
         taskEither.chainFirstTaskK(({ updatedProjectInfo, projectDirRelative }) =>
           projectRepository.upsertOne(
             projectADT.local({
               ...project.value,
               files: updatedProjectInfo.files,
+              dirs: updatedProjectInfo.dirs,
               basePath: projectDirRelative,
               syncedAt: time.now(),
               syncState: projectADT.fold(project, {
@@ -775,7 +1038,24 @@ export const useProjectSync = () => {
             }),
           ),
         ),
+        taskEither.chainFirstTaskK(({ projectDir, filesToUpload, filesToDownload, filesToDelete }) =>
+          commitProjectHistory(project.value.projectId, projectDir, {
+            uploaded: pipe(
+              filesToUpload,
+              option.fold(() => [], array.map(({ path }) => path)),
+            ),
+            downloaded: pipe(
+              filesToDownload,
+              option.fold(() => [], array.map(({ path }) => path)),
+            ),
+            deleted: pipe(
+              filesToDelete,
+              option.fold(() => [], array.map(({ path }) => path)),
+            ),
+          }),
+        ),
         taskEither.map(constVoid),
+        whileWatchingPaused(project.value.projectId),
       ),
     [projectRepository, time],
   );
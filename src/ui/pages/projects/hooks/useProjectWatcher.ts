@@ -0,0 +1,90 @@
+import { useProperty } from '@frp-ts/react';
+import { listen } from '@tauri-apps/api/event';
+import { api } from 'api';
+import React from 'react';
+import { array, iots, pipe, task, taskEither, taskOption } from '@code-expert/prelude';
+import { ProjectId, projectADT, projectPrism } from '@/domain/Project';
+import { SyncState, changesADT, syncStateADT } from '@/domain/SyncState';
+import { useGlobalContext } from '@/ui/GlobalContext';
+
+interface ProjectChanged {
+  projectId: ProjectId;
+  paths: Array<string>;
+}
+
+const withLocalChanges = (syncState: SyncState): SyncState =>
+  syncStateADT.fold(syncState, {
+    exception: () => syncState,
+    synced: (changes) =>
+      changesADT.fold(changes, {
+        both: () => syncState,
+        remote: () => syncStateADT.synced(changesADT.both()),
+        local: () => syncState,
+        unknown: () => syncStateADT.synced(changesADT.local()),
+      }),
+  });
+
+/**
+ * Keep the native file watcher in line with the local projects and mark a project as locally
+ * changed whenever files in its directory change.
+ */
+export const useProjectWatcher = () => {
+  const { projectRepository } = useGlobalContext();
+  const projects = useProperty(projectRepository.projects);
+
+  const watched = React.useMemo(
+    () =>
+      pipe(
+        projects,
+        array.filterMap(projectPrism.local.getOption),
+        array.map(({ value: { projectId, basePath } }) => ({ projectId, basePath })),
+      ),
+    [projects],
+  );
+
+  React.useEffect(() => {
+    void pipe(
+      api.settingRead('projectDir', iots.string),
+      taskOption.chainTaskK((rootDir) =>
+        pipe(
+          api.watchProjects(rootDir, watched),
+          taskEither.match(
+            (e) => console.debug(`[useProjectWatcher] Could not watch projects: ${e.message}`),
+            () => undefined,
+          ),
+        ),
+      ),
+    )();
+  }, [watched]);
+
+  React.useEffect(
+    () => () => {
+      void pipe(
+        api.unwatchProjects,
+        taskEither.match(
+          (e) => console.debug(`[useProjectWatcher] Could not unwatch projects: ${e.message}`),
+          () => undefined,
+        ),
+      )();
+    },
+    [],
+  );
+
+  React.useEffect(() => {
+    const unlisten = listen<ProjectChanged>('project-changed', ({ payload: { projectId } }) => {
+      void pipe(
+        projectRepository.getProject(projectId),
+        taskOption.chainOptionK(projectPrism.local.getOption),
+        taskOption.chainTaskK(({ value }) =>
+          projectRepository.upsertOne(
+            projectADT.local({ ...value, syncState: withLocalChanges(value.syncState) }),
+          ),
+        ),
+        task.map(() => undefined),
+      )();
+    });
+    return () => {
+      void unlisten.then((f) => f());
+    };
+  }, [projectRepository]);
+};
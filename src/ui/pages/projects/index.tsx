@@ -24,7 +24,9 @@ import ProjectEventStatus from '@/ui/pages/projects/components/ProjectEventStatu
 import { ProjectList } from '@/ui/pages/projects/components/ProjectList';
 import { projectsByExercise } from '@/ui/pages/projects/components/ProjectList/model/Exercise';
 import { useProjectOpen } from '@/ui/pages/projects/hooks/useProjectOpen';
+import { useProjectPreservedFiles } from '@/ui/pages/projects/hooks/useProjectPreservedFiles';
 import { useProjectSync } from '@/ui/pages/projects/hooks/useProjectSync';
+import { useProjectWatcher } from '@/ui/pages/projects/hooks/useProjectWatcher';
 import { routes, useRoute } from '@/ui/routes';
 import { panic } from '@/utils/error';
 import { useProjectEventUpdate } from './hooks/useProjectEventUpdate';
@@ -37,6 +39,7 @@ export function Projects({ clientId, course }: { clientId: ClientId; course: Cou
   );
   const openProject = useProjectOpen();
   const syncProject = useProjectSync();
+  const preservedFiles = useProjectPreservedFiles();
   const { navigateTo } = useRoute();
 
   const goOverview = () => {
@@ -44,6 +47,7 @@ export function Projects({ clientId, course }: { clientId: ClientId; course: Cou
   };
 
   const sseStatus = useProjectEventUpdate(projectRepository.fetchChanges, clientId);
+  useProjectWatcher();
 
   return (
     <PageLayout>
@@ -64,7 +68,7 @@ export function Projects({ clientId, course }: { clientId: ClientId; course: Cou
                 openProject,
                 taskEither.fromTaskOption(() => 'Could not open project'),
               )}
-              onSync={(projectId, force) =>
+              onSync={(projectId, options) =>
                 pipe(
                   online,
                   boolean.fold(
@@ -78,7 +82,7 @@ export function Projects({ clientId, course }: { clientId: ClientId; course: Cou
                         taskEither.fromTaskOption(() => {
                           panic('Project to sync not found');
                         }),
-                        taskEither.chainFirst((project) => syncProject(project, { force })),
+                        taskEither.chainFirst((project) => syncProject(project, options)),
                         taskEither.map(constVoid),
                       ),
                   ),
@@ -96,6 +100,8 @@ export function Projects({ clientId, course }: { clientId: ClientId; course: Cou
                   ),
                 )
               }
+              onListPreserved={preservedFiles.list}
+              onRestorePreserved={preservedFiles.restore}
             />
           )),
         ),
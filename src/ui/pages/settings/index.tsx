@@ -25,7 +25,15 @@ const SettingsDiv = styled('div', ({ tokens }) => ({
   padding: tokens.padding,
 }));
 
-function SettingsInner({ projectDir, userInfo }: { projectDir: string; userInfo: UserInfo }) {
+function SettingsInner({
+  projectDir,
+  projectHistory,
+  userInfo,
+}: {
+  projectDir: string;
+  projectHistory: boolean;
+  userInfo: UserInfo;
+}) {
   const { navigateTo } = useRoute();
   const [form] = Form.useForm();
 
@@ -42,6 +50,13 @@ function SettingsInner({ projectDir, userInfo }: { projectDir: string; userInfo:
     }
   };
 
+  const toggleProjectHistory = async () => {
+    const enabled = !form.getFieldValue('projectHistory');
+    form.setFieldsValue({ projectHistory: enabled });
+    await api.settingWrite('projectHistory', enabled)();
+    void message.success('Saved the settings');
+  };
+
   const logout = () => {
     navigateTo(routes.logout());
   };
@@ -56,7 +71,7 @@ function SettingsInner({ projectDir, userInfo }: { projectDir: string; userInfo:
         <Form
           requiredMark={false}
           form={form}
-          initialValues={{ projectDir, userName: userInfo.userName }}
+          initialValues={{ projectDir, projectHistory, userName: userInfo.userName }}
         >
           <Form.Item dependencies={['userName']}>
             {({ getFieldValue }) => (
@@ -97,6 +112,24 @@ function SettingsInner({ projectDir, userInfo }: { projectDir: string; userInfo:
               />
             )}
           </Form.Item>
+          <Form.Item dependencies={['projectHistory']}>
+            {({ getFieldValue }) => (
+              <EditableCard
+                iconName="code-branch"
+                title="Version history"
+                description="Commit each project to a local git repository after every sync"
+                value={getFieldValue('projectHistory') ? 'On' : 'Off'}
+                actions={[
+                  {
+                    name: getFieldValue('projectHistory') ? 'Turn off' : 'Turn on',
+                    iconName: getFieldValue('projectHistory') ? 'toggle-off' : 'toggle-on',
+                    type: 'link',
+                    onClick: toggleProjectHistory,
+                  },
+                ]}
+              />
+            )}
+          </Form.Item>
         </Form>
       </SettingsDiv>
       <Version />
@@ -106,16 +139,22 @@ function SettingsInner({ projectDir, userInfo }: { projectDir: string; userInfo:
 
 export function Settings() {
   const projectDirRD = useSettingsFallback('projectDir', iots.string, '', []);
+  const projectHistoryRD = useSettingsFallback('projectHistory', iots.boolean, false, []);
   const userInfoRD = useUserInfo();
 
   return (
     <GuardRemote
       value={remote.sequenceS({
         projectDir: projectDirRD,
+        projectHistory: projectHistoryRD,
         userInfo: userInfoRD,
       })}
-      render={({ projectDir, userInfo }) => (
-        <SettingsInner userInfo={userInfo} projectDir={projectDir} />
+      render={({ projectDir, projectHistory, userInfo }) => (
+        <SettingsInner
+          userInfo={userInfo}
+          projectDir={projectDir}
+          projectHistory={projectHistory}
+        />
       )}
     />
   );
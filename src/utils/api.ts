@@ -96,6 +96,19 @@ export const apiPostSigned = <A>({
     task.chain((token) => apiPost({ ...options, token })),
   );
 
+/** Decode the answer to a request sent by a command like those of `apiPost`. */
+export const decodeApiResponse =
+  <A>(codec: iots.Type<A, unknown>) =>
+  ({ status, body }: { status: number; body: unknown }): either.Either<ApiError, A> =>
+    fromHttpError(
+      pipe(
+        status >= 200 && status < 300
+          ? pipe(codec.decode(body), either.map((a) => either.right<ResponseError, A>(a)))
+          : pipe(ResponseError.decode(body), either.map((e) => either.left<ResponseError, A>(e))),
+        either.mapLeft(flow(iots.formatValidationErrors, httpError.invalidPayload)),
+      ),
+    );
+
 // -------------------------------------------------------------------------------------------------
 
 export type ApiError =